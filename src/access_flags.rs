@@ -0,0 +1,387 @@
+use class_file::*;
+
+//typed sets over the raw access_flags, the structs keep the raw u16 so unknown bits still round trip
+
+pub struct ClassAccessFlags { bits: u16 }
+pub struct FieldAccessFlags { bits: u16 }
+pub struct MethodAccessFlags { bits: u16 }
+pub struct InnerClassAccessFlags { bits: u16 }
+
+//contains, each, acc_names and to_str for a set over the flags its flag list names, in that order
+macro_rules! flag_set(
+    ($set:ident, $flag:ident, $flags:ident) => (
+        impl $set {
+            pub fn contains(&self, flag: $flag) -> bool { self.bits & flag as u16 != 0 }
+
+            pub fn each(&self, f: fn($flag) -> bool) {
+                for $flags().each |flag| {
+                    if self.contains(*flag) && !f(*flag) {
+                        break;
+                    }
+                }
+            }
+
+            pub fn acc_names(&self) -> ~[&static/str] {
+                let mut names: ~[&static/str] = ~[];
+                for self.each |flag| {
+                    names.push(flag.acc_name());
+                }
+                names
+            }
+
+            //e.g. public final
+            pub fn to_str(&self) -> ~str {
+                let mut keywords: ~[&static/str] = ~[];
+                for self.each |flag| {
+                    for flag.keyword().each |keyword| { keywords.push(*keyword); }
+                }
+                modifiers_to_str(keywords)
+            }
+        }
+    )
+)
+
+flag_set!(ClassAccessFlags, ClassAccessFlag, class_flags)
+flag_set!(FieldAccessFlags, FieldAccessFlag, field_flags)
+flag_set!(MethodAccessFlags, MethodAccessFlag, method_flags)
+flag_set!(InnerClassAccessFlags, InnerClassAccessFlag, inner_class_flags)
+
+impl ClassAccessFlag {
+    pub fn acc_name(&self) -> &static/str {
+        match *self {
+            ClassAccess_Public => "ACC_PUBLIC",
+            ClassAccess_Static => "ACC_STATIC",
+            ClassAccess_Final => "ACC_FINAL",
+            ClassAccess_Super => "ACC_SUPER",
+            ClassAccess_Interface => "ACC_INTERFACE",
+            ClassAccess_Abstract => "ACC_ABSTRACT",
+            ClassAccess_Synthetic => "ACC_SYNTHETIC",
+            ClassAccess_Annotation => "ACC_ANNOTATION",
+            ClassAccess_Enum => "ACC_ENUM",
+            ClassAccess_Module => "ACC_MODULE"
+        }
+    }
+
+    //the Java source modifier, if the flag has one
+    pub fn keyword(&self) -> Option<&static/str> {
+        match *self {
+            ClassAccess_Public => Some("public"),
+            ClassAccess_Static => Some("static"),
+            ClassAccess_Final => Some("final"),
+            ClassAccess_Abstract => Some("abstract"),
+            _ => None
+        }
+    }
+}
+
+impl FieldAccessFlag {
+    pub fn acc_name(&self) -> &static/str {
+        match *self {
+            FieldAccess_Public => "ACC_PUBLIC",
+            FieldAccess_Private => "ACC_PRIVATE",
+            FieldAccess_Protected => "ACC_PROTECTED",
+            FieldAccess_Static => "ACC_STATIC",
+            FieldAccess_Final => "ACC_FINAL",
+            FieldAccess_Volatile => "ACC_VOLATILE",
+            FieldAccess_Transient => "ACC_TRANSIENT",
+            FieldAccess_Synthetic => "ACC_SYNTHETIC",
+            FieldAccess_Enum => "ACC_ENUM"
+        }
+    }
+
+    pub fn keyword(&self) -> Option<&static/str> {
+        match *self {
+            FieldAccess_Public => Some("public"),
+            FieldAccess_Private => Some("private"),
+            FieldAccess_Protected => Some("protected"),
+            FieldAccess_Static => Some("static"),
+            FieldAccess_Final => Some("final"),
+            FieldAccess_Volatile => Some("volatile"),
+            FieldAccess_Transient => Some("transient"),
+            _ => None
+        }
+    }
+}
+
+impl MethodAccessFlag {
+    pub fn acc_name(&self) -> &static/str {
+        match *self {
+            MethodAccess_Public => "ACC_PUBLIC",
+            MethodAccess_Private => "ACC_PRIVATE",
+            MethodAccess_Protected => "ACC_PROTECTED",
+            MethodAccess_Static => "ACC_STATIC",
+            MethodAccess_Final => "ACC_FINAL",
+            MethodAccess_Synchronized => "ACC_SYNCHRONIZED",
+            MethodAccess_Bridge => "ACC_BRIDGE",
+            MethodAccess_VarArgs => "ACC_VARARGS",
+            MethodAccess_Native => "ACC_NATIVE",
+            MethodAccess_Abstract => "ACC_ABSTRACT",
+            MethodAccess_Strict => "ACC_STRICT",
+            MethodAccess_Synthetic => "ACC_SYNTHETIC"
+        }
+    }
+
+    pub fn keyword(&self) -> Option<&static/str> {
+        match *self {
+            MethodAccess_Public => Some("public"),
+            MethodAccess_Private => Some("private"),
+            MethodAccess_Protected => Some("protected"),
+            MethodAccess_Static => Some("static"),
+            MethodAccess_Final => Some("final"),
+            MethodAccess_Synchronized => Some("synchronized"),
+            MethodAccess_Native => Some("native"),
+            MethodAccess_Abstract => Some("abstract"),
+            MethodAccess_Strict => Some("strictfp"),
+            _ => None
+        }
+    }
+}
+
+impl InnerClassAccessFlag {
+    pub fn acc_name(&self) -> &static/str {
+        match *self {
+            InnerClassAccess_Public => "ACC_PUBLIC",
+            InnerClassAccess_Private => "ACC_PRIVATE",
+            InnerClassAccess_Protected => "ACC_PROTECTED",
+            InnerClassAccess_Static => "ACC_STATIC",
+            InnerClassAccess_Final => "ACC_FINAL",
+            InnerClassAccess_Interface => "ACC_INTERFACE",
+            InnerClassAccess_Abstract => "ACC_ABSTRACT",
+            InnerClassAccess_Synthetic => "ACC_SYNTHETIC",
+            InnerClassAccess_Annotation => "ACC_ANNOTATION",
+            InnerClassAccess_Enum => "ACC_ENUM"
+        }
+    }
+
+    pub fn keyword(&self) -> Option<&static/str> {
+        match *self {
+            InnerClassAccess_Public => Some("public"),
+            InnerClassAccess_Private => Some("private"),
+            InnerClassAccess_Protected => Some("protected"),
+            InnerClassAccess_Static => Some("static"),
+            InnerClassAccess_Final => Some("final"),
+            InnerClassAccess_Abstract => Some("abstract"),
+            _ => None
+        }
+    }
+}
+
+fn class_flags() -> ~[ClassAccessFlag] {
+    ~[ClassAccess_Public, ClassAccess_Static, ClassAccess_Final, ClassAccess_Super, ClassAccess_Interface,
+      ClassAccess_Abstract, ClassAccess_Synthetic, ClassAccess_Annotation, ClassAccess_Enum, ClassAccess_Module]
+}
+
+fn field_flags() -> ~[FieldAccessFlag] {
+    ~[FieldAccess_Public, FieldAccess_Private, FieldAccess_Protected, FieldAccess_Static, FieldAccess_Final,
+      FieldAccess_Volatile, FieldAccess_Transient, FieldAccess_Synthetic, FieldAccess_Enum]
+}
+
+fn method_flags() -> ~[MethodAccessFlag] {
+    ~[MethodAccess_Public, MethodAccess_Private, MethodAccess_Protected, MethodAccess_Static, MethodAccess_Final,
+      MethodAccess_Synchronized, MethodAccess_Bridge, MethodAccess_VarArgs, MethodAccess_Native,
+      MethodAccess_Abstract, MethodAccess_Strict, MethodAccess_Synthetic]
+}
+
+fn inner_class_flags() -> ~[InnerClassAccessFlag] {
+    ~[InnerClassAccess_Public, InnerClassAccess_Private, InnerClassAccess_Protected, InnerClassAccess_Static,
+      InnerClassAccess_Final, InnerClassAccess_Interface, InnerClassAccess_Abstract, InnerClassAccess_Synthetic,
+      InnerClassAccess_Annotation, InnerClassAccess_Enum]
+}
+
+impl ClassAccessFlags {
+    //JVMS 4.1
+    pub fn validate(&self) -> Result<(), ~str> {
+        if self.contains(ClassAccess_Module) {
+            if self.bits != ClassAccess_Module as u16 {
+                return Err(~"ACC_MODULE with other flags");
+            }
+            return Ok(());
+        }
+        if self.contains(ClassAccess_Interface) {
+            if !self.contains(ClassAccess_Abstract) {
+                return Err(~"ACC_INTERFACE without ACC_ABSTRACT");
+            }
+            if self.contains(ClassAccess_Final) || self.contains(ClassAccess_Super) ||
+                    self.contains(ClassAccess_Enum) {
+                return Err(~"ACC_INTERFACE with ACC_FINAL, ACC_SUPER or ACC_ENUM");
+            }
+        } else {
+            if self.contains(ClassAccess_Annotation) {
+                return Err(~"ACC_ANNOTATION without ACC_INTERFACE");
+            }
+            if self.contains(ClassAccess_Final) && self.contains(ClassAccess_Abstract) {
+                return Err(~"ACC_FINAL with ACC_ABSTRACT");
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FieldAccessFlags {
+    //JVMS 4.5
+    pub fn validate(&self, in_interface: bool) -> Result<(), ~str> {
+        if !at_most_one([self.contains(FieldAccess_Public), self.contains(FieldAccess_Private),
+                self.contains(FieldAccess_Protected)]) {
+            return Err(~"more than one of ACC_PUBLIC, ACC_PRIVATE and ACC_PROTECTED");
+        }
+        if self.contains(FieldAccess_Final) && self.contains(FieldAccess_Volatile) {
+            return Err(~"ACC_FINAL with ACC_VOLATILE");
+        }
+        if in_interface {
+            let required = FieldAccess_Public as u16 | FieldAccess_Static as u16 | FieldAccess_Final as u16;
+            if self.bits & required != required {
+                return Err(~"interface field without ACC_PUBLIC, ACC_STATIC and ACC_FINAL");
+            }
+            if self.bits & !(required | FieldAccess_Synthetic as u16) != 0 {
+                return Err(~"interface field with flags other than ACC_PUBLIC, ACC_STATIC, ACC_FINAL \
+                    and ACC_SYNTHETIC");
+            }
+        }
+        Ok(())
+    }
+}
+
+impl MethodAccessFlags {
+    //JVMS 4.6, some rules depend on the method name and the class file major version
+    pub fn validate(&self, in_interface: bool, name: &str, major_version: u16) -> Result<(), ~str> {
+        //other flags on a class initializer are ignored
+        if name == "<clinit>" {
+            if major_version >= 51 && !self.contains(MethodAccess_Static) {
+                return Err(~"<clinit> without ACC_STATIC");
+            }
+            return Ok(());
+        }
+        if !at_most_one([self.contains(MethodAccess_Public), self.contains(MethodAccess_Private),
+                self.contains(MethodAccess_Protected)]) {
+            return Err(~"more than one of ACC_PUBLIC, ACC_PRIVATE and ACC_PROTECTED");
+        }
+        if in_interface {
+            if self.contains(MethodAccess_Protected) || self.contains(MethodAccess_Final) ||
+                    self.contains(MethodAccess_Synchronized) || self.contains(MethodAccess_Native) {
+                return Err(~"interface method with ACC_PROTECTED, ACC_FINAL, ACC_SYNCHRONIZED or ACC_NATIVE");
+            }
+            if major_version < 52 {
+                if !self.contains(MethodAccess_Public) || !self.contains(MethodAccess_Abstract) {
+                    return Err(~"interface method without ACC_PUBLIC and ACC_ABSTRACT");
+                }
+            } else if !self.contains(MethodAccess_Public) && !self.contains(MethodAccess_Private) {
+                return Err(~"interface method without ACC_PUBLIC or ACC_PRIVATE");
+            }
+        }
+        if self.contains(MethodAccess_Abstract) {
+            if self.contains(MethodAccess_Private) || self.contains(MethodAccess_Static) ||
+                    self.contains(MethodAccess_Final) || self.contains(MethodAccess_Synchronized) ||
+                    self.contains(MethodAccess_Native) {
+                return Err(~"ACC_ABSTRACT with ACC_PRIVATE, ACC_STATIC, ACC_FINAL, ACC_SYNCHRONIZED or ACC_NATIVE");
+            }
+            if major_version >= 46 && major_version <= 60 && self.contains(MethodAccess_Strict) {
+                return Err(~"ACC_ABSTRACT with ACC_STRICT");
+            }
+        }
+        if name == "<init>" {
+            let allowed = MethodAccess_Public as u16 | MethodAccess_Private as u16 | MethodAccess_Protected as u16 |
+                MethodAccess_VarArgs as u16 | MethodAccess_Strict as u16 | MethodAccess_Synthetic as u16;
+            if self.bits & !allowed != 0 {
+                return Err(~"<init> with flags other than access, ACC_VARARGS, ACC_STRICT and ACC_SYNTHETIC");
+            }
+        }
+        Ok(())
+    }
+}
+
+impl InnerClassAccessFlags {
+    //JVMS 4.7.6, the class rules of 4.1 apply to the inner class, which also has at most one access level
+    pub fn validate(&self) -> Result<(), ~str> {
+        if !at_most_one([self.contains(InnerClassAccess_Public), self.contains(InnerClassAccess_Private),
+                self.contains(InnerClassAccess_Protected)]) {
+            return Err(~"more than one of ACC_PUBLIC, ACC_PRIVATE and ACC_PROTECTED");
+        }
+        if self.contains(InnerClassAccess_Interface) {
+            if !self.contains(InnerClassAccess_Abstract) {
+                return Err(~"ACC_INTERFACE without ACC_ABSTRACT");
+            }
+            if self.contains(InnerClassAccess_Final) || self.contains(InnerClassAccess_Enum) {
+                return Err(~"ACC_INTERFACE with ACC_FINAL or ACC_ENUM");
+            }
+        } else {
+            if self.contains(InnerClassAccess_Annotation) {
+                return Err(~"ACC_ANNOTATION without ACC_INTERFACE");
+            }
+            if self.contains(InnerClassAccess_Final) && self.contains(InnerClassAccess_Abstract) {
+                return Err(~"ACC_FINAL with ACC_ABSTRACT");
+            }
+        }
+        Ok(())
+    }
+}
+
+//every access flag problem in the class, prefixed with where it is
+pub fn validate_class_file(class_file: &ClassFile) -> ~[~str] {
+    let cp = &class_file.constant_pool;
+    let mut errors: ~[~str] = ~[];
+    let class_flags = class_file.flags();
+    match class_flags.validate() {
+        Err(reason) => errors.push(~"class: " + reason),
+        Ok(()) => ()
+    }
+    let in_interface = class_flags.contains(ClassAccess_Interface);
+    for class_file.fields.each |field| {
+        match field.flags().validate(in_interface) {
+            Err(reason) => errors.push(fmt!("field %s: %s", utf8_or_index(cp, field.name_index), reason)),
+            Ok(()) => ()
+        }
+    }
+    for class_file.methods.each |method| {
+        let name = utf8_or_index(cp, method.name_index);
+        match method.flags().validate(in_interface, name, class_file.major_version) {
+            Err(reason) => errors.push(fmt!("method %s%s: %s", name, utf8_or_index(cp, method.descriptor_index),
+                reason)),
+            Ok(()) => ()
+        }
+    }
+    for class_file.attributes.each |attribute| {
+        match attribute.info {
+            AttrStruct_InnerClasses(ref classes) => for classes.each |class| {
+                match class.flags().validate() {
+                    Err(reason) => errors.push(fmt!("inner class %s: %s",
+                        class_name_or_index(cp, class.inner_class_info_index), reason)),
+                    Ok(()) => ()
+                }
+            },
+            _ => ()
+        }
+    }
+    errors
+}
+
+//names that are not utf8 constants show as their index
+fn utf8_or_index(cp: &ConstantPool, index: u16) -> ~str {
+    match cp.get_utf8(index) {
+        Some(strval) => str::from_slice(strval),
+        None => fmt!("#%u", index as uint)
+    }
+}
+
+fn class_name_or_index(cp: &ConstantPool, index: u16) -> ~str {
+    match cp.get_class_name(index) {
+        Some(name) => str::from_slice(name),
+        None => fmt!("#%u", index as uint)
+    }
+}
+
+fn at_most_one(set: &[bool]) -> bool {
+    vec::count(set, &true) <= 1
+}
+
+//in the order the Java Language Specification recommends
+fn modifiers_to_str(keywords: &[&static/str]) -> ~str {
+    let order = ["public", "protected", "private", "abstract", "static", "final", "transient", "volatile",
+        "synchronized", "native", "strictfp"];
+    let mut ordered: ~[&static/str] = ~[];
+    for order.each |keyword| {
+        if vec::contains(keywords, keyword) {
+            ordered.push(*keyword);
+        }
+    }
+    str::connect_slices(ordered, " ")
+}
@@ -0,0 +1,375 @@
+use class_file::{ClassFile, ClassFormatError};
+use core::send_map::linear::LinearMap;
+
+//jar and zip archives read through the central directory, only stored and deflated entries are supported
+//and zip64 archives are rejected
+
+pub struct Archive {
+    bytes: ~[u8],
+    entries: ~[ArchiveEntry],
+    //entry name to its index in entries, the first of any duplicates
+    indices: LinearMap<~str, uint>,
+    //a bad manifest is kept as its error so the entries can still be read
+    manifest: Result<Option<Manifest>, ArchiveError>
+}
+
+pub struct ArchiveEntry {
+    name: ~str,
+    method: u16,
+    flags: u16,
+    crc32: u32,
+    compressed_size: u32,
+    uncompressed_size: u32,
+    local_header_offset: u32
+}
+
+pub struct Manifest {
+    main_attributes: ~[(~str, ~str)],
+    //per entry sections, keyed by their Name attribute
+    sections: ~[(~str, ~[(~str, ~str)])]
+}
+
+pub enum ArchiveError {
+    ArchiveError_Io(~str /*reason*/),
+    ArchiveError_Invalid(uint /*offset*/, ~str /*reason*/),
+    ArchiveError_Unsupported(~str /*name*/, ~str /*reason*/),
+    ArchiveError_Corrupt(~str /*name*/, ~str /*reason*/),
+    ArchiveError_Class(~str /*name*/, ClassFormatError)
+}
+
+impl ArchiveError {
+    pub fn to_str(&self) -> ~str {
+        match *self {
+            ArchiveError_Io(ref reason) => copy *reason,
+            ArchiveError_Invalid(offset, ref reason) => fmt!("invalid archive at offset %u: %s", offset, *reason),
+            ArchiveError_Unsupported(ref name, ref reason) => fmt!("%s: unsupported %s", *name, *reason),
+            ArchiveError_Corrupt(ref name, ref reason) => fmt!("%s: %s", *name, *reason),
+            ArchiveError_Class(ref name, ref err) => fmt!("%s: %s", *name, err.to_str())
+        }
+    }
+}
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x04034b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x06054b50;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+
+const VERSIONS_PREFIX: &static/str = "META-INF/versions/";
+
+impl Archive {
+    pub fn find(&self, name: &str) -> Option<&self/ArchiveEntry> {
+        match self.indices.find(&str::from_slice(name)) {
+            Some(index) => Some(&self.entries[*index]),
+            None => None
+        }
+    }
+
+    pub fn read(&self, entry: &ArchiveEntry) -> Result<~[u8], ArchiveError> {
+        if entry.flags & 1 != 0 {
+            return Err(ArchiveError_Unsupported(copy entry.name, ~"encryption"));
+        }
+        let offset = entry.local_header_offset as uint;
+        if offset + 30 > self.bytes.len() || le_u32(self.bytes, offset) != LOCAL_HEADER_SIGNATURE {
+            return Err(ArchiveError_Invalid(offset, ~"bad local file header"));
+        }
+        //the local name and extra lengths can differ from the central directory ones
+        let start = offset + 30 + le_u16(self.bytes, offset + 26) as uint + le_u16(self.bytes, offset + 28) as uint;
+        let end = start + entry.compressed_size as uint;
+        if end > self.bytes.len() {
+            return Err(ArchiveError_Invalid(offset, ~"entry data past end of archive"));
+        }
+        let data = vec::view(self.bytes, start, end);
+        let bytes = if entry.method == METHOD_STORED {
+            vec::from_slice(data)
+        } else if entry.method == METHOD_DEFLATED {
+            match inflate::inflate(data) {
+                Ok(bytes) => bytes,
+                Err(reason) => return Err(ArchiveError_Corrupt(copy entry.name, reason))
+            }
+        } else {
+            return Err(ArchiveError_Unsupported(copy entry.name, fmt!("compression method %u",
+                entry.method as uint)));
+        };
+        if bytes.len() != entry.uncompressed_size as uint {
+            return Err(ArchiveError_Corrupt(copy entry.name, ~"uncompressed size mismatch"));
+        }
+        if crc32(bytes) != entry.crc32 {
+            return Err(ArchiveError_Corrupt(copy entry.name, ~"crc-32 mismatch"));
+        }
+        Ok(bytes)
+    }
+
+    pub fn read_class(&self, entry: &ArchiveEntry) -> Result<ClassFile, ArchiveError> {
+        let bytes = match self.read(entry) {
+            Ok(bytes) => bytes,
+            Err(err) => return Err(err)
+        };
+        match io::with_bytes_reader(bytes, |reader| class_file::ClassFile(reader)) {
+            Ok(class_file) => Ok(class_file),
+            Err(err) => Err(ArchiveError_Class(copy entry.name, err))
+        }
+    }
+
+    pub fn manifest(&self) -> Result<Option<&self/Manifest>, ArchiveError> {
+        match self.manifest {
+            Ok(Some(ref manifest)) => Ok(Some(manifest)),
+            Ok(None) => Ok(None),
+            Err(ref err) => Err(copy *err)
+        }
+    }
+
+    pub fn main_class(&self) -> Option<&self/str> {
+        match self.manifest {
+            Ok(Some(ref manifest)) => manifest.get("Main-Class"),
+            _ => None
+        }
+    }
+
+    //an unreadable manifest is treated as a plain jar
+    pub fn is_multi_release(&self) -> bool {
+        match self.manifest {
+            Ok(Some(ref manifest)) => match manifest.get("Multi-Release") {
+                Some(value) => str::eq_slice(str::trim(value), "true"),
+                None => false
+            },
+            _ => false
+        }
+    }
+
+    //the entry for an internal class name as seen by the given Java feature release, which for a multi-release
+    //jar is the highest META-INF/versions/N/ copy with N <= release before falling back to the root one
+    pub fn class_entry(&self, internal_name: &str, release: uint) -> Option<&self/ArchiveEntry> {
+        if self.is_multi_release() {
+            let mut version = release;
+            while version >= 9 {
+                let name = fmt!("%s%u/%s.class", VERSIONS_PREFIX, version, internal_name);
+                match self.find(name) {
+                    Some(entry) => return Some(entry),
+                    None => ()
+                }
+                version -= 1;
+            }
+        }
+        self.find(internal_name + ~".class")
+    }
+
+    //each class visible to the given release once, multi-release copies replacing the root ones
+    pub fn each_class_entry(&self, release: uint, f: fn(&str, &ArchiveEntry) -> bool) {
+        let multi_release = self.is_multi_release();
+        let mut seen = LinearMap();
+        for self.entries.each |entry| {
+            if !entry.name.ends_with(".class") {
+                loop;
+            }
+            let internal_name = match versioned_name(entry.name) {
+                Some((version, name)) => {
+                    if !multi_release || version > release {
+                        loop;
+                    }
+                    name
+                },
+                None => str::from_slice(entry.name)
+            };
+            let internal_name = str::slice(internal_name, 0, internal_name.len() - 6);
+            if !seen.insert(copy internal_name, ()) {
+                loop;
+            }
+            match self.class_entry(internal_name, release) {
+                Some(chosen) => if !f(internal_name, chosen) { break; },
+                None => ()
+            }
+        }
+    }
+
+    pub fn each_class(&self, release: uint, f: fn(&str, Result<ClassFile, ArchiveError>) -> bool) {
+        for self.each_class_entry(release) |internal_name, entry| {
+            if !f(internal_name, self.read_class(entry)) {
+                break;
+            }
+        }
+    }
+}
+
+impl Manifest {
+    //attribute names are case insensitive
+    pub fn get(&self, name: &str) -> Option<&self/str> {
+        find_attribute(self.main_attributes, name)
+    }
+
+    pub fn get_for_entry(&self, entry_name: &str, name: &str) -> Option<&self/str> {
+        for self.sections.each |section| {
+            let (ref section_name, ref attributes) = *section;
+            if str::eq_slice(*section_name, entry_name) {
+                return find_attribute(*attributes, name);
+            }
+        }
+        None
+    }
+}
+
+fn find_attribute(attributes: &r/[(~str, ~str)], name: &str) -> Option<&r/str> {
+    let name = str::to_lower(name);
+    for attributes.each |attribute| {
+        let (ref key, ref value) = *attribute;
+        if str::to_lower(*key) == name {
+            return Some(str::view(*value, 0, value.len()));
+        }
+    }
+    None
+}
+
+pub fn open_archive(path: &Path) -> Result<Archive, ArchiveError> {
+    match io::read_whole_file(path) {
+        Ok(bytes) => Archive(bytes),
+        Err(reason) => Err(ArchiveError_Io(reason))
+    }
+}
+
+pub fn Archive(bytes: ~[u8]) -> Result<Archive, ArchiveError> {
+    let entries = try!(read_central_directory(bytes));
+    let mut indices = LinearMap();
+    for entries.eachi |index, entry| {
+        if !indices.contains_key(&entry.name) {
+            indices.insert(copy entry.name, index);
+        }
+    }
+    let mut archive = Archive { bytes: bytes, entries: entries, indices: indices, manifest: Ok(None) };
+    archive.manifest = read_manifest(&archive);
+    Ok(archive)
+}
+
+fn read_manifest(archive: &Archive) -> Result<Option<Manifest>, ArchiveError> {
+    match archive.find("META-INF/MANIFEST.MF") {
+        Some(entry) => {
+            let bytes = try!(archive.read(entry));
+            if !str::is_utf8(bytes) {
+                return Err(ArchiveError_Invalid(entry.local_header_offset as uint, ~"manifest is not utf-8"));
+            }
+            Ok(Some(Manifest(str::from_bytes(bytes))))
+        },
+        None => Ok(None)
+    }
+}
+
+fn read_central_directory(bytes: &[u8]) -> Result<~[ArchiveEntry], ArchiveError> {
+    //the end record is 22 bytes followed by a comment of up to 65535
+    if bytes.len() < 22 {
+        return Err(ArchiveError_Invalid(0, ~"too short for a zip archive"));
+    }
+    let mut end = bytes.len() - 22;
+    let lowest = if end > 65535 { end - 65535 } else { 0 };
+    while le_u32(bytes, end) != END_OF_CENTRAL_DIRECTORY_SIGNATURE {
+        if end == lowest {
+            return Err(ArchiveError_Invalid(bytes.len(), ~"no end of central directory record"));
+        }
+        end -= 1;
+    }
+    let count = le_u16(bytes, end + 10) as uint;
+    let directory_offset = le_u32(bytes, end + 16);
+    if count == 0xFFFF || directory_offset == 0xFFFFFFFF {
+        return Err(ArchiveError_Invalid(end, ~"zip64 archives are not supported"));
+    }
+    let mut entries: ~[ArchiveEntry] = ~[];
+    vec::reserve(&mut entries, count);
+    let mut pos = directory_offset as uint;
+    for iter::repeat(count) {
+        if pos + 46 > bytes.len() || le_u32(bytes, pos) != CENTRAL_HEADER_SIGNATURE {
+            return Err(ArchiveError_Invalid(pos, ~"bad central directory header"));
+        }
+        let name_length = le_u16(bytes, pos + 28) as uint;
+        let extra_length = le_u16(bytes, pos + 30) as uint;
+        let comment_length = le_u16(bytes, pos + 32) as uint;
+        if pos + 46 + name_length > bytes.len() {
+            return Err(ArchiveError_Invalid(pos, ~"central directory name past end of archive"));
+        }
+        //names are utf-8 when flag bit 11 is set and cp437 otherwise, only the ascii subset the two share (and
+        //utf-8 that tools write without setting the flag) is accepted
+        let name = vec::view(bytes, pos + 46, pos + 46 + name_length);
+        if !str::is_utf8(name) {
+            return Err(ArchiveError_Invalid(pos + 46, ~"entry name is not utf-8"));
+        }
+        entries.push(ArchiveEntry {
+            name: str::from_bytes(name),
+            method: le_u16(bytes, pos + 10),
+            flags: le_u16(bytes, pos + 8),
+            crc32: le_u32(bytes, pos + 16),
+            compressed_size: le_u32(bytes, pos + 20),
+            uncompressed_size: le_u32(bytes, pos + 24),
+            local_header_offset: le_u32(bytes, pos + 42)
+        });
+        pos += 46 + name_length + extra_length + comment_length;
+    }
+    Ok(entries)
+}
+
+//META-INF/versions/N/name to (N, name)
+fn versioned_name(name: &str) -> Option<(uint, ~str)> {
+    if !name.starts_with(VERSIONS_PREFIX) {
+        return None;
+    }
+    let rest = str::slice(name, VERSIONS_PREFIX.len(), name.len());
+    match str::find_char(rest, '/') {
+        Some(slash) => match uint::from_str(str::slice(rest, 0, slash)) {
+            Some(version) if version >= 9 => Some((version, str::slice(rest, slash + 1, rest.len()))),
+            _ => None
+        },
+        None => None
+    }
+}
+
+//the jar manifest format: name: value lines, continuations start with a space, sections split by blank lines
+pub fn Manifest(text: &str) -> Manifest {
+    let mut sections: ~[~[(~str, ~str)]] = ~[~[]];
+    for str::lines_any(text).each |line| {
+        if line.is_empty() {
+            if !sections.last().is_empty() {
+                sections.push(~[]);
+            }
+        } else if line.starts_with(" ") {
+            let current = sections.len() - 1;
+            if !sections[current].is_empty() {
+                let last = sections[current].len() - 1;
+                let (key, value) = copy sections[current][last];
+                sections[current][last] = (key, value + str::slice(*line, 1, line.len()));
+            }
+        } else {
+            match str::find_char(*line, ':') {
+                Some(colon) => {
+                    let current = sections.len() - 1;
+                    sections[current].push((str::slice(*line, 0, colon),
+                        str::trim_left(str::slice(*line, colon + 1, line.len()))));
+                },
+                None => ()
+            }
+        }
+    }
+    let main_attributes = sections.shift();
+    let mut named: ~[(~str, ~[(~str, ~str)])] = ~[];
+    for sections.each |section| {
+        match find_attribute(*section, "Name") {
+            Some(name) => named.push((str::from_slice(name), copy *section)),
+            None => ()
+        }
+    }
+    Manifest { main_attributes: main_attributes, sections: named }
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+    for bytes.each |byte| {
+        crc ^= *byte as u32;
+        for iter::repeat(8) {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn le_u16(bytes: &[u8], pos: uint) -> u16 {
+    bytes[pos] as u16 | (bytes[pos + 1] as u16 << 8)
+}
+fn le_u32(bytes: &[u8], pos: uint) -> u32 {
+    bytes[pos] as u32 | (bytes[pos + 1] as u32 << 8) | (bytes[pos + 2] as u32 << 16) | (bytes[pos + 3] as u32 << 24)
+}
@@ -0,0 +1,589 @@
+use class_file::*;
+use bytecode::*;
+
+//in assembler input, the branch operands of an Instruction name labels rather than pcs
+pub type Label = uint;
+
+pub enum AsmItem {
+    Asm_Label(Label),
+    Asm_Insn(Instruction)
+}
+
+pub struct LabeledExceptionHandler {
+    start: Label,
+    end: Label,
+    handler: Label,
+    catch_type: u16
+}
+
+pub struct LabeledLineNumber {
+    start: Label,
+    line_number: u16
+}
+
+pub struct LabeledLocalVariable {
+    start: Label,
+    end: Label,
+    name_index: u16,
+    //descriptor_index for LocalVariableTable, signature_index for LocalVariableTypeTable
+    type_index: u16,
+    index: u16
+}
+
+pub struct LabeledCode {
+    items: ~[AsmItem],
+    exception_table: ~[LabeledExceptionHandler],
+    line_numbers: ~[LabeledLineNumber],
+    local_variables: ~[LabeledLocalVariable],
+    local_variable_types: ~[LabeledLocalVariable],
+    mut label_count: uint
+}
+
+impl LabeledCode {
+    pub fn new_label(&self) -> Label {
+        let label = self.label_count;
+        self.label_count += 1;
+        label
+    }
+}
+
+pub struct AssembledCode {
+    code: ~[u8],
+    exception_table: ~[ExceptionTableInfo],
+    line_numbers: ~[LineNumberTableInfo],
+    local_variables: ~[LocalVariableTableInfo],
+    local_variable_types: ~[LocalVariableTypeTableInfo]
+}
+
+pub enum AssembleError {
+    AsmError_UndefinedLabel(Label),
+    AsmError_DuplicateLabel(Label),
+    AsmError_CodeTooLarge(uint /*length*/),
+    //an exception handler or local variable range whose end label is placed before its start
+    AsmError_BadRange(Label /*start*/, Label /*end*/),
+    AsmError_ConstantPoolFull
+}
+
+impl AssembleError {
+    pub fn to_str(&self) -> ~str {
+        match *self {
+            AsmError_UndefinedLabel(label) => fmt!("undefined label %u", label),
+            AsmError_DuplicateLabel(label) => fmt!("label %u placed more than once", label),
+            AsmError_CodeTooLarge(length) => fmt!("code length %u exceeds 65535", length),
+            AsmError_BadRange(start, end) => fmt!("range from label %u to label %u is backwards", start, end),
+            AsmError_ConstantPoolFull => ~"no room in the constant pool for an attribute name"
+        }
+    }
+}
+
+//turns an existing Code attribute into labeled form, the label for a pc is the pc
+//itself and code.len() labels the end of the code
+pub fn disassemble(code: &CodeAttributeInfo) -> Result<LabeledCode, ClassFormatError> {
+    let insns = try!(bytecode::decode(code.code));
+    let mut boundaries = vec::from_elem(code.code.len() + 1, false);
+    let mut referenced = vec::from_elem(code.code.len() + 1, false);
+    boundaries[code.code.len()] = true;
+    for insns.each |pair| {
+        let (pc, ref insn) = *pair;
+        boundaries[pc] = true;
+        for insn.branch_targets().each |target| {
+            referenced[*target] = true;
+        }
+    }
+
+    let mut exception_table: ~[LabeledExceptionHandler] = ~[];
+    for code.exception_table.each |entry| {
+        for (~[entry.start_pc, entry.end_pc, entry.handler_pc]).each |pc| {
+            try!(reference_pc(*pc as uint, boundaries, &mut referenced, "exception_table"));
+        }
+        exception_table.push(LabeledExceptionHandler {
+            start: entry.start_pc as Label,
+            end: entry.end_pc as Label,
+            handler: entry.handler_pc as Label,
+            catch_type: entry.catch_type
+        });
+    }
+
+    let mut line_numbers: ~[LabeledLineNumber] = ~[];
+    let mut local_variables: ~[LabeledLocalVariable] = ~[];
+    let mut local_variable_types: ~[LabeledLocalVariable] = ~[];
+    for code.attributes.each |attribute| {
+        match attribute.info {
+            AttrStruct_LineNumberTable(ref table) => {
+                for table.each |entry| {
+                    try!(reference_pc(entry.start_pc as uint, boundaries, &mut referenced, "LineNumberTable"));
+                    line_numbers.push(LabeledLineNumber {
+                        start: entry.start_pc as Label,
+                        line_number: entry.line_number
+                    });
+                }
+            },
+            AttrStruct_LocalVariableTable(ref table) => {
+                for table.each |entry| {
+                    let end = entry.start_pc as uint + entry.length as uint;
+                    try!(reference_pc(entry.start_pc as uint, boundaries, &mut referenced, "LocalVariableTable"));
+                    try!(reference_pc(end, boundaries, &mut referenced, "LocalVariableTable"));
+                    local_variables.push(LabeledLocalVariable {
+                        start: entry.start_pc as Label,
+                        end: end,
+                        name_index: entry.name_index,
+                        type_index: entry.descriptor_index,
+                        index: entry.index
+                    });
+                }
+            },
+            AttrStruct_LocalVariableTypeTable(ref table) => {
+                for table.each |entry| {
+                    let end = entry.start_pc as uint + entry.length as uint;
+                    try!(reference_pc(entry.start_pc as uint, boundaries, &mut referenced,
+                        "LocalVariableTypeTable"));
+                    try!(reference_pc(end, boundaries, &mut referenced, "LocalVariableTypeTable"));
+                    local_variable_types.push(LabeledLocalVariable {
+                        start: entry.start_pc as Label,
+                        end: end,
+                        name_index: entry.name_index,
+                        type_index: entry.signature_index,
+                        index: entry.index
+                    });
+                }
+            },
+            _ => ()
+        }
+    }
+
+    let mut items: ~[AsmItem] = ~[];
+    for insns.each |pair| {
+        let (pc, ref insn) = *pair;
+        if referenced[pc] {
+            items.push(Asm_Label(pc));
+        }
+        items.push(Asm_Insn(copy *insn));
+    }
+    if referenced[code.code.len()] {
+        items.push(Asm_Label(code.code.len()));
+    }
+    Ok(LabeledCode {
+        items: items,
+        exception_table: exception_table,
+        line_numbers: line_numbers,
+        local_variables: local_variables,
+        local_variable_types: local_variable_types,
+        label_count: code.code.len() + 1
+    })
+}
+
+fn reference_pc(pc: uint, boundaries: &[bool], referenced: &mut ~[bool], structure: &str)
+        -> Result<(), ClassFormatError> {
+    if pc >= boundaries.len() || !boundaries[pc] {
+        return Err(FormatError_Invalid(pc, str::from_slice(structure), ~"pc is not an instruction boundary"));
+    }
+    referenced[pc] = true;
+    Ok(())
+}
+
+pub fn assemble(labeled: &LabeledCode) -> Result<AssembledCode, AssembleError> {
+    let (label_pcs, long) = try!(layout(labeled));
+
+    let mut code: ~[u8] = ~[];
+    for labeled.items.eachi |i, item| {
+        match *item {
+            Asm_Insn(ref insn) => {
+                let pc = code.len();
+                encode_instruction(insn, pc, |label| label_pcs[label].get(), long[i], &mut code);
+            },
+            Asm_Label(_) => ()
+        }
+    }
+    if code.len() > 65535 {
+        return Err(AsmError_CodeTooLarge(code.len()));
+    }
+
+    let mut exception_table: ~[ExceptionTableInfo] = ~[];
+    for labeled.exception_table.each |entry| {
+        let start_pc = try!(label_pc(label_pcs, entry.start));
+        let end_pc = try!(label_pc(label_pcs, entry.end));
+        //the range is exclusive of end_pc so it may not be empty either
+        if end_pc <= start_pc {
+            return Err(AsmError_BadRange(entry.start, entry.end));
+        }
+        exception_table.push(ExceptionTableInfo {
+            start_pc: start_pc as u16,
+            end_pc: end_pc as u16,
+            handler_pc: try!(label_pc(label_pcs, entry.handler)) as u16,
+            catch_type: entry.catch_type
+        });
+    }
+    let mut line_numbers: ~[LineNumberTableInfo] = ~[];
+    for labeled.line_numbers.each |entry| {
+        line_numbers.push(LineNumberTableInfo {
+            start_pc: try!(label_pc(label_pcs, entry.start)) as u16,
+            line_number: entry.line_number
+        });
+    }
+    let mut local_variables: ~[LocalVariableTableInfo] = ~[];
+    for labeled.local_variables.each |entry| {
+        let (start_pc, length) = try!(label_range(label_pcs, entry));
+        local_variables.push(LocalVariableTableInfo {
+            start_pc: start_pc,
+            length: length,
+            name_index: entry.name_index,
+            descriptor_index: entry.type_index,
+            index: entry.index
+        });
+    }
+    let mut local_variable_types: ~[LocalVariableTypeTableInfo] = ~[];
+    for labeled.local_variable_types.each |entry| {
+        let (start_pc, length) = try!(label_range(label_pcs, entry));
+        local_variable_types.push(LocalVariableTypeTableInfo {
+            start_pc: start_pc,
+            length: length,
+            name_index: entry.name_index,
+            signature_index: entry.type_index,
+            index: entry.index
+        });
+    }
+    Ok(AssembledCode {
+        code: code,
+        exception_table: exception_table,
+        line_numbers: line_numbers,
+        local_variables: local_variables,
+        local_variable_types: local_variable_types
+    })
+}
+
+//assembles labeled code over an existing Code attribute, replacing the code, the exception table
+//and the line number and local variable tables, which are added when the original had none and
+//names for them are taken from or added to the constant pool. A StackMapTable and the Code's
+//type annotations, whose targets are pcs and exception table indices, no longer match the new
+//code so they are dropped and have to be recomputed by the caller. Other nested attributes are
+//kept as they are.
+pub fn rewrite(original: &CodeAttributeInfo, labeled: &LabeledCode, constant_pool: &mut ConstantPool)
+        -> Result<CodeAttributeInfo, AssembleError> {
+    let AssembledCode { code, exception_table, line_numbers, local_variables, local_variable_types } =
+        try!(assemble(labeled));
+    let mut attributes: ~[AttributeInfo] = ~[];
+    let mut line_numbers = Some(line_numbers);
+    let mut local_variables = Some(local_variables);
+    let mut local_variable_types = Some(local_variable_types);
+    //entries from multiple tables of the same kind are merged into the first one
+    for original.attributes.each |attribute| {
+        match attribute.info {
+            AttrStruct_LineNumberTable(_) => if line_numbers.is_some() {
+                attributes.push(line_number_attribute(attribute.attribute_name_index,
+                    option::swap_unwrap(&mut line_numbers)));
+            },
+            AttrStruct_LocalVariableTable(_) => if local_variables.is_some() {
+                attributes.push(local_variable_attribute(attribute.attribute_name_index,
+                    option::swap_unwrap(&mut local_variables)));
+            },
+            AttrStruct_LocalVariableTypeTable(_) => if local_variable_types.is_some() {
+                attributes.push(local_variable_type_attribute(attribute.attribute_name_index,
+                    option::swap_unwrap(&mut local_variable_types)));
+            },
+            AttrStruct_StackMapTable(_) | AttrStruct_RuntimeVisibleTypeAnnotations(_) |
+            AttrStruct_RuntimeInvisibleTypeAnnotations(_) => (),
+            _ => attributes.push(copy *attribute)
+        }
+    }
+    match line_numbers {
+        Some(table) => if !table.is_empty() {
+            let name_index = try!(attribute_name_index(constant_pool, "LineNumberTable"));
+            attributes.push(line_number_attribute(name_index, table));
+        },
+        None => ()
+    }
+    match local_variables {
+        Some(table) => if !table.is_empty() {
+            let name_index = try!(attribute_name_index(constant_pool, "LocalVariableTable"));
+            attributes.push(local_variable_attribute(name_index, table));
+        },
+        None => ()
+    }
+    match local_variable_types {
+        Some(table) => if !table.is_empty() {
+            let name_index = try!(attribute_name_index(constant_pool, "LocalVariableTypeTable"));
+            attributes.push(local_variable_type_attribute(name_index, table));
+        },
+        None => ()
+    }
+    Ok(CodeAttributeInfo {
+        max_stack: original.max_stack,
+        max_locals: original.max_locals,
+        code: code,
+        exception_table: exception_table,
+        attributes: attributes
+    })
+}
+
+fn attribute_name_index(constant_pool: &mut ConstantPool, name: &str) -> Result<u16, AssembleError> {
+    match constant_pool.utf8_index(name) {
+        Some(index) => Ok(index),
+        None => Err(AsmError_ConstantPoolFull)
+    }
+}
+
+fn line_number_attribute(name_index: u16, table: ~[LineNumberTableInfo]) -> AttributeInfo {
+    AttributeInfo {
+        attribute_name_index: name_index,
+        attribute_length: 2 + 4 * table.len() as u32,
+        info: AttrStruct_LineNumberTable(table)
+    }
+}
+
+fn local_variable_attribute(name_index: u16, table: ~[LocalVariableTableInfo]) -> AttributeInfo {
+    AttributeInfo {
+        attribute_name_index: name_index,
+        attribute_length: 2 + 10 * table.len() as u32,
+        info: AttrStruct_LocalVariableTable(table)
+    }
+}
+
+fn local_variable_type_attribute(name_index: u16, table: ~[LocalVariableTypeTableInfo]) -> AttributeInfo {
+    AttributeInfo {
+        attribute_name_index: name_index,
+        attribute_length: 2 + 10 * table.len() as u32,
+        info: AttrStruct_LocalVariableTypeTable(table)
+    }
+}
+
+//places every label, widening branches whose offsets do not fit in 16 bits until nothing changes.
+//Widening only ever grows the code so this terminates, and a branch once widened stays widened.
+fn layout(labeled: &LabeledCode) -> Result<(~[Option<uint>], ~[bool]), AssembleError> {
+    let mut label_count = labeled.label_count;
+    for labeled.items.each |item| {
+        match *item {
+            Asm_Label(label) if label >= label_count => label_count = label + 1,
+            _ => ()
+        }
+    }
+    let mut long = vec::from_elem(labeled.items.len(), false);
+    loop {
+        let mut label_pcs: ~[Option<uint>] = vec::from_elem(label_count, None);
+        let mut pc = 0u;
+        let mut item_pcs: ~[uint] = ~[];
+        for labeled.items.eachi |i, item| {
+            item_pcs.push(pc);
+            match *item {
+                Asm_Label(label) => {
+                    if label_pcs[label].is_some() {
+                        return Err(AsmError_DuplicateLabel(label));
+                    }
+                    label_pcs[label] = Some(pc);
+                },
+                Asm_Insn(ref insn) => pc += instruction_size(insn, pc, long[i])
+            }
+        }
+        for labeled.items.each |item| {
+            match *item {
+                Asm_Insn(ref insn) => for insn.branch_targets().each |label| {
+                    if *label >= label_count || label_pcs[*label].is_none() {
+                        return Err(AsmError_UndefinedLabel(*label));
+                    }
+                },
+                Asm_Label(_) => ()
+            }
+        }
+        let mut changed = false;
+        for labeled.items.eachi |i, item| {
+            match *item {
+                Asm_Insn(ref insn) if !long[i] => {
+                    if !fits_short(insn, item_pcs[i], |label| label_pcs[label].get()) {
+                        long[i] = true;
+                        changed = true;
+                    }
+                },
+                _ => ()
+            }
+        }
+        if !changed {
+            return Ok((label_pcs, long));
+        }
+    }
+}
+
+fn label_pc(label_pcs: &[Option<uint>], label: Label) -> Result<uint, AssembleError> {
+    if label >= label_pcs.len() || label_pcs[label].is_none() {
+        return Err(AsmError_UndefinedLabel(label));
+    }
+    Ok(label_pcs[label].get())
+}
+
+fn label_range(label_pcs: &[Option<uint>], entry: &LabeledLocalVariable) -> Result<(u16, u16), AssembleError> {
+    let start_pc = try!(label_pc(label_pcs, entry.start));
+    let end_pc = try!(label_pc(label_pcs, entry.end));
+    if end_pc < start_pc {
+        return Err(AsmError_BadRange(entry.start, entry.end));
+    }
+    Ok((start_pc as u16, (end_pc - start_pc) as u16))
+}
+
+fn short_branch_target(insn: &Instruction) -> Option<Label> {
+    match *insn {
+        Insn_Ifeq(target) | Insn_Ifne(target) | Insn_Iflt(target) | Insn_Ifge(target) |
+        Insn_Ifgt(target) | Insn_Ifle(target) | Insn_IfIcmpeq(target) | Insn_IfIcmpne(target) |
+        Insn_IfIcmplt(target) | Insn_IfIcmpge(target) | Insn_IfIcmpgt(target) | Insn_IfIcmple(target) |
+        Insn_IfAcmpeq(target) | Insn_IfAcmpne(target) | Insn_Ifnull(target) | Insn_Ifnonnull(target) |
+        Insn_Goto(target) | Insn_Jsr(target) => Some(target),
+        _ => None
+    }
+}
+
+fn fits_short(insn: &Instruction, pc: uint, resolve: fn(Label) -> uint) -> bool {
+    match short_branch_target(insn) {
+        Some(label) => {
+            let offset = resolve(label) as int - pc as int;
+            offset >= -32768 && offset <= 32767
+        },
+        None => true
+    }
+}
+
+fn instruction_size(insn: &Instruction, pc: uint, long: bool) -> uint {
+    //offsets do not change the size so every label can resolve to this pc
+    let mut scratch: ~[u8] = ~[];
+    encode_instruction(insn, pc, |_| pc, long, &mut scratch);
+    scratch.len()
+}
+
+//conditional branches that need a long offset become the inverted condition jumping over a goto_w
+fn inverted_opcode(opcode: u8) -> u8 {
+    match opcode {
+        153..166 => if (opcode - 153) % 2 == 0 { opcode + 1 } else { opcode - 1 },
+        198 => 199,
+        199 => 198,
+        _ => fail fmt!("Opcode %u is not a conditional branch", opcode as uint)
+    }
+}
+
+fn encode_instruction(insn: &Instruction, pc: uint, resolve: fn(Label) -> uint, long: bool, out: &mut ~[u8]) {
+    let opcode = insn.opcode();
+    match *insn {
+        Insn_Bipush(value) => {
+            out.push(opcode);
+            out.push(value as u8);
+        },
+        Insn_Sipush(value) => {
+            out.push(opcode);
+            push_u16(out, value as u16);
+        },
+        Insn_Ldc(index) => if index > 255 {
+            out.push(Insn_LdcW(index).opcode());
+            push_u16(out, index);
+        } else {
+            out.push(opcode);
+            out.push(index as u8);
+        },
+        Insn_LdcW(index) | Insn_Ldc2W(index) | Insn_Getstatic(index) | Insn_Putstatic(index) |
+        Insn_Getfield(index) | Insn_Putfield(index) | Insn_Invokevirtual(index) | Insn_Invokespecial(index) |
+        Insn_Invokestatic(index) | Insn_New(index) | Insn_Anewarray(index) | Insn_Checkcast(index) |
+        Insn_Instanceof(index) => {
+            out.push(opcode);
+            push_u16(out, index);
+        },
+        Insn_Iload(index) | Insn_Lload(index) | Insn_Fload(index) | Insn_Dload(index) | Insn_Aload(index) |
+        Insn_Istore(index) | Insn_Lstore(index) | Insn_Fstore(index) | Insn_Dstore(index) | Insn_Astore(index) |
+        Insn_Ret(index) => if index > 255 {
+            out.push(WIDE);
+            out.push(opcode);
+            push_u16(out, index);
+        } else {
+            out.push(opcode);
+            out.push(index as u8);
+        },
+        Insn_Iinc(index, value) => if index > 255 || value < -128 || value > 127 {
+            out.push(WIDE);
+            out.push(opcode);
+            push_u16(out, index);
+            push_u16(out, value as u16);
+        } else {
+            out.push(opcode);
+            out.push(index as u8);
+            out.push(value as u8);
+        },
+        Insn_Goto(label) | Insn_Jsr(label) if long => {
+            out.push(match *insn { Insn_Goto(_) => Insn_GotoW(0).opcode(), _ => Insn_JsrW(0).opcode() });
+            push_u32(out, (resolve(label) as int - pc as int) as u32);
+        },
+        Insn_GotoW(label) | Insn_JsrW(label) => {
+            out.push(opcode);
+            push_u32(out, (resolve(label) as int - pc as int) as u32);
+        },
+        Insn_Tableswitch(default, low, high, ref targets) => {
+            out.push(opcode);
+            push_switch_padding(out, pc);
+            push_u32(out, (resolve(default) as int - pc as int) as u32);
+            push_u32(out, low as u32);
+            push_u32(out, high as u32);
+            for targets.each |target| {
+                push_u32(out, (resolve(*target) as int - pc as int) as u32);
+            }
+        },
+        Insn_Lookupswitch(default, ref pairs) => {
+            out.push(opcode);
+            push_switch_padding(out, pc);
+            push_u32(out, (resolve(default) as int - pc as int) as u32);
+            push_u32(out, pairs.len() as u32);
+            for pairs.each |pair| {
+                let (key, target) = *pair;
+                push_u32(out, key as u32);
+                push_u32(out, (resolve(target) as int - pc as int) as u32);
+            }
+        },
+        Insn_Invokeinterface(index, count) => {
+            out.push(opcode);
+            push_u16(out, index);
+            out.push(count);
+            out.push(0);
+        },
+        Insn_Invokedynamic(index) => {
+            out.push(opcode);
+            push_u16(out, index);
+            out.push(0);
+            out.push(0);
+        },
+        Insn_Newarray(atype) => {
+            out.push(opcode);
+            out.push(atype);
+        },
+        Insn_Multianewarray(index, dimensions) => {
+            out.push(opcode);
+            push_u16(out, index);
+            out.push(dimensions);
+        },
+        _ => match short_branch_target(insn) {
+            Some(label) if long => {
+                //goto_w is 5 bytes, so skip 3 + 5 past it
+                out.push(inverted_opcode(opcode));
+                push_u16(out, 8);
+                out.push(Insn_GotoW(0).opcode());
+                push_u32(out, (resolve(label) as int - (pc + 3) as int) as u32);
+            },
+            Some(label) => {
+                out.push(opcode);
+                push_u16(out, (resolve(label) as int - pc as int) as u16);
+            },
+            None => out.push(opcode)
+        }
+    }
+}
+
+const WIDE: u8 = 196;
+
+fn push_switch_padding(out: &mut ~[u8], pc: uint) {
+    //pad so the operands start 4-byte aligned relative to the start of the code
+    for iter::repeat((4 - (pc + 1) % 4) % 4) {
+        out.push(0);
+    }
+}
+
+fn push_u16(out: &mut ~[u8], value: u16) {
+    out.push((value >> 8) as u8);
+    out.push(value as u8);
+}
+
+fn push_u32(out: &mut ~[u8], value: u32) {
+    out.push((value >> 24) as u8);
+    out.push((value >> 16) as u8);
+    out.push((value >> 8) as u8);
+    out.push(value as u8);
+}
@@ -0,0 +1,1006 @@
+use class_file::{ClassFormatError, FormatError_UnexpectedEof, FormatError_UnrecognizedTag, FormatError_Invalid};
+
+//branch operands are resolved to absolute pcs, local indices and constant pool
+//indices are widened to u16 so wide and non-wide forms decode the same
+pub enum Instruction {
+    Insn_Nop,
+    Insn_AconstNull,
+    Insn_IconstM1,
+    Insn_Iconst0,
+    Insn_Iconst1,
+    Insn_Iconst2,
+    Insn_Iconst3,
+    Insn_Iconst4,
+    Insn_Iconst5,
+    Insn_Lconst0,
+    Insn_Lconst1,
+    Insn_Fconst0,
+    Insn_Fconst1,
+    Insn_Fconst2,
+    Insn_Dconst0,
+    Insn_Dconst1,
+    Insn_Bipush(i8 /*byte*/),
+    Insn_Sipush(i16 /*short*/),
+    Insn_Ldc(u16 /*index*/),
+    Insn_LdcW(u16 /*index*/),
+    Insn_Ldc2W(u16 /*index*/),
+    Insn_Iload(u16 /*index*/),
+    Insn_Lload(u16 /*index*/),
+    Insn_Fload(u16 /*index*/),
+    Insn_Dload(u16 /*index*/),
+    Insn_Aload(u16 /*index*/),
+    Insn_Iload0,
+    Insn_Iload1,
+    Insn_Iload2,
+    Insn_Iload3,
+    Insn_Lload0,
+    Insn_Lload1,
+    Insn_Lload2,
+    Insn_Lload3,
+    Insn_Fload0,
+    Insn_Fload1,
+    Insn_Fload2,
+    Insn_Fload3,
+    Insn_Dload0,
+    Insn_Dload1,
+    Insn_Dload2,
+    Insn_Dload3,
+    Insn_Aload0,
+    Insn_Aload1,
+    Insn_Aload2,
+    Insn_Aload3,
+    Insn_Iaload,
+    Insn_Laload,
+    Insn_Faload,
+    Insn_Daload,
+    Insn_Aaload,
+    Insn_Baload,
+    Insn_Caload,
+    Insn_Saload,
+    Insn_Istore(u16 /*index*/),
+    Insn_Lstore(u16 /*index*/),
+    Insn_Fstore(u16 /*index*/),
+    Insn_Dstore(u16 /*index*/),
+    Insn_Astore(u16 /*index*/),
+    Insn_Istore0,
+    Insn_Istore1,
+    Insn_Istore2,
+    Insn_Istore3,
+    Insn_Lstore0,
+    Insn_Lstore1,
+    Insn_Lstore2,
+    Insn_Lstore3,
+    Insn_Fstore0,
+    Insn_Fstore1,
+    Insn_Fstore2,
+    Insn_Fstore3,
+    Insn_Dstore0,
+    Insn_Dstore1,
+    Insn_Dstore2,
+    Insn_Dstore3,
+    Insn_Astore0,
+    Insn_Astore1,
+    Insn_Astore2,
+    Insn_Astore3,
+    Insn_Iastore,
+    Insn_Lastore,
+    Insn_Fastore,
+    Insn_Dastore,
+    Insn_Aastore,
+    Insn_Bastore,
+    Insn_Castore,
+    Insn_Sastore,
+    Insn_Pop,
+    Insn_Pop2,
+    Insn_Dup,
+    Insn_DupX1,
+    Insn_DupX2,
+    Insn_Dup2,
+    Insn_Dup2X1,
+    Insn_Dup2X2,
+    Insn_Swap,
+    Insn_Iadd,
+    Insn_Ladd,
+    Insn_Fadd,
+    Insn_Dadd,
+    Insn_Isub,
+    Insn_Lsub,
+    Insn_Fsub,
+    Insn_Dsub,
+    Insn_Imul,
+    Insn_Lmul,
+    Insn_Fmul,
+    Insn_Dmul,
+    Insn_Idiv,
+    Insn_Ldiv,
+    Insn_Fdiv,
+    Insn_Ddiv,
+    Insn_Irem,
+    Insn_Lrem,
+    Insn_Frem,
+    Insn_Drem,
+    Insn_Ineg,
+    Insn_Lneg,
+    Insn_Fneg,
+    Insn_Dneg,
+    Insn_Ishl,
+    Insn_Lshl,
+    Insn_Ishr,
+    Insn_Lshr,
+    Insn_Iushr,
+    Insn_Lushr,
+    Insn_Iand,
+    Insn_Land,
+    Insn_Ior,
+    Insn_Lor,
+    Insn_Ixor,
+    Insn_Lxor,
+    Insn_Iinc(u16 /*index*/, i16 /*const*/),
+    Insn_I2l,
+    Insn_I2f,
+    Insn_I2d,
+    Insn_L2i,
+    Insn_L2f,
+    Insn_L2d,
+    Insn_F2i,
+    Insn_F2l,
+    Insn_F2d,
+    Insn_D2i,
+    Insn_D2l,
+    Insn_D2f,
+    Insn_I2b,
+    Insn_I2c,
+    Insn_I2s,
+    Insn_Lcmp,
+    Insn_Fcmpl,
+    Insn_Fcmpg,
+    Insn_Dcmpl,
+    Insn_Dcmpg,
+    Insn_Ifeq(uint /*target*/),
+    Insn_Ifne(uint /*target*/),
+    Insn_Iflt(uint /*target*/),
+    Insn_Ifge(uint /*target*/),
+    Insn_Ifgt(uint /*target*/),
+    Insn_Ifle(uint /*target*/),
+    Insn_IfIcmpeq(uint /*target*/),
+    Insn_IfIcmpne(uint /*target*/),
+    Insn_IfIcmplt(uint /*target*/),
+    Insn_IfIcmpge(uint /*target*/),
+    Insn_IfIcmpgt(uint /*target*/),
+    Insn_IfIcmple(uint /*target*/),
+    Insn_IfAcmpeq(uint /*target*/),
+    Insn_IfAcmpne(uint /*target*/),
+    Insn_Goto(uint /*target*/),
+    Insn_Jsr(uint /*target*/),
+    Insn_Ret(u16 /*index*/),
+    Insn_Tableswitch(uint /*default*/, i32 /*low*/, i32 /*high*/, ~[uint] /*targets*/),
+    Insn_Lookupswitch(uint /*default*/, ~[(i32, uint)] /*match_offset_pairs*/),
+    Insn_Ireturn,
+    Insn_Lreturn,
+    Insn_Freturn,
+    Insn_Dreturn,
+    Insn_Areturn,
+    Insn_Return,
+    Insn_Getstatic(u16 /*index*/),
+    Insn_Putstatic(u16 /*index*/),
+    Insn_Getfield(u16 /*index*/),
+    Insn_Putfield(u16 /*index*/),
+    Insn_Invokevirtual(u16 /*index*/),
+    Insn_Invokespecial(u16 /*index*/),
+    Insn_Invokestatic(u16 /*index*/),
+    Insn_Invokeinterface(u16 /*index*/, u8 /*count*/),
+    Insn_Invokedynamic(u16 /*index*/),
+    Insn_New(u16 /*index*/),
+    Insn_Newarray(u8 /*atype*/),
+    Insn_Anewarray(u16 /*index*/),
+    Insn_Arraylength,
+    Insn_Athrow,
+    Insn_Checkcast(u16 /*index*/),
+    Insn_Instanceof(u16 /*index*/),
+    Insn_Monitorenter,
+    Insn_Monitorexit,
+    Insn_Multianewarray(u16 /*index*/, u8 /*dimensions*/),
+    Insn_Ifnull(uint /*target*/),
+    Insn_Ifnonnull(uint /*target*/),
+    Insn_GotoW(uint /*target*/),
+    Insn_JsrW(uint /*target*/)
+}
+
+impl Instruction {
+    pub fn opcode(&self) -> u8 {
+        match *self {
+            Insn_Nop => 0,
+            Insn_AconstNull => 1,
+            Insn_IconstM1 => 2,
+            Insn_Iconst0 => 3,
+            Insn_Iconst1 => 4,
+            Insn_Iconst2 => 5,
+            Insn_Iconst3 => 6,
+            Insn_Iconst4 => 7,
+            Insn_Iconst5 => 8,
+            Insn_Lconst0 => 9,
+            Insn_Lconst1 => 10,
+            Insn_Fconst0 => 11,
+            Insn_Fconst1 => 12,
+            Insn_Fconst2 => 13,
+            Insn_Dconst0 => 14,
+            Insn_Dconst1 => 15,
+            Insn_Bipush(_) => 16,
+            Insn_Sipush(_) => 17,
+            Insn_Ldc(_) => 18,
+            Insn_LdcW(_) => 19,
+            Insn_Ldc2W(_) => 20,
+            Insn_Iload(_) => 21,
+            Insn_Lload(_) => 22,
+            Insn_Fload(_) => 23,
+            Insn_Dload(_) => 24,
+            Insn_Aload(_) => 25,
+            Insn_Iload0 => 26,
+            Insn_Iload1 => 27,
+            Insn_Iload2 => 28,
+            Insn_Iload3 => 29,
+            Insn_Lload0 => 30,
+            Insn_Lload1 => 31,
+            Insn_Lload2 => 32,
+            Insn_Lload3 => 33,
+            Insn_Fload0 => 34,
+            Insn_Fload1 => 35,
+            Insn_Fload2 => 36,
+            Insn_Fload3 => 37,
+            Insn_Dload0 => 38,
+            Insn_Dload1 => 39,
+            Insn_Dload2 => 40,
+            Insn_Dload3 => 41,
+            Insn_Aload0 => 42,
+            Insn_Aload1 => 43,
+            Insn_Aload2 => 44,
+            Insn_Aload3 => 45,
+            Insn_Iaload => 46,
+            Insn_Laload => 47,
+            Insn_Faload => 48,
+            Insn_Daload => 49,
+            Insn_Aaload => 50,
+            Insn_Baload => 51,
+            Insn_Caload => 52,
+            Insn_Saload => 53,
+            Insn_Istore(_) => 54,
+            Insn_Lstore(_) => 55,
+            Insn_Fstore(_) => 56,
+            Insn_Dstore(_) => 57,
+            Insn_Astore(_) => 58,
+            Insn_Istore0 => 59,
+            Insn_Istore1 => 60,
+            Insn_Istore2 => 61,
+            Insn_Istore3 => 62,
+            Insn_Lstore0 => 63,
+            Insn_Lstore1 => 64,
+            Insn_Lstore2 => 65,
+            Insn_Lstore3 => 66,
+            Insn_Fstore0 => 67,
+            Insn_Fstore1 => 68,
+            Insn_Fstore2 => 69,
+            Insn_Fstore3 => 70,
+            Insn_Dstore0 => 71,
+            Insn_Dstore1 => 72,
+            Insn_Dstore2 => 73,
+            Insn_Dstore3 => 74,
+            Insn_Astore0 => 75,
+            Insn_Astore1 => 76,
+            Insn_Astore2 => 77,
+            Insn_Astore3 => 78,
+            Insn_Iastore => 79,
+            Insn_Lastore => 80,
+            Insn_Fastore => 81,
+            Insn_Dastore => 82,
+            Insn_Aastore => 83,
+            Insn_Bastore => 84,
+            Insn_Castore => 85,
+            Insn_Sastore => 86,
+            Insn_Pop => 87,
+            Insn_Pop2 => 88,
+            Insn_Dup => 89,
+            Insn_DupX1 => 90,
+            Insn_DupX2 => 91,
+            Insn_Dup2 => 92,
+            Insn_Dup2X1 => 93,
+            Insn_Dup2X2 => 94,
+            Insn_Swap => 95,
+            Insn_Iadd => 96,
+            Insn_Ladd => 97,
+            Insn_Fadd => 98,
+            Insn_Dadd => 99,
+            Insn_Isub => 100,
+            Insn_Lsub => 101,
+            Insn_Fsub => 102,
+            Insn_Dsub => 103,
+            Insn_Imul => 104,
+            Insn_Lmul => 105,
+            Insn_Fmul => 106,
+            Insn_Dmul => 107,
+            Insn_Idiv => 108,
+            Insn_Ldiv => 109,
+            Insn_Fdiv => 110,
+            Insn_Ddiv => 111,
+            Insn_Irem => 112,
+            Insn_Lrem => 113,
+            Insn_Frem => 114,
+            Insn_Drem => 115,
+            Insn_Ineg => 116,
+            Insn_Lneg => 117,
+            Insn_Fneg => 118,
+            Insn_Dneg => 119,
+            Insn_Ishl => 120,
+            Insn_Lshl => 121,
+            Insn_Ishr => 122,
+            Insn_Lshr => 123,
+            Insn_Iushr => 124,
+            Insn_Lushr => 125,
+            Insn_Iand => 126,
+            Insn_Land => 127,
+            Insn_Ior => 128,
+            Insn_Lor => 129,
+            Insn_Ixor => 130,
+            Insn_Lxor => 131,
+            Insn_Iinc(_, _) => 132,
+            Insn_I2l => 133,
+            Insn_I2f => 134,
+            Insn_I2d => 135,
+            Insn_L2i => 136,
+            Insn_L2f => 137,
+            Insn_L2d => 138,
+            Insn_F2i => 139,
+            Insn_F2l => 140,
+            Insn_F2d => 141,
+            Insn_D2i => 142,
+            Insn_D2l => 143,
+            Insn_D2f => 144,
+            Insn_I2b => 145,
+            Insn_I2c => 146,
+            Insn_I2s => 147,
+            Insn_Lcmp => 148,
+            Insn_Fcmpl => 149,
+            Insn_Fcmpg => 150,
+            Insn_Dcmpl => 151,
+            Insn_Dcmpg => 152,
+            Insn_Ifeq(_) => 153,
+            Insn_Ifne(_) => 154,
+            Insn_Iflt(_) => 155,
+            Insn_Ifge(_) => 156,
+            Insn_Ifgt(_) => 157,
+            Insn_Ifle(_) => 158,
+            Insn_IfIcmpeq(_) => 159,
+            Insn_IfIcmpne(_) => 160,
+            Insn_IfIcmplt(_) => 161,
+            Insn_IfIcmpge(_) => 162,
+            Insn_IfIcmpgt(_) => 163,
+            Insn_IfIcmple(_) => 164,
+            Insn_IfAcmpeq(_) => 165,
+            Insn_IfAcmpne(_) => 166,
+            Insn_Goto(_) => 167,
+            Insn_Jsr(_) => 168,
+            Insn_Ret(_) => 169,
+            Insn_Tableswitch(_, _, _, _) => 170,
+            Insn_Lookupswitch(_, _) => 171,
+            Insn_Ireturn => 172,
+            Insn_Lreturn => 173,
+            Insn_Freturn => 174,
+            Insn_Dreturn => 175,
+            Insn_Areturn => 176,
+            Insn_Return => 177,
+            Insn_Getstatic(_) => 178,
+            Insn_Putstatic(_) => 179,
+            Insn_Getfield(_) => 180,
+            Insn_Putfield(_) => 181,
+            Insn_Invokevirtual(_) => 182,
+            Insn_Invokespecial(_) => 183,
+            Insn_Invokestatic(_) => 184,
+            Insn_Invokeinterface(_, _) => 185,
+            Insn_Invokedynamic(_) => 186,
+            Insn_New(_) => 187,
+            Insn_Newarray(_) => 188,
+            Insn_Anewarray(_) => 189,
+            Insn_Arraylength => 190,
+            Insn_Athrow => 191,
+            Insn_Checkcast(_) => 192,
+            Insn_Instanceof(_) => 193,
+            Insn_Monitorenter => 194,
+            Insn_Monitorexit => 195,
+            Insn_Multianewarray(_, _) => 197,
+            Insn_Ifnull(_) => 198,
+            Insn_Ifnonnull(_) => 199,
+            Insn_GotoW(_) => 200,
+            Insn_JsrW(_) => 201
+        }
+    }
+
+    pub fn mnemonic(&self) -> &static/str {
+        match *self {
+            Insn_Nop => "nop",
+            Insn_AconstNull => "aconst_null",
+            Insn_IconstM1 => "iconst_m1",
+            Insn_Iconst0 => "iconst_0",
+            Insn_Iconst1 => "iconst_1",
+            Insn_Iconst2 => "iconst_2",
+            Insn_Iconst3 => "iconst_3",
+            Insn_Iconst4 => "iconst_4",
+            Insn_Iconst5 => "iconst_5",
+            Insn_Lconst0 => "lconst_0",
+            Insn_Lconst1 => "lconst_1",
+            Insn_Fconst0 => "fconst_0",
+            Insn_Fconst1 => "fconst_1",
+            Insn_Fconst2 => "fconst_2",
+            Insn_Dconst0 => "dconst_0",
+            Insn_Dconst1 => "dconst_1",
+            Insn_Bipush(_) => "bipush",
+            Insn_Sipush(_) => "sipush",
+            Insn_Ldc(_) => "ldc",
+            Insn_LdcW(_) => "ldc_w",
+            Insn_Ldc2W(_) => "ldc2_w",
+            Insn_Iload(_) => "iload",
+            Insn_Lload(_) => "lload",
+            Insn_Fload(_) => "fload",
+            Insn_Dload(_) => "dload",
+            Insn_Aload(_) => "aload",
+            Insn_Iload0 => "iload_0",
+            Insn_Iload1 => "iload_1",
+            Insn_Iload2 => "iload_2",
+            Insn_Iload3 => "iload_3",
+            Insn_Lload0 => "lload_0",
+            Insn_Lload1 => "lload_1",
+            Insn_Lload2 => "lload_2",
+            Insn_Lload3 => "lload_3",
+            Insn_Fload0 => "fload_0",
+            Insn_Fload1 => "fload_1",
+            Insn_Fload2 => "fload_2",
+            Insn_Fload3 => "fload_3",
+            Insn_Dload0 => "dload_0",
+            Insn_Dload1 => "dload_1",
+            Insn_Dload2 => "dload_2",
+            Insn_Dload3 => "dload_3",
+            Insn_Aload0 => "aload_0",
+            Insn_Aload1 => "aload_1",
+            Insn_Aload2 => "aload_2",
+            Insn_Aload3 => "aload_3",
+            Insn_Iaload => "iaload",
+            Insn_Laload => "laload",
+            Insn_Faload => "faload",
+            Insn_Daload => "daload",
+            Insn_Aaload => "aaload",
+            Insn_Baload => "baload",
+            Insn_Caload => "caload",
+            Insn_Saload => "saload",
+            Insn_Istore(_) => "istore",
+            Insn_Lstore(_) => "lstore",
+            Insn_Fstore(_) => "fstore",
+            Insn_Dstore(_) => "dstore",
+            Insn_Astore(_) => "astore",
+            Insn_Istore0 => "istore_0",
+            Insn_Istore1 => "istore_1",
+            Insn_Istore2 => "istore_2",
+            Insn_Istore3 => "istore_3",
+            Insn_Lstore0 => "lstore_0",
+            Insn_Lstore1 => "lstore_1",
+            Insn_Lstore2 => "lstore_2",
+            Insn_Lstore3 => "lstore_3",
+            Insn_Fstore0 => "fstore_0",
+            Insn_Fstore1 => "fstore_1",
+            Insn_Fstore2 => "fstore_2",
+            Insn_Fstore3 => "fstore_3",
+            Insn_Dstore0 => "dstore_0",
+            Insn_Dstore1 => "dstore_1",
+            Insn_Dstore2 => "dstore_2",
+            Insn_Dstore3 => "dstore_3",
+            Insn_Astore0 => "astore_0",
+            Insn_Astore1 => "astore_1",
+            Insn_Astore2 => "astore_2",
+            Insn_Astore3 => "astore_3",
+            Insn_Iastore => "iastore",
+            Insn_Lastore => "lastore",
+            Insn_Fastore => "fastore",
+            Insn_Dastore => "dastore",
+            Insn_Aastore => "aastore",
+            Insn_Bastore => "bastore",
+            Insn_Castore => "castore",
+            Insn_Sastore => "sastore",
+            Insn_Pop => "pop",
+            Insn_Pop2 => "pop2",
+            Insn_Dup => "dup",
+            Insn_DupX1 => "dup_x1",
+            Insn_DupX2 => "dup_x2",
+            Insn_Dup2 => "dup2",
+            Insn_Dup2X1 => "dup2_x1",
+            Insn_Dup2X2 => "dup2_x2",
+            Insn_Swap => "swap",
+            Insn_Iadd => "iadd",
+            Insn_Ladd => "ladd",
+            Insn_Fadd => "fadd",
+            Insn_Dadd => "dadd",
+            Insn_Isub => "isub",
+            Insn_Lsub => "lsub",
+            Insn_Fsub => "fsub",
+            Insn_Dsub => "dsub",
+            Insn_Imul => "imul",
+            Insn_Lmul => "lmul",
+            Insn_Fmul => "fmul",
+            Insn_Dmul => "dmul",
+            Insn_Idiv => "idiv",
+            Insn_Ldiv => "ldiv",
+            Insn_Fdiv => "fdiv",
+            Insn_Ddiv => "ddiv",
+            Insn_Irem => "irem",
+            Insn_Lrem => "lrem",
+            Insn_Frem => "frem",
+            Insn_Drem => "drem",
+            Insn_Ineg => "ineg",
+            Insn_Lneg => "lneg",
+            Insn_Fneg => "fneg",
+            Insn_Dneg => "dneg",
+            Insn_Ishl => "ishl",
+            Insn_Lshl => "lshl",
+            Insn_Ishr => "ishr",
+            Insn_Lshr => "lshr",
+            Insn_Iushr => "iushr",
+            Insn_Lushr => "lushr",
+            Insn_Iand => "iand",
+            Insn_Land => "land",
+            Insn_Ior => "ior",
+            Insn_Lor => "lor",
+            Insn_Ixor => "ixor",
+            Insn_Lxor => "lxor",
+            Insn_Iinc(_, _) => "iinc",
+            Insn_I2l => "i2l",
+            Insn_I2f => "i2f",
+            Insn_I2d => "i2d",
+            Insn_L2i => "l2i",
+            Insn_L2f => "l2f",
+            Insn_L2d => "l2d",
+            Insn_F2i => "f2i",
+            Insn_F2l => "f2l",
+            Insn_F2d => "f2d",
+            Insn_D2i => "d2i",
+            Insn_D2l => "d2l",
+            Insn_D2f => "d2f",
+            Insn_I2b => "i2b",
+            Insn_I2c => "i2c",
+            Insn_I2s => "i2s",
+            Insn_Lcmp => "lcmp",
+            Insn_Fcmpl => "fcmpl",
+            Insn_Fcmpg => "fcmpg",
+            Insn_Dcmpl => "dcmpl",
+            Insn_Dcmpg => "dcmpg",
+            Insn_Ifeq(_) => "ifeq",
+            Insn_Ifne(_) => "ifne",
+            Insn_Iflt(_) => "iflt",
+            Insn_Ifge(_) => "ifge",
+            Insn_Ifgt(_) => "ifgt",
+            Insn_Ifle(_) => "ifle",
+            Insn_IfIcmpeq(_) => "if_icmpeq",
+            Insn_IfIcmpne(_) => "if_icmpne",
+            Insn_IfIcmplt(_) => "if_icmplt",
+            Insn_IfIcmpge(_) => "if_icmpge",
+            Insn_IfIcmpgt(_) => "if_icmpgt",
+            Insn_IfIcmple(_) => "if_icmple",
+            Insn_IfAcmpeq(_) => "if_acmpeq",
+            Insn_IfAcmpne(_) => "if_acmpne",
+            Insn_Goto(_) => "goto",
+            Insn_Jsr(_) => "jsr",
+            Insn_Ret(_) => "ret",
+            Insn_Tableswitch(_, _, _, _) => "tableswitch",
+            Insn_Lookupswitch(_, _) => "lookupswitch",
+            Insn_Ireturn => "ireturn",
+            Insn_Lreturn => "lreturn",
+            Insn_Freturn => "freturn",
+            Insn_Dreturn => "dreturn",
+            Insn_Areturn => "areturn",
+            Insn_Return => "return",
+            Insn_Getstatic(_) => "getstatic",
+            Insn_Putstatic(_) => "putstatic",
+            Insn_Getfield(_) => "getfield",
+            Insn_Putfield(_) => "putfield",
+            Insn_Invokevirtual(_) => "invokevirtual",
+            Insn_Invokespecial(_) => "invokespecial",
+            Insn_Invokestatic(_) => "invokestatic",
+            Insn_Invokeinterface(_, _) => "invokeinterface",
+            Insn_Invokedynamic(_) => "invokedynamic",
+            Insn_New(_) => "new",
+            Insn_Newarray(_) => "newarray",
+            Insn_Anewarray(_) => "anewarray",
+            Insn_Arraylength => "arraylength",
+            Insn_Athrow => "athrow",
+            Insn_Checkcast(_) => "checkcast",
+            Insn_Instanceof(_) => "instanceof",
+            Insn_Monitorenter => "monitorenter",
+            Insn_Monitorexit => "monitorexit",
+            Insn_Multianewarray(_, _) => "multianewarray",
+            Insn_Ifnull(_) => "ifnull",
+            Insn_Ifnonnull(_) => "ifnonnull",
+            Insn_GotoW(_) => "goto_w",
+            Insn_JsrW(_) => "jsr_w"
+        }
+    }
+
+    //absolute pcs this instruction may transfer control to, not counting fall through
+    pub fn branch_targets(&self) -> ~[uint] {
+        match *self {
+            Insn_Ifeq(target) |
+            Insn_Ifne(target) |
+            Insn_Iflt(target) |
+            Insn_Ifge(target) |
+            Insn_Ifgt(target) |
+            Insn_Ifle(target) |
+            Insn_IfIcmpeq(target) |
+            Insn_IfIcmpne(target) |
+            Insn_IfIcmplt(target) |
+            Insn_IfIcmpge(target) |
+            Insn_IfIcmpgt(target) |
+            Insn_IfIcmple(target) |
+            Insn_IfAcmpeq(target) |
+            Insn_IfAcmpne(target) |
+            Insn_Goto(target) |
+            Insn_Jsr(target) |
+            Insn_Ifnull(target) |
+            Insn_Ifnonnull(target) |
+            Insn_GotoW(target) |
+            Insn_JsrW(target) => ~[target],
+            Insn_Tableswitch(default, _, _, ref targets) => ~[default] + *targets,
+            Insn_Lookupswitch(default, ref pairs) => {
+                let mut targets = ~[default];
+                for pairs.each |pair| {
+                    let (_, target) = *pair;
+                    targets.push(target);
+                }
+                targets
+            },
+            _ => ~[]
+        }
+    }
+}
+
+//decodes the instruction at pc, returning it along with the pc of the next instruction
+pub fn decode_instruction(code: &[u8], pc: uint) -> Result<(Instruction, uint), ClassFormatError> {
+    let opcode = try!(read_u8_at(code, pc));
+    let (insn, length) = match opcode {
+        0 => (Insn_Nop, 1),
+        1 => (Insn_AconstNull, 1),
+        2 => (Insn_IconstM1, 1),
+        3 => (Insn_Iconst0, 1),
+        4 => (Insn_Iconst1, 1),
+        5 => (Insn_Iconst2, 1),
+        6 => (Insn_Iconst3, 1),
+        7 => (Insn_Iconst4, 1),
+        8 => (Insn_Iconst5, 1),
+        9 => (Insn_Lconst0, 1),
+        10 => (Insn_Lconst1, 1),
+        11 => (Insn_Fconst0, 1),
+        12 => (Insn_Fconst1, 1),
+        13 => (Insn_Fconst2, 1),
+        14 => (Insn_Dconst0, 1),
+        15 => (Insn_Dconst1, 1),
+        16 => (Insn_Bipush(try!(read_u8_at(code, pc + 1)) as i8), 2),
+        17 => (Insn_Sipush(try!(read_u16_at(code, pc + 1)) as i16), 3),
+        18 => (Insn_Ldc(try!(read_u8_at(code, pc + 1)) as u16), 2),
+        19 => (Insn_LdcW(try!(read_u16_at(code, pc + 1))), 3),
+        20 => (Insn_Ldc2W(try!(read_u16_at(code, pc + 1))), 3),
+        21 => (Insn_Iload(try!(read_u8_at(code, pc + 1)) as u16), 2),
+        22 => (Insn_Lload(try!(read_u8_at(code, pc + 1)) as u16), 2),
+        23 => (Insn_Fload(try!(read_u8_at(code, pc + 1)) as u16), 2),
+        24 => (Insn_Dload(try!(read_u8_at(code, pc + 1)) as u16), 2),
+        25 => (Insn_Aload(try!(read_u8_at(code, pc + 1)) as u16), 2),
+        26 => (Insn_Iload0, 1),
+        27 => (Insn_Iload1, 1),
+        28 => (Insn_Iload2, 1),
+        29 => (Insn_Iload3, 1),
+        30 => (Insn_Lload0, 1),
+        31 => (Insn_Lload1, 1),
+        32 => (Insn_Lload2, 1),
+        33 => (Insn_Lload3, 1),
+        34 => (Insn_Fload0, 1),
+        35 => (Insn_Fload1, 1),
+        36 => (Insn_Fload2, 1),
+        37 => (Insn_Fload3, 1),
+        38 => (Insn_Dload0, 1),
+        39 => (Insn_Dload1, 1),
+        40 => (Insn_Dload2, 1),
+        41 => (Insn_Dload3, 1),
+        42 => (Insn_Aload0, 1),
+        43 => (Insn_Aload1, 1),
+        44 => (Insn_Aload2, 1),
+        45 => (Insn_Aload3, 1),
+        46 => (Insn_Iaload, 1),
+        47 => (Insn_Laload, 1),
+        48 => (Insn_Faload, 1),
+        49 => (Insn_Daload, 1),
+        50 => (Insn_Aaload, 1),
+        51 => (Insn_Baload, 1),
+        52 => (Insn_Caload, 1),
+        53 => (Insn_Saload, 1),
+        54 => (Insn_Istore(try!(read_u8_at(code, pc + 1)) as u16), 2),
+        55 => (Insn_Lstore(try!(read_u8_at(code, pc + 1)) as u16), 2),
+        56 => (Insn_Fstore(try!(read_u8_at(code, pc + 1)) as u16), 2),
+        57 => (Insn_Dstore(try!(read_u8_at(code, pc + 1)) as u16), 2),
+        58 => (Insn_Astore(try!(read_u8_at(code, pc + 1)) as u16), 2),
+        59 => (Insn_Istore0, 1),
+        60 => (Insn_Istore1, 1),
+        61 => (Insn_Istore2, 1),
+        62 => (Insn_Istore3, 1),
+        63 => (Insn_Lstore0, 1),
+        64 => (Insn_Lstore1, 1),
+        65 => (Insn_Lstore2, 1),
+        66 => (Insn_Lstore3, 1),
+        67 => (Insn_Fstore0, 1),
+        68 => (Insn_Fstore1, 1),
+        69 => (Insn_Fstore2, 1),
+        70 => (Insn_Fstore3, 1),
+        71 => (Insn_Dstore0, 1),
+        72 => (Insn_Dstore1, 1),
+        73 => (Insn_Dstore2, 1),
+        74 => (Insn_Dstore3, 1),
+        75 => (Insn_Astore0, 1),
+        76 => (Insn_Astore1, 1),
+        77 => (Insn_Astore2, 1),
+        78 => (Insn_Astore3, 1),
+        79 => (Insn_Iastore, 1),
+        80 => (Insn_Lastore, 1),
+        81 => (Insn_Fastore, 1),
+        82 => (Insn_Dastore, 1),
+        83 => (Insn_Aastore, 1),
+        84 => (Insn_Bastore, 1),
+        85 => (Insn_Castore, 1),
+        86 => (Insn_Sastore, 1),
+        87 => (Insn_Pop, 1),
+        88 => (Insn_Pop2, 1),
+        89 => (Insn_Dup, 1),
+        90 => (Insn_DupX1, 1),
+        91 => (Insn_DupX2, 1),
+        92 => (Insn_Dup2, 1),
+        93 => (Insn_Dup2X1, 1),
+        94 => (Insn_Dup2X2, 1),
+        95 => (Insn_Swap, 1),
+        96 => (Insn_Iadd, 1),
+        97 => (Insn_Ladd, 1),
+        98 => (Insn_Fadd, 1),
+        99 => (Insn_Dadd, 1),
+        100 => (Insn_Isub, 1),
+        101 => (Insn_Lsub, 1),
+        102 => (Insn_Fsub, 1),
+        103 => (Insn_Dsub, 1),
+        104 => (Insn_Imul, 1),
+        105 => (Insn_Lmul, 1),
+        106 => (Insn_Fmul, 1),
+        107 => (Insn_Dmul, 1),
+        108 => (Insn_Idiv, 1),
+        109 => (Insn_Ldiv, 1),
+        110 => (Insn_Fdiv, 1),
+        111 => (Insn_Ddiv, 1),
+        112 => (Insn_Irem, 1),
+        113 => (Insn_Lrem, 1),
+        114 => (Insn_Frem, 1),
+        115 => (Insn_Drem, 1),
+        116 => (Insn_Ineg, 1),
+        117 => (Insn_Lneg, 1),
+        118 => (Insn_Fneg, 1),
+        119 => (Insn_Dneg, 1),
+        120 => (Insn_Ishl, 1),
+        121 => (Insn_Lshl, 1),
+        122 => (Insn_Ishr, 1),
+        123 => (Insn_Lshr, 1),
+        124 => (Insn_Iushr, 1),
+        125 => (Insn_Lushr, 1),
+        126 => (Insn_Iand, 1),
+        127 => (Insn_Land, 1),
+        128 => (Insn_Ior, 1),
+        129 => (Insn_Lor, 1),
+        130 => (Insn_Ixor, 1),
+        131 => (Insn_Lxor, 1),
+        132 => (Insn_Iinc(try!(read_u8_at(code, pc + 1)) as u16, try!(read_u8_at(code, pc + 2)) as i8 as i16), 3),
+        133 => (Insn_I2l, 1),
+        134 => (Insn_I2f, 1),
+        135 => (Insn_I2d, 1),
+        136 => (Insn_L2i, 1),
+        137 => (Insn_L2f, 1),
+        138 => (Insn_L2d, 1),
+        139 => (Insn_F2i, 1),
+        140 => (Insn_F2l, 1),
+        141 => (Insn_F2d, 1),
+        142 => (Insn_D2i, 1),
+        143 => (Insn_D2l, 1),
+        144 => (Insn_D2f, 1),
+        145 => (Insn_I2b, 1),
+        146 => (Insn_I2c, 1),
+        147 => (Insn_I2s, 1),
+        148 => (Insn_Lcmp, 1),
+        149 => (Insn_Fcmpl, 1),
+        150 => (Insn_Fcmpg, 1),
+        151 => (Insn_Dcmpl, 1),
+        152 => (Insn_Dcmpg, 1),
+        153 => (Insn_Ifeq(try!(branch_target(code, pc, try!(read_u16_at(code, pc + 1)) as i16 as int))), 3),
+        154 => (Insn_Ifne(try!(branch_target(code, pc, try!(read_u16_at(code, pc + 1)) as i16 as int))), 3),
+        155 => (Insn_Iflt(try!(branch_target(code, pc, try!(read_u16_at(code, pc + 1)) as i16 as int))), 3),
+        156 => (Insn_Ifge(try!(branch_target(code, pc, try!(read_u16_at(code, pc + 1)) as i16 as int))), 3),
+        157 => (Insn_Ifgt(try!(branch_target(code, pc, try!(read_u16_at(code, pc + 1)) as i16 as int))), 3),
+        158 => (Insn_Ifle(try!(branch_target(code, pc, try!(read_u16_at(code, pc + 1)) as i16 as int))), 3),
+        159 => (Insn_IfIcmpeq(try!(branch_target(code, pc, try!(read_u16_at(code, pc + 1)) as i16 as int))), 3),
+        160 => (Insn_IfIcmpne(try!(branch_target(code, pc, try!(read_u16_at(code, pc + 1)) as i16 as int))), 3),
+        161 => (Insn_IfIcmplt(try!(branch_target(code, pc, try!(read_u16_at(code, pc + 1)) as i16 as int))), 3),
+        162 => (Insn_IfIcmpge(try!(branch_target(code, pc, try!(read_u16_at(code, pc + 1)) as i16 as int))), 3),
+        163 => (Insn_IfIcmpgt(try!(branch_target(code, pc, try!(read_u16_at(code, pc + 1)) as i16 as int))), 3),
+        164 => (Insn_IfIcmple(try!(branch_target(code, pc, try!(read_u16_at(code, pc + 1)) as i16 as int))), 3),
+        165 => (Insn_IfAcmpeq(try!(branch_target(code, pc, try!(read_u16_at(code, pc + 1)) as i16 as int))), 3),
+        166 => (Insn_IfAcmpne(try!(branch_target(code, pc, try!(read_u16_at(code, pc + 1)) as i16 as int))), 3),
+        167 => (Insn_Goto(try!(branch_target(code, pc, try!(read_u16_at(code, pc + 1)) as i16 as int))), 3),
+        168 => (Insn_Jsr(try!(branch_target(code, pc, try!(read_u16_at(code, pc + 1)) as i16 as int))), 3),
+        169 => (Insn_Ret(try!(read_u8_at(code, pc + 1)) as u16), 2),
+        170 => return decode_tableswitch(code, pc),
+        171 => return decode_lookupswitch(code, pc),
+        172 => (Insn_Ireturn, 1),
+        173 => (Insn_Lreturn, 1),
+        174 => (Insn_Freturn, 1),
+        175 => (Insn_Dreturn, 1),
+        176 => (Insn_Areturn, 1),
+        177 => (Insn_Return, 1),
+        178 => (Insn_Getstatic(try!(read_u16_at(code, pc + 1))), 3),
+        179 => (Insn_Putstatic(try!(read_u16_at(code, pc + 1))), 3),
+        180 => (Insn_Getfield(try!(read_u16_at(code, pc + 1))), 3),
+        181 => (Insn_Putfield(try!(read_u16_at(code, pc + 1))), 3),
+        182 => (Insn_Invokevirtual(try!(read_u16_at(code, pc + 1))), 3),
+        183 => (Insn_Invokespecial(try!(read_u16_at(code, pc + 1))), 3),
+        184 => (Insn_Invokestatic(try!(read_u16_at(code, pc + 1))), 3),
+        185 => {
+            let insn = Insn_Invokeinterface(try!(read_u16_at(code, pc + 1)), try!(read_u8_at(code, pc + 3)));
+            try!(expect_zero(code, pc + 4, 1));
+            (insn, 5)
+        },
+        186 => {
+            let insn = Insn_Invokedynamic(try!(read_u16_at(code, pc + 1)));
+            try!(expect_zero(code, pc + 3, 2));
+            (insn, 5)
+        },
+        187 => (Insn_New(try!(read_u16_at(code, pc + 1))), 3),
+        188 => (Insn_Newarray(try!(read_u8_at(code, pc + 1))), 2),
+        189 => (Insn_Anewarray(try!(read_u16_at(code, pc + 1))), 3),
+        190 => (Insn_Arraylength, 1),
+        191 => (Insn_Athrow, 1),
+        192 => (Insn_Checkcast(try!(read_u16_at(code, pc + 1))), 3),
+        193 => (Insn_Instanceof(try!(read_u16_at(code, pc + 1))), 3),
+        194 => (Insn_Monitorenter, 1),
+        195 => (Insn_Monitorexit, 1),
+        196 => return decode_wide(code, pc),
+        197 => (Insn_Multianewarray(try!(read_u16_at(code, pc + 1)), try!(read_u8_at(code, pc + 3))), 4),
+        198 => (Insn_Ifnull(try!(branch_target(code, pc, try!(read_u16_at(code, pc + 1)) as i16 as int))), 3),
+        199 => (Insn_Ifnonnull(try!(branch_target(code, pc, try!(read_u16_at(code, pc + 1)) as i16 as int))), 3),
+        200 => (Insn_GotoW(try!(branch_target(code, pc, try!(read_u32_at(code, pc + 1)) as i32 as int))), 5),
+        201 => (Insn_JsrW(try!(branch_target(code, pc, try!(read_u32_at(code, pc + 1)) as i32 as int))), 5),
+        _ => return Err(FormatError_UnrecognizedTag(pc, ~"code", opcode))
+    };
+    //every operand above is read, this only guards against a length that outruns them
+    if pc + length > code.len() {
+        return Err(FormatError_UnexpectedEof(pc, ~"code"));
+    }
+    Ok((insn, pc + length))
+}
+
+//the reserved operand bytes of invokeinterface and invokedynamic
+fn expect_zero(code: &[u8], pos: uint, count: uint) -> Result<(), ClassFormatError> {
+    for uint::range(pos, pos + count) |i| {
+        if try!(read_u8_at(code, i)) != 0 {
+            return Err(FormatError_Invalid(i, ~"code", ~"reserved operand byte is not zero"));
+        }
+    }
+    Ok(())
+}
+
+fn decode_wide(code: &[u8], pc: uint) -> Result<(Instruction, uint), ClassFormatError> {
+    let opcode = try!(read_u8_at(code, pc + 1));
+    let index = try!(read_u16_at(code, pc + 2));
+    let insn = match opcode {
+        21 => Insn_Iload(index),
+        22 => Insn_Lload(index),
+        23 => Insn_Fload(index),
+        24 => Insn_Dload(index),
+        25 => Insn_Aload(index),
+        54 => Insn_Istore(index),
+        55 => Insn_Lstore(index),
+        56 => Insn_Fstore(index),
+        57 => Insn_Dstore(index),
+        58 => Insn_Astore(index),
+        169 => Insn_Ret(index),
+        132 => return Ok((Insn_Iinc(index, try!(read_u16_at(code, pc + 4)) as i16), pc + 6)),
+        _ => return Err(FormatError_UnrecognizedTag(pc + 1, ~"wide", opcode))
+    };
+    Ok((insn, pc + 4))
+}
+
+fn decode_tableswitch(code: &[u8], pc: uint) -> Result<(Instruction, uint), ClassFormatError> {
+    //operands start at the next 4-byte boundary relative to the start of the code
+    let mut pos = (pc + 4) & !3u;
+    let default = try!(branch_target(code, pc, try!(read_u32_at(code, pos)) as i32 as int));
+    let low = try!(read_u32_at(code, pos + 4)) as i32;
+    let high = try!(read_u32_at(code, pos + 8)) as i32;
+    if high < low {
+        return Err(FormatError_Invalid(pc, ~"tableswitch", fmt!("high %d below low %d", high as int, low as int)));
+    }
+    pos += 12;
+    let count = (high as int - low as int + 1) as uint;
+    if pos + count * 4 > code.len() {
+        return Err(FormatError_UnexpectedEof(pos, ~"tableswitch"));
+    }
+    let mut targets: ~[uint] = ~[];
+    vec::reserve(&mut targets, count);
+    for iter::repeat(count) {
+        targets.push(try!(branch_target(code, pc, try!(read_u32_at(code, pos)) as i32 as int)));
+        pos += 4;
+    }
+    Ok((Insn_Tableswitch(default, low, high, targets), pos))
+}
+
+fn decode_lookupswitch(code: &[u8], pc: uint) -> Result<(Instruction, uint), ClassFormatError> {
+    let mut pos = (pc + 4) & !3u;
+    let default = try!(branch_target(code, pc, try!(read_u32_at(code, pos)) as i32 as int));
+    let npairs = try!(read_u32_at(code, pos + 4)) as i32;
+    if npairs < 0 {
+        return Err(FormatError_Invalid(pc, ~"lookupswitch", fmt!("negative npairs %d", npairs as int)));
+    }
+    pos += 8;
+    if pos + npairs as uint * 8 > code.len() {
+        return Err(FormatError_UnexpectedEof(pos, ~"lookupswitch"));
+    }
+    let mut pairs: ~[(i32, uint)] = ~[];
+    vec::reserve(&mut pairs, npairs as uint);
+    for iter::repeat(npairs as uint) {
+        let key = try!(read_u32_at(code, pos)) as i32;
+        let target = try!(branch_target(code, pc, try!(read_u32_at(code, pos + 4)) as i32 as int));
+        pairs.push((key, target));
+        pos += 8;
+    }
+    Ok((Insn_Lookupswitch(default, pairs), pos))
+}
+
+pub fn each_instruction(code: &[u8], f: fn(uint, &Instruction) -> bool) -> Result<(), ClassFormatError> {
+    let mut pc = 0u;
+    while pc < code.len() {
+        let (insn, next_pc) = try!(decode_instruction(code, pc));
+        if !f(pc, &insn) {
+            break;
+        }
+        pc = next_pc;
+    }
+    Ok(())
+}
+
+pub fn decode(code: &[u8]) -> Result<~[(uint, Instruction)], ClassFormatError> {
+    let mut insns: ~[(uint, Instruction)] = ~[];
+    let mut pc = 0u;
+    while pc < code.len() {
+        let (insn, next_pc) = try!(decode_instruction(code, pc));
+        insns.push((pc, insn));
+        pc = next_pc;
+    }
+    Ok(insns)
+}
+
+fn branch_target(code: &[u8], pc: uint, offset: int) -> Result<uint, ClassFormatError> {
+    let target = pc as int + offset;
+    if target < 0 || target >= code.len() as int {
+        return Err(FormatError_Invalid(pc, ~"code", fmt!("branch target %d out of range", target)));
+    }
+    Ok(target as uint)
+}
+
+fn read_u8_at(code: &[u8], pos: uint) -> Result<u8, ClassFormatError> {
+    if pos >= code.len() {
+        return Err(FormatError_UnexpectedEof(pos, ~"code"));
+    }
+    Ok(code[pos])
+}
+fn read_u16_at(code: &[u8], pos: uint) -> Result<u16, ClassFormatError> {
+    if pos + 2 > code.len() {
+        return Err(FormatError_UnexpectedEof(pos, ~"code"));
+    }
+    Ok((code[pos] as u16 << 8) | code[pos + 1] as u16)
+}
+fn read_u32_at(code: &[u8], pos: uint) -> Result<u32, ClassFormatError> {
+    if pos + 4 > code.len() {
+        return Err(FormatError_UnexpectedEof(pos, ~"code"));
+    }
+    Ok((code[pos] as u32 << 24) | (code[pos + 1] as u32 << 16) | (code[pos + 2] as u32 << 8) | code[pos + 3] as u32)
+}
@@ -0,0 +1,411 @@
+use bytecode::*;
+use class_file::{ClassFormatError, FormatError_Invalid, CodeAttributeInfo, ExceptionTableInfo};
+
+//basic blocks of a method's code with the edges between them, their dominator and post-dominator trees and natural
+//loops, jsr is treated as a call that falls through to the next instruction and ret as leaving the method
+
+pub enum EdgeKind {
+    Edge_FallThrough,
+    Edge_Branch,
+    Edge_Switch(Option<i32> /*key, None for default*/),
+    Edge_Exception(u16 /*catch_type, 0 for any*/)
+}
+
+pub struct Edge {
+    //block indices
+    from: uint,
+    to: uint,
+    kind: EdgeKind
+}
+
+impl Edge {
+    pub fn is_exceptional(&self) -> bool {
+        match self.kind {
+            Edge_Exception(_) => true,
+            _ => false
+        }
+    }
+}
+
+pub struct BasicBlock {
+    start_pc: uint,
+    //exclusive
+    end_pc: uint,
+    //indices into the graph's instructions, last inclusive
+    first: uint,
+    last: uint
+}
+
+pub struct Loop {
+    header: uint,
+    //blocks with a back edge to the header
+    latches: ~[uint],
+    //the header first, then the body in no particular order
+    blocks: ~[uint]
+}
+
+pub struct ControlFlowGraph {
+    instructions: ~[(uint, Instruction)],
+    //in pc order, the entry block is 0
+    blocks: ~[BasicBlock],
+    edges: ~[Edge],
+    successors: ~[~[uint]],
+    predecessors: ~[~[uint]],
+    //immediate dominators, None for the entry block and unreachable blocks
+    dominators: ~[Option<uint>],
+    //immediate post-dominators, None for exit blocks and blocks that never reach one. Exits are the blocks ending
+    //in a return, athrow or ret, a block only left through its exception edges is not one
+    post_dominators: ~[Option<uint>]
+}
+
+impl ControlFlowGraph {
+    pub fn block_at(&self, pc: uint) -> Option<uint> {
+        for self.blocks.eachi |index, block| {
+            if pc >= block.start_pc && pc < block.end_pc {
+                return Some(index);
+            }
+        }
+        None
+    }
+
+    pub fn dominates(&self, dominator: uint, block: uint) -> bool {
+        tree_contains(self.dominators, dominator, block)
+    }
+
+    pub fn post_dominates(&self, post_dominator: uint, block: uint) -> bool {
+        tree_contains(self.post_dominators, post_dominator, block)
+    }
+
+    //from the entry block through any edges, exception edges included
+    pub fn is_reachable(&self, block: uint) -> bool {
+        block == 0 || self.dominators[block].is_some()
+    }
+
+    //natural loops from back edges, edges whose target dominates their source, with back edges to the same header
+    //merged, irreducible loops have no such header and are not found. Unreachable blocks are in no loop
+    pub fn loops(&self) -> ~[Loop] {
+        let mut loops: ~[Loop] = ~[];
+        for self.edges.each |edge| {
+            if !self.is_reachable(edge.from) || !self.dominates(edge.to, edge.from) {
+                loop;
+            }
+            let mut existing = None;
+            for loops.eachi |index, found| {
+                if found.header == edge.to {
+                    existing = Some(index);
+                    break;
+                }
+            }
+            let index = match existing {
+                Some(index) => index,
+                None => {
+                    loops.push(Loop { header: edge.to, latches: ~[], blocks: ~[edge.to] });
+                    loops.len() - 1
+                }
+            };
+            if !vec::contains(loops[index].latches, &edge.from) {
+                loops[index].latches.push(edge.from);
+            }
+            //everything reaching the latch without going through the header
+            let mut stack = ~[edge.from];
+            while !stack.is_empty() {
+                let block = stack.pop();
+                if self.is_reachable(block) && !vec::contains(loops[index].blocks, &block) {
+                    loops[index].blocks.push(block);
+                    stack.push_all(self.predecessors[block]);
+                }
+            }
+        }
+        loops
+    }
+
+    //blocks list their instructions, branches are solid, switch cases labeled and exception edges dashed
+    pub fn to_dot(&self, name: &str) -> ~str {
+        let mut dot = fmt!("digraph \"%s\" {\n", escape_dot(name));
+        str::push_str(&mut dot, "    node [shape=box, fontname=\"monospace\"];\n");
+        for self.blocks.eachi |index, block| {
+            let mut label = fmt!("B%u\\l", index);
+            for uint::range(block.first, block.last + 1) |i| {
+                match self.instructions[i] {
+                    (pc, ref insn) => str::push_str(&mut label, fmt!("%u: %s\\l", pc, insn.mnemonic()))
+                }
+            }
+            str::push_str(&mut dot, fmt!("    b%u [label=\"%s\"];\n", index, label));
+        }
+        for self.edges.each |edge| {
+            let attributes = match edge.kind {
+                Edge_FallThrough => ~"",
+                Edge_Branch => ~" [color=blue]",
+                Edge_Switch(Some(key)) => fmt!(" [label=\"case %d\"]", key as int),
+                Edge_Switch(None) => ~" [label=\"default\"]",
+                Edge_Exception(0) => ~" [style=dashed, color=red, label=\"any\"]",
+                Edge_Exception(catch_type) => fmt!(" [style=dashed, color=red, label=\"#%u\"]", catch_type as uint)
+            };
+            str::push_str(&mut dot, fmt!("    b%u -> b%u%s;\n", edge.from, edge.to, attributes));
+        }
+        str::push_str(&mut dot, "}\n");
+        dot
+    }
+}
+
+pub fn ControlFlowGraph(code: &CodeAttributeInfo) -> Result<ControlFlowGraph, ClassFormatError> {
+    control_flow_graph(code.code, code.exception_table)
+}
+
+pub fn control_flow_graph(code: &[u8], exception_table: &[ExceptionTableInfo])
+        -> Result<ControlFlowGraph, ClassFormatError> {
+    let instructions = try!(bytecode::decode(code));
+    if instructions.is_empty() {
+        return Err(FormatError_Invalid(0, ~"code", ~"empty code"));
+    }
+
+    //a block starts at the entry, every branch target and handler, the ends of exception ranges and after every
+    //instruction that branches or does not fall through
+    let mut starts = vec::from_elem(code.len() + 1, false);
+    starts[code.len()] = true;
+    for instructions.each |entry| {
+        match *entry {
+            (pc, _) => starts[pc] = true
+        }
+    }
+    let mut leaders = vec::from_elem(code.len() + 1, false);
+    leaders[0] = true;
+    for instructions.eachi |index, entry| {
+        match *entry {
+            (pc, ref insn) => {
+                let targets = insn.branch_targets();
+                for targets.each |target| {
+                    if !starts[*target] {
+                        return Err(FormatError_Invalid(pc, ~"code",
+                            fmt!("branch target %u is not the start of an instruction", *target)));
+                    }
+                    leaders[*target] = true;
+                }
+                if !targets.is_empty() || !falls_through(insn) {
+                    leaders[next_pc(instructions, index, code.len())] = true;
+                }
+            }
+        }
+    }
+    for exception_table.each |entry| {
+        let (start_pc, end_pc, handler_pc) = (entry.start_pc as uint, entry.end_pc as uint, entry.handler_pc as uint);
+        if start_pc >= end_pc || end_pc > code.len() || !starts[start_pc] || !starts[end_pc] ||
+                handler_pc >= code.len() || !starts[handler_pc] {
+            return Err(FormatError_Invalid(start_pc, ~"exception_table",
+                fmt!("bad exception range [%u, %u) with handler %u", start_pc, end_pc, handler_pc)));
+        }
+        leaders[start_pc] = true;
+        leaders[end_pc] = true;
+        leaders[handler_pc] = true;
+    }
+
+    let mut blocks: ~[BasicBlock] = ~[];
+    //the block of each instruction's pc
+    let mut block_of = vec::from_elem(code.len(), 0u);
+    for instructions.eachi |index, entry| {
+        match *entry {
+            (pc, _) => {
+                if leaders[pc] {
+                    blocks.push(BasicBlock { start_pc: pc, end_pc: pc, first: index, last: index });
+                }
+                let last = blocks.len() - 1;
+                blocks[last].end_pc = next_pc(instructions, index, code.len());
+                blocks[last].last = index;
+                block_of[pc] = last;
+            }
+        }
+    }
+
+    let mut edges: ~[Edge] = ~[];
+    for blocks.eachi |index, block| {
+        match instructions[block.last] {
+            (_, ref insn) => {
+                match *insn {
+                    Insn_Tableswitch(default, low, _, ref targets) => {
+                        edges.push(Edge { from: index, to: block_of[default], kind: Edge_Switch(None) });
+                        for targets.eachi |i, target| {
+                            let key = low + i as i32;
+                            edges.push(Edge { from: index, to: block_of[*target], kind: Edge_Switch(Some(key)) });
+                        }
+                    },
+                    Insn_Lookupswitch(default, ref pairs) => {
+                        edges.push(Edge { from: index, to: block_of[default], kind: Edge_Switch(None) });
+                        for pairs.each |pair| {
+                            let (key, target) = *pair;
+                            edges.push(Edge { from: index, to: block_of[target], kind: Edge_Switch(Some(key)) });
+                        }
+                    },
+                    _ => for insn.branch_targets().each |target| {
+                        edges.push(Edge { from: index, to: block_of[*target], kind: Edge_Branch });
+                    }
+                }
+                //falling off the end of the code is left to the verifier
+                if falls_through(insn) && index + 1 < blocks.len() {
+                    edges.push(Edge { from: index, to: index + 1, kind: Edge_FallThrough });
+                }
+            }
+        }
+        for exception_table.each |entry| {
+            if block.start_pc >= entry.start_pc as uint && block.start_pc < entry.end_pc as uint {
+                let handler = block_of[entry.handler_pc as uint];
+                edges.push(Edge { from: index, to: handler, kind: Edge_Exception(entry.catch_type) });
+            }
+        }
+    }
+
+    let mut successors = vec::from_elem(blocks.len(), ~[]);
+    let mut predecessors = vec::from_elem(blocks.len(), ~[]);
+    for edges.each |edge| {
+        if !vec::contains(successors[edge.from], &edge.to) {
+            successors[edge.from].push(edge.to);
+            predecessors[edge.to].push(edge.from);
+        }
+    }
+
+    //both trees are computed from a virtual root at index blocks.len(), tied to the entry for dominators and to
+    //every exit for post-dominators over the reversed edges. Exits are chosen by their last instruction rather than
+    //by having no successors, a return inside a try range still has an exception edge to its handler
+    let count = blocks.len();
+    let mut forward = copy successors;
+    forward.push(~[0u]);
+    let mut backward = copy predecessors;
+    let mut exits = ~[];
+    for blocks.eachi |index, block| {
+        match instructions[block.last] {
+            (_, ref insn) => if !falls_through(insn) && insn.branch_targets().is_empty() {
+                exits.push(index);
+            }
+        }
+    }
+    backward.push(exits);
+
+    Ok(ControlFlowGraph {
+        instructions: instructions,
+        blocks: blocks,
+        edges: edges,
+        successors: successors,
+        predecessors: predecessors,
+        dominators: immediate_dominators(forward, count),
+        post_dominators: immediate_dominators(backward, count)
+    })
+}
+
+fn next_pc(instructions: &[(uint, Instruction)], index: uint, code_length: uint) -> uint {
+    if index + 1 < instructions.len() {
+        match instructions[index + 1] {
+            (pc, _) => pc
+        }
+    } else {
+        code_length
+    }
+}
+
+fn falls_through(insn: &Instruction) -> bool {
+    match *insn {
+        Insn_Goto(_) | Insn_GotoW(_) | Insn_Ret(_) | Insn_Athrow | Insn_Tableswitch(_, _, _, _) |
+        Insn_Lookupswitch(_, _) | Insn_Ireturn | Insn_Lreturn | Insn_Freturn | Insn_Dreturn | Insn_Areturn |
+        Insn_Return => false,
+        _ => true
+    }
+}
+
+fn tree_contains(tree: &[Option<uint>], ancestor: uint, node: uint) -> bool {
+    let mut current = node;
+    loop {
+        if current == ancestor {
+            return true;
+        }
+        current = match tree[current] {
+            Some(parent) => parent,
+            None => return false
+        };
+    }
+}
+
+//Cooper, Harvey and Kennedy's iterative algorithm over the nodes before root, children of the root get None as do
+//nodes the root does not reach
+fn immediate_dominators(successors: &[~[uint]], root: uint) -> ~[Option<uint>] {
+    let count = successors.len();
+    let mut predecessors = vec::from_elem(count, ~[]);
+    for successors.eachi |node, node_successors| {
+        for node_successors.each |successor| {
+            predecessors[*successor].push(node);
+        }
+    }
+
+    //postorder by an iterative depth first search from the root, which ends up last
+    let mut order = vec::from_elem(count, 0u);
+    let mut postorder = ~[];
+    let mut visited = vec::from_elem(count, false);
+    let mut stack = ~[(root, 0u)];
+    visited[root] = true;
+    while !stack.is_empty() {
+        let top = stack.len() - 1;
+        let (node, next) = stack[top];
+        if next < successors[node].len() {
+            stack[top] = (node, next + 1);
+            let successor = successors[node][next];
+            if !visited[successor] {
+                visited[successor] = true;
+                stack.push((successor, 0u));
+            }
+        } else {
+            stack.pop();
+            order[node] = postorder.len();
+            postorder.push(node);
+        }
+    }
+
+    let mut idom = vec::from_elem(count, None);
+    idom[root] = Some(root);
+    let mut changed = true;
+    while changed {
+        changed = false;
+        //reverse postorder, skipping the root
+        let mut i = postorder.len() - 1;
+        while i > 0 {
+            i -= 1;
+            let node = postorder[i];
+            let mut new_idom = None;
+            for predecessors[node].each |predecessor| {
+                if idom[*predecessor].is_some() {
+                    new_idom = match new_idom {
+                        Some(current) => Some(intersect(idom, order, current, *predecessor)),
+                        None => Some(*predecessor)
+                    };
+                }
+            }
+            if new_idom.is_some() && idom[node] != new_idom {
+                idom[node] = new_idom;
+                changed = true;
+            }
+        }
+    }
+
+    let mut result = ~[];
+    for uint::range(0, root) |node| {
+        result.push(match idom[node] {
+            Some(dominator) if dominator != root => Some(dominator),
+            _ => None
+        });
+    }
+    result
+}
+
+fn intersect(idom: &[Option<uint>], order: &[uint], first: uint, second: uint) -> uint {
+    let mut first = first;
+    let mut second = second;
+    while first != second {
+        while order[first] < order[second] {
+            first = idom[first].get();
+        }
+        while order[second] < order[first] {
+            second = idom[second].get();
+        }
+    }
+    first
+}
+
+fn escape_dot(name: &str) -> ~str {
+    str::replace(str::replace(name, "\\", "\\\\"), "\"", "\\\"")
+}
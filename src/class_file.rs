@@ -1,9 +1,9 @@
 
-struct ClassFile {
+pub struct ClassFile {
     magic: u32,
     minor_version: u16,
     major_version: u16,
-    constant_pool: ~[ConstantPoolInfo],
+    constant_pool: ConstantPool,
     access_flags: u16,
     this_class: u16,
     super_class: u16,
@@ -13,6 +13,135 @@ struct ClassFile {
     attributes: ~[AttributeInfo]
 }
 
+impl ClassFile {
+    pub fn write(&self, writer: io::Writer) -> Result<(), ClassFormatError> {
+        class_writer::write_class_file(self, writer)
+    }
+
+    pub fn to_bytes(&self) -> Result<~[u8], ClassFormatError> {
+        class_writer::class_file_to_bytes(self)
+    }
+
+    pub fn flags(&self) -> access_flags::ClassAccessFlags {
+        access_flags::ClassAccessFlags { bits: self.access_flags }
+    }
+
+    //internal names, e.g. java/lang/String, None when the index is not a class constant, which a class file that
+    //was read rather than built by hand never has
+    pub fn class_name(&self) -> Option<&self/str> {
+        self.constant_pool.get_class_name(self.this_class)
+    }
+
+    //None for java/lang/Object and module-info, or when super_class is not a class constant
+    pub fn super_name(&self) -> Option<&self/str> {
+        if self.super_class == 0 { None } else { self.constant_pool.get_class_name(self.super_class) }
+    }
+
+    //the Module attribute of a module-info class
+    pub fn module(&self) -> Option<&self/ModuleAttributeInfo> {
+        for self.attributes.each |attribute| {
+            match attribute.info {
+                AttrStruct_Module(ref module) => return Some(module),
+                _ => ()
+            }
+        }
+        None
+    }
+
+    //None when any of the indices is not a class constant
+    pub fn interfaces(&self) -> Option<~[&self/str]> {
+        let mut names: ~[&self/str] = ~[];
+        for self.interfaces.each |index| {
+            match self.constant_pool.get_class_name(*index) {
+                Some(name) => names.push(name),
+                None => return None
+            }
+        }
+        Some(names)
+    }
+}
+
+pub enum ClassFormatError {
+    FormatError_UnexpectedEof(uint /*offset*/, ~str /*structure*/),
+    FormatError_BadMagic(uint /*offset*/, u32 /*magic*/),
+    FormatError_UnrecognizedTag(uint /*offset*/, ~str /*structure*/, u8 /*tag*/),
+    FormatError_BadConstantIndex(uint /*offset*/, ~str /*structure*/, u16 /*index*/),
+    FormatError_Invalid(uint /*offset*/, ~str /*structure*/, ~str /*reason*/),
+    //from parsing a descriptor or signature string, the position is a char index into that string rather than a
+    //byte offset into the class file
+    FormatError_BadDescriptor(uint /*position*/, ~str /*structure*/, ~str /*reason*/)
+}
+
+impl ClassFormatError {
+    pub fn offset(&self) -> uint {
+        match *self {
+            FormatError_UnexpectedEof(offset, _) => offset,
+            FormatError_BadMagic(offset, _) => offset,
+            FormatError_UnrecognizedTag(offset, _, _) => offset,
+            FormatError_BadConstantIndex(offset, _, _) => offset,
+            FormatError_Invalid(offset, _, _) => offset,
+            FormatError_BadDescriptor(position, _, _) => position
+        }
+    }
+
+    pub fn structure(&self) -> ~str {
+        match *self {
+            FormatError_UnexpectedEof(_, ref structure) => copy *structure,
+            FormatError_BadMagic(_, _) => ~"magic",
+            FormatError_UnrecognizedTag(_, ref structure, _) => copy *structure,
+            FormatError_BadConstantIndex(_, ref structure, _) => copy *structure,
+            FormatError_Invalid(_, ref structure, _) => copy *structure,
+            FormatError_BadDescriptor(_, ref structure, _) => copy *structure
+        }
+    }
+
+    pub fn reason(&self) -> ~str {
+        match *self {
+            FormatError_UnexpectedEof(_, _) => ~"unexpected end of input",
+            FormatError_BadMagic(_, magic) => fmt!("bad magic 0x%x", magic as uint),
+            FormatError_UnrecognizedTag(_, _, tag) => fmt!("unrecognized tag %u", tag as uint),
+            FormatError_BadConstantIndex(_, _, index) => fmt!("bad constant pool index %u", index as uint),
+            FormatError_Invalid(_, _, ref reason) => copy *reason,
+            FormatError_BadDescriptor(_, _, ref reason) => copy *reason
+        }
+    }
+
+    pub fn to_str(&self) -> ~str {
+        match *self {
+            FormatError_BadDescriptor(position, _, _) =>
+                fmt!("%s at position %u: %s", self.structure(), position, self.reason()),
+            _ => fmt!("%s at offset %u: %s", self.structure(), self.offset(), self.reason())
+        }
+    }
+
+    //errors from a reader over part of the class file carry offsets relative to that part
+    pub fn rebase(&self, base: uint) -> ClassFormatError {
+        match *self {
+            FormatError_UnexpectedEof(offset, ref structure) =>
+                FormatError_UnexpectedEof(base + offset, copy *structure),
+            FormatError_BadMagic(offset, magic) => FormatError_BadMagic(base + offset, magic),
+            FormatError_UnrecognizedTag(offset, ref structure, tag) =>
+                FormatError_UnrecognizedTag(base + offset, copy *structure, tag),
+            FormatError_BadConstantIndex(offset, ref structure, index) =>
+                FormatError_BadConstantIndex(base + offset, copy *structure, index),
+            FormatError_Invalid(offset, ref structure, ref reason) =>
+                FormatError_Invalid(base + offset, copy *structure, copy *reason),
+            //positions are within the descriptor, not the class file
+            FormatError_BadDescriptor(position, ref structure, ref reason) =>
+                FormatError_BadDescriptor(position, copy *structure, copy *reason)
+        }
+    }
+}
+
+pub struct ParseOptions {
+    //keep attributes that fail to parse within their attribute_length as AttrStruct_Other instead of failing
+    lenient_attributes: bool
+}
+
+pub fn ParseOptions() -> ParseOptions {
+    ParseOptions { lenient_attributes: false }
+}
+
 enum ClassAccessFlag {
     ClassAccess_Public = 0x0001,
     ClassAccess_Static = 0x0008,
@@ -22,7 +151,8 @@ enum ClassAccessFlag {
     ClassAccess_Abstract = 0x0400,
     ClassAccess_Synthetic = 0x1000,
     ClassAccess_Annotation = 0x2000,
-    ClassAccess_Enum = 0x4000
+    ClassAccess_Enum = 0x4000,
+    ClassAccess_Module = 0x8000
 }
 
 enum ConstantPoolTag {
@@ -39,27 +169,33 @@ enum ConstantPoolTag {
     PoolTag_NameAndType = 12,
     PoolTag_MethodHandle = 15,
     PoolTag_MethodType = 16,
-    PoolTag_InvokeDynamic = 18
+    PoolTag_Dynamic = 17,
+    PoolTag_InvokeDynamic = 18,
+    PoolTag_Module = 19,
+    PoolTag_Package = 20
 }
 
 //ref: rust #2132
-fn ConstantPoolTag_from_int(ordinal: int) -> ConstantPoolTag {
+fn ConstantPoolTag_from_int(ordinal: int) -> Option<ConstantPoolTag> {
     match ordinal {
-        1 => PoolTag_Utf8,
-        3 => PoolTag_Integer,
-        4 => PoolTag_Float,
-        5 => PoolTag_Long,
-        6 => PoolTag_Double,
-        7 => PoolTag_Class,
-        8 => PoolTag_String,
-        9 => PoolTag_FieldRef,
-        10 => PoolTag_MethodRef,
-        11 => PoolTag_InterfaceMethodRef,
-        12 => PoolTag_NameAndType,
-        15 => PoolTag_MethodHandle,
-        16 => PoolTag_MethodType,
-        18 => PoolTag_InvokeDynamic,
-        _ => fail fmt!("Unrecognized ordinal %?", ordinal)
+        1 => Some(PoolTag_Utf8),
+        3 => Some(PoolTag_Integer),
+        4 => Some(PoolTag_Float),
+        5 => Some(PoolTag_Long),
+        6 => Some(PoolTag_Double),
+        7 => Some(PoolTag_Class),
+        8 => Some(PoolTag_String),
+        9 => Some(PoolTag_FieldRef),
+        10 => Some(PoolTag_MethodRef),
+        11 => Some(PoolTag_InterfaceMethodRef),
+        12 => Some(PoolTag_NameAndType),
+        15 => Some(PoolTag_MethodHandle),
+        16 => Some(PoolTag_MethodType),
+        17 => Some(PoolTag_Dynamic),
+        18 => Some(PoolTag_InvokeDynamic),
+        19 => Some(PoolTag_Module),
+        20 => Some(PoolTag_Package),
+        _ => None
     }
 }
 
@@ -68,40 +204,328 @@ struct ConstantPoolInfo {
     info: ConstantPoolStructure
 }
 
-fn ConstantPoolInfo(reader: io::ReaderUtil) -> ConstantPoolInfo {
-    let tag = ConstantPoolTag_from_int(read_u8(reader) as int);
+fn ConstantPoolInfo(reader: io::Reader) -> Result<ConstantPoolInfo, ClassFormatError> {
+    let s = "cp_info";
+    let offset = reader.tell();
+    let raw_tag = try!(read_u8(reader, s));
+    let tag = match ConstantPoolTag_from_int(raw_tag as int) {
+        Some(tag) => tag,
+        None => return Err(FormatError_UnrecognizedTag(offset, ~"cp_info", raw_tag))
+    };
     let inf: ConstantPoolStructure = match tag {
-        PoolTag_Class => PoolStruct_Class(read_u16(reader)),
-        PoolTag_FieldRef => PoolStruct_FieldRef(read_u16(reader), read_u16(reader)),
-        PoolTag_MethodRef => PoolStruct_MethodRef(read_u16(reader), read_u16(reader)),
-        PoolTag_InterfaceMethodRef => PoolStruct_InterfaceMethodRef(read_u16(reader), read_u16(reader)),
-        PoolTag_String => PoolStruct_String(read_u16(reader)),
-        PoolTag_Integer => PoolStruct_Integer(read_u32(reader)),
-        PoolTag_Float => PoolStruct_Float(read_u32(reader)),
-        PoolTag_Long => PoolStruct_Long(read_u32(reader), read_u32(reader)),
-        PoolTag_NameAndType => PoolStruct_NameAndType(read_u16(reader), read_u16(reader)),
+        PoolTag_Class => PoolStruct_Class(try!(read_u16(reader, s))),
+        PoolTag_FieldRef => PoolStruct_FieldRef(try!(read_u16(reader, s)), try!(read_u16(reader, s))),
+        PoolTag_MethodRef => PoolStruct_MethodRef(try!(read_u16(reader, s)), try!(read_u16(reader, s))),
+        PoolTag_InterfaceMethodRef => PoolStruct_InterfaceMethodRef(try!(read_u16(reader, s)),
+            try!(read_u16(reader, s))),
+        PoolTag_String => PoolStruct_String(try!(read_u16(reader, s))),
+        PoolTag_Integer => PoolStruct_Integer(try!(read_u32(reader, s))),
+        PoolTag_Float => PoolStruct_Float(try!(read_u32(reader, s))),
+        PoolTag_Long => PoolStruct_Long(try!(read_u32(reader, s)), try!(read_u32(reader, s))),
+        PoolTag_Double => PoolStruct_Double(try!(read_u32(reader, s)), try!(read_u32(reader, s))),
+        PoolTag_NameAndType => PoolStruct_NameAndType(try!(read_u16(reader, s)), try!(read_u16(reader, s))),
         PoolTag_Utf8 => {
-            let byts = reader.read_bytes(read_u16(reader) as uint);
-            assert str::is_utf8(byts);
-            PoolStruct_Utf8(str::from_bytes(byts))
+            let length = try!(read_u16(reader, s));
+            let byts_offset = reader.tell();
+            let byts = try!(read_bytes(reader, length as uint, s));
+            match mutf8::decode(byts) {
+                Some(strval) => PoolStruct_Utf8(strval, byts),
+                None => return Err(FormatError_Invalid(byts_offset, ~"CONSTANT_Utf8_info",
+                    ~"invalid modified utf8"))
+            }
         },
-        PoolTag_MethodHandle => PoolStruct_MethodHandle(read_u8(reader), read_u16(reader)),
-        PoolTag_MethodType => PoolStruct_MethodType(read_u16(reader)),
-        PoolTag_InvokeDynamic => PoolStruct_InvokeDynamic(read_u16(reader), read_u16(reader)),            
-        _ => fail fmt!("Unrecognized tag %?", tag)
+        PoolTag_MethodHandle => PoolStruct_MethodHandle(try!(read_u8(reader, s)), try!(read_u16(reader, s))),
+        PoolTag_MethodType => PoolStruct_MethodType(try!(read_u16(reader, s))),
+        PoolTag_Dynamic => PoolStruct_Dynamic(try!(read_u16(reader, s)), try!(read_u16(reader, s))),
+        PoolTag_InvokeDynamic => PoolStruct_InvokeDynamic(try!(read_u16(reader, s)), try!(read_u16(reader, s))),
+        PoolTag_Module => PoolStruct_Module(try!(read_u16(reader, s))),
+        PoolTag_Package => PoolStruct_Package(try!(read_u16(reader, s)))
     };
-    ConstantPoolInfo { tag: tag, info: inf }
+    Ok(ConstantPoolInfo { tag: tag, info: inf })
 }
 
-fn read_constant_pool(reader: io::ReaderUtil) -> ~[ConstantPoolInfo] {
-    let constant_pool_count = read_u16(reader);
-    let mut constant_pool: ~[ConstantPoolInfo] = ~[];
-    vec::reserve(&mut constant_pool, constant_pool_count as uint - 1);
+pub struct ConstantPool {
+    entries: ~[ConstantPoolEntry]
+}
+
+//the lookups reading attributes makes, so they decode against a ConstantPool or straight from the class bytes
+//through a class_view::ConstantPoolView alike
+pub trait ConstantLookup {
+    //None for index 0, out of range indices and the slot following a Long or Double
+    fn tag_of(&self, index: u16) -> Option<ConstantPoolTag>;
+    fn utf8_of(&self, index: u16) -> Option<~str>;
+}
+
+impl ConstantPool: ConstantLookup {
+    fn tag_of(&self, index: u16) -> Option<ConstantPoolTag> {
+        match self.get(index) {
+            Some(info) => Some(info.tag),
+            None => None
+        }
+    }
+
+    fn utf8_of(&self, index: u16) -> Option<~str> {
+        match self.get_utf8(index) {
+            Some(strval) => Some(str::from_slice(strval)),
+            None => None
+        }
+    }
+}
+
+enum ConstantPoolEntry {
+    //index 0 and the slot following a Long or Double
+    PoolEntry_Unusable,
+    PoolEntry_Info(ConstantPoolInfo)
+}
+
+impl ConstantPool {
+    //the constant_pool_count, one more than the highest valid index
+    pub fn count(&self) -> uint { self.entries.len() }
+
+    pub fn get(&self, index: u16) -> Option<&self/ConstantPoolInfo> {
+        if index as uint >= self.entries.len() {
+            return None;
+        }
+        match self.entries[index] {
+            PoolEntry_Info(ref info) => Some(info),
+            PoolEntry_Unusable => None
+        }
+    }
+
+    pub fn get_utf8(&self, index: u16) -> Option<&self/str> {
+        match self.get(index) {
+            Some(&ConstantPoolInfo { info: PoolStruct_Utf8(ref strval, _), _ }) =>
+                Some(str::view(*strval, 0, strval.len())),
+            _ => None
+        }
+    }
+
+    //the internal name a Class constant points at
+    pub fn get_class_name(&self, index: u16) -> Option<&self/str> {
+        match self.get(index) {
+            Some(&ConstantPoolInfo { info: PoolStruct_Class(name_index), _ }) => self.get_utf8(name_index),
+            _ => None
+        }
+    }
+
+    //(name, descriptor)
+    pub fn get_name_and_type(&self, index: u16) -> Option<(&self/str, &self/str)> {
+        match self.get(index) {
+            Some(&ConstantPoolInfo { info: PoolStruct_NameAndType(name_index, descriptor_index), _ }) =>
+                match (self.get_utf8(name_index), self.get_utf8(descriptor_index)) {
+                    (Some(name), Some(descriptor)) => Some((name, descriptor)),
+                    _ => None
+                },
+            _ => None
+        }
+    }
+
+    //resolves a FieldRef, MethodRef or InterfaceMethodRef
+    pub fn get_member_ref(&self, index: u16) -> Option<MemberRef> {
+        let (kind, class_index, name_and_type_index) = match self.get(index) {
+            Some(&ConstantPoolInfo { info: PoolStruct_FieldRef(class_index, name_and_type_index), _ }) =>
+                (MemberRef_Field, class_index, name_and_type_index),
+            Some(&ConstantPoolInfo { info: PoolStruct_MethodRef(class_index, name_and_type_index), _ }) =>
+                (MemberRef_Method, class_index, name_and_type_index),
+            Some(&ConstantPoolInfo { info: PoolStruct_InterfaceMethodRef(class_index, name_and_type_index), _ }) =>
+                (MemberRef_InterfaceMethod, class_index, name_and_type_index),
+            _ => return None
+        };
+        match (self.get_class_name(class_index), self.get_name_and_type(name_and_type_index)) {
+            (Some(owner), Some((name, descriptor))) => Some(MemberRef {
+                kind: kind,
+                owner: str::from_slice(owner),
+                name: str::from_slice(name),
+                descriptor: str::from_slice(descriptor)
+            }),
+            _ => None
+        }
+    }
+
+    //the index of a Utf8 constant holding the string, appending one when there is none, None when the pool is full
+    pub fn utf8_index(&mut self, strval: &str) -> Option<u16> {
+        for self.entries.eachi |index, entry| {
+            match *entry {
+                PoolEntry_Info(ConstantPoolInfo { info: PoolStruct_Utf8(ref existing, _), _ })
+                        if str::eq_slice(*existing, strval) => return Some(index as u16),
+                _ => ()
+            }
+        }
+        if self.entries.len() >= 65535 {
+            return None;
+        }
+        self.entries.push(PoolEntry_Info(ConstantPoolInfo { tag: PoolTag_Utf8, info: Utf8(strval) }));
+        Some((self.entries.len() - 1) as u16)
+    }
+
+    pub fn each(&self, f: fn(u16, &ConstantPoolInfo) -> bool) {
+        for self.entries.eachi |index, entry| {
+            match *entry {
+                PoolEntry_Info(ref info) => if !f(index as u16, info) { break; },
+                PoolEntry_Unusable => ()
+            }
+        }
+    }
+}
+
+pub enum MemberRefKind {
+    MemberRef_Field,
+    MemberRef_Method,
+    MemberRef_InterfaceMethod
+}
+
+pub struct MemberRef {
+    kind: MemberRefKind,
+    //internal name of the class or interface named by class_index
+    owner: ~str,
+    name: ~str,
+    descriptor: ~str
+}
+
+impl MemberRef {
+    pub fn is_field(&self) -> bool {
+        match self.kind {
+            MemberRef_Field => true,
+            _ => false
+        }
+    }
+
+    //e.g. java/lang/String.length:()I
+    pub fn to_str(&self) -> ~str {
+        fmt!("%s.%s:%s", self.owner, self.name, self.descriptor)
+    }
+}
+
+fn read_constant_pool(reader: io::Reader) -> Result<ConstantPool, ClassFormatError> {
+    let offset = reader.tell();
+    let constant_pool_count = try!(read_u16(reader, "constant_pool_count"));
+    if constant_pool_count == 0 {
+        return Err(FormatError_Invalid(offset, ~"constant_pool_count", ~"count must be at least 1"));
+    }
+    let mut entries: ~[ConstantPoolEntry] = ~[PoolEntry_Unusable];
+    let mut offsets: ~[uint] = ~[offset];
+    vec::reserve(&mut entries, constant_pool_count as uint);
     debug!("Running for %? consts", constant_pool_count);
-    for iter::repeat(constant_pool_count as uint - 1) {
-        constant_pool.push(ConstantPoolInfo(reader));
+    while entries.len() < constant_pool_count as uint {
+        let entry_offset = reader.tell();
+        let info = try!(ConstantPoolInfo(reader));
+        //longs and doubles take up two slots
+        let wide = match info.tag { PoolTag_Long | PoolTag_Double => true, _ => false };
+        entries.push(PoolEntry_Info(info));
+        offsets.push(entry_offset);
+        if wide {
+            if entries.len() >= constant_pool_count as uint {
+                return Err(FormatError_Invalid(entry_offset, ~"cp_info", ~"8-byte constant in last slot"));
+            }
+            entries.push(PoolEntry_Unusable);
+            offsets.push(entry_offset);
+        }
+    }
+    let constant_pool = ConstantPool { entries: entries };
+    try!(check_constant_pool(&constant_pool, offsets));
+    Ok(constant_pool)
+}
+
+fn check_constant_pool(constant_pool: &ConstantPool, offsets: &[uint]) -> Result<(), ClassFormatError> {
+    let s = "cp_info";
+    for constant_pool.each |index, info| {
+        let offset = offsets[index];
+        match info.info {
+            PoolStruct_Class(name_index) => try!(check_constant(constant_pool, name_index, PoolTag_Utf8, offset, s)),
+            PoolStruct_FieldRef(class_index, name_and_type_index) |
+            PoolStruct_MethodRef(class_index, name_and_type_index) |
+            PoolStruct_InterfaceMethodRef(class_index, name_and_type_index) => {
+                try!(check_constant(constant_pool, class_index, PoolTag_Class, offset, s));
+                try!(check_constant(constant_pool, name_and_type_index, PoolTag_NameAndType, offset, s));
+            },
+            PoolStruct_String(string_index) => try!(check_constant(constant_pool, string_index, PoolTag_Utf8,
+                offset, s)),
+            PoolStruct_NameAndType(name_index, descriptor_index) => {
+                try!(check_constant(constant_pool, name_index, PoolTag_Utf8, offset, s));
+                try!(check_constant(constant_pool, descriptor_index, PoolTag_Utf8, offset, s));
+            },
+            PoolStruct_MethodHandle(reference_kind, reference_index) => {
+                //JVMS 4.4.8, interface methods are allowed for invokestatic and invokespecial from version 52
+                let tags = match reference_kind {
+                    1..4 => ~[PoolTag_FieldRef],
+                    5 | 8 => ~[PoolTag_MethodRef],
+                    6 | 7 => ~[PoolTag_MethodRef, PoolTag_InterfaceMethodRef],
+                    9 => ~[PoolTag_InterfaceMethodRef],
+                    _ => return Err(FormatError_Invalid(offset, ~"CONSTANT_MethodHandle_info",
+                        fmt!("bad reference_kind %u", reference_kind as uint)))
+                };
+                try!(check_constant_in(constant_pool, reference_index, tags, offset, s));
+            },
+            PoolStruct_MethodType(descriptor_index) => try!(check_constant(constant_pool, descriptor_index,
+                PoolTag_Utf8, offset, s)),
+            PoolStruct_Dynamic(_, name_and_type_index) |
+            PoolStruct_InvokeDynamic(_, name_and_type_index) => try!(check_constant(constant_pool,
+                name_and_type_index, PoolTag_NameAndType, offset, s)),
+            PoolStruct_Module(name_index) | PoolStruct_Package(name_index) => try!(check_constant(constant_pool,
+                name_index, PoolTag_Utf8, offset, s)),
+            _ => ()
+        }
+    }
+    Ok(())
+}
+
+fn check_constant<P: ConstantLookup>(constant_pool: &P, index: u16, tag: ConstantPoolTag, offset: uint, structure: &str)
+        -> Result<(), ClassFormatError> {
+    match constant_pool.tag_of(index) {
+        Some(found) if found as int == tag as int => Ok(()),
+        _ => Err(FormatError_BadConstantIndex(offset, str::from_slice(structure), index))
+    }
+}
+
+fn check_constant_in<P: ConstantLookup>(constant_pool: &P, index: u16, tags: &[ConstantPoolTag], offset: uint,
+        structure: &str) -> Result<(), ClassFormatError> {
+    match constant_pool.tag_of(index) {
+        Some(found) if tags.any(|tag| found as int == *tag as int) => Ok(()),
+        _ => Err(FormatError_BadConstantIndex(offset, str::from_slice(structure), index))
     }
-    constant_pool
+}
+
+fn read_constant_index_in<P: ConstantLookup>(constant_pool: &P, reader: io::Reader, tags: &[ConstantPoolTag],
+        structure: &str) -> Result<u16, ClassFormatError> {
+    let offset = reader.tell();
+    let index = try!(read_u16(reader, structure));
+    try!(check_constant_in(constant_pool, index, tags, offset, structure));
+    Ok(index)
+}
+
+//constants ldc and bootstrap arguments may load, JVMS 4.4
+fn loadable_tags() -> ~[ConstantPoolTag] {
+    ~[PoolTag_Integer, PoolTag_Float, PoolTag_Long, PoolTag_Double, PoolTag_Class, PoolTag_String,
+        PoolTag_MethodHandle, PoolTag_MethodType, PoolTag_Dynamic]
+}
+
+fn read_constant_index<P: ConstantLookup>(constant_pool: &P, reader: io::Reader, tag: ConstantPoolTag, structure: &str)
+        -> Result<u16, ClassFormatError> {
+    let offset = reader.tell();
+    let index = try!(read_u16(reader, structure));
+    try!(check_constant(constant_pool, index, tag, offset, structure));
+    Ok(index)
+}
+
+fn read_optional_constant_index<P: ConstantLookup>(constant_pool: &P, reader: io::Reader, tag: ConstantPoolTag,
+        structure: &str) -> Result<u16, ClassFormatError> {
+    let offset = reader.tell();
+    let index = try!(read_u16(reader, structure));
+    if index != 0 {
+        try!(check_constant(constant_pool, index, tag, offset, structure));
+    }
+    Ok(index)
+}
+
+fn read_constant_index_vec<P: ConstantLookup>(constant_pool: &P, reader: io::Reader, tag: ConstantPoolTag,
+        structure: &str) -> Result<~[u16], ClassFormatError> {
+    let count = try!(read_u16(reader, structure));
+    let mut vec: ~[u16] = ~[];
+    vec::reserve(&mut vec, count as uint);
+    for iter::repeat(count as uint) {
+        vec.push(try!(read_constant_index(constant_pool, reader, tag, structure)));
+    }
+    Ok(vec)
 }
 
 enum ConstantPoolStructure {
@@ -113,12 +537,51 @@ enum ConstantPoolStructure {
     PoolStruct_Integer(u32 /*bytes*/),
     PoolStruct_Float(u32 /*bytes*/),
     PoolStruct_Long(u32 /*high_bytes*/, u32 /*low_bytes*/),
+    PoolStruct_Double(u32 /*high_bytes*/, u32 /*low_bytes*/),
     PoolStruct_NameAndType(u16 /*name_index*/, u16 /*descriptor_index*/),
-    //PoolStruct_Utf8(u16 /*length*/, ~[u8] /*bytes*/),
-    PoolStruct_Utf8(~str /*str*/),
+    PoolStruct_Utf8(~str /*str*/, ~[u8] /*bytes*/),
     PoolStruct_MethodHandle(u8 /*reference_kind*/, u16 /*reference_index*/),
     PoolStruct_MethodType(u16 /*descriptor_index*/),
-    PoolStruct_InvokeDynamic(u16 /*bootstrap_method_attr_index*/, u16 /*name_and_type_index*/)
+    PoolStruct_Dynamic(u16 /*bootstrap_method_attr_index*/, u16 /*name_and_type_index*/),
+    PoolStruct_InvokeDynamic(u16 /*bootstrap_method_attr_index*/, u16 /*name_and_type_index*/),
+    PoolStruct_Module(u16 /*name_index*/),
+    PoolStruct_Package(u16 /*name_index*/)
+}
+
+//builds a utf8 entry whose bytes are the modified utf8 encoding of the string
+pub fn Utf8(strval: &str) -> ConstantPoolStructure {
+    PoolStruct_Utf8(str::from_slice(strval), mutf8::encode(strval))
+}
+
+impl ConstantPoolStructure {
+    pub fn integer_value(&self) -> Option<i32> {
+        match *self {
+            PoolStruct_Integer(bytes) => Some(bytes as i32),
+            _ => None
+        }
+    }
+
+    pub fn long_value(&self) -> Option<i64> {
+        match *self {
+            PoolStruct_Long(high_bytes, low_bytes) => Some(((high_bytes as u64 << 32) | low_bytes as u64) as i64),
+            _ => None
+        }
+    }
+
+    pub fn float_value(&self) -> Option<f32> {
+        match *self {
+            PoolStruct_Float(bytes) => Some(unsafe { cast::transmute(bytes) }),
+            _ => None
+        }
+    }
+
+    pub fn double_value(&self) -> Option<f64> {
+        match *self {
+            PoolStruct_Double(high_bytes, low_bytes) =>
+                Some(unsafe { cast::transmute((high_bytes as u64 << 32) | low_bytes as u64) }),
+            _ => None
+        }
+    }
 }
 
 struct FieldInfo {
@@ -128,27 +591,44 @@ struct FieldInfo {
     attributes: ~[AttributeInfo]
 }
 
-fn FieldInfo(constant_pool: &~[ConstantPoolInfo], reader: io::ReaderUtil) -> FieldInfo {
-    let access_flags = read_u16(reader);
-    let name_index = read_u16(reader);
-    let descriptor_index = read_u16(reader);
-    let attributes = read_attributes(constant_pool, reader);
-    FieldInfo {
+impl FieldInfo {
+    pub fn flags(&self) -> access_flags::FieldAccessFlags {
+        access_flags::FieldAccessFlags { bits: self.access_flags }
+    }
+
+    //None when the index is not a utf8 constant, reading the class file checks both
+    pub fn name(&self, cp: &r/ConstantPool) -> Option<&r/str> {
+        cp.get_utf8(self.name_index)
+    }
+
+    pub fn descriptor(&self, cp: &r/ConstantPool) -> Option<&r/str> {
+        cp.get_utf8(self.descriptor_index)
+    }
+}
+
+fn FieldInfo<P: ConstantLookup>(constant_pool: &P, reader: io::Reader, options: &ParseOptions)
+        -> Result<FieldInfo, ClassFormatError> {
+    let access_flags = try!(read_u16(reader, "field_info"));
+    let name_index = try!(read_constant_index(constant_pool, reader, PoolTag_Utf8, "field_info"));
+    let descriptor_index = try!(read_constant_index(constant_pool, reader, PoolTag_Utf8, "field_info"));
+    let attributes = try!(read_attributes(constant_pool, reader, AttrLocation_Field, options));
+    Ok(FieldInfo {
         access_flags: access_flags,
         name_index: name_index,
         descriptor_index: descriptor_index,
         attributes: attributes
-    }
+    })
 }
 
-fn read_fields(constant_pool: ~[ConstantPoolInfo], reader: io::ReaderUtil) -> ~[FieldInfo] {
-    let field_count = read_u16(reader);
+fn read_fields<P: ConstantLookup>(constant_pool: &P, reader: io::Reader, options: &ParseOptions)
+        -> Result<~[FieldInfo], ClassFormatError> {
+    let field_count = try!(read_u16(reader, "fields_count"));
     let mut fields: ~[FieldInfo] = ~[];
     vec::reserve(&mut fields, field_count as uint);
     for iter::repeat(field_count as uint) {
-        fields.push(FieldInfo(&constant_pool, reader));
+        fields.push(try!(FieldInfo(constant_pool, reader, options)));
     }
-    fields
+    Ok(fields)
 }
 
 
@@ -171,6 +651,46 @@ struct MethodInfo {
     attributes: ~[AttributeInfo]
 }
 
+impl MethodInfo {
+    pub fn flags(&self) -> access_flags::MethodAccessFlags {
+        access_flags::MethodAccessFlags { bits: self.access_flags }
+    }
+
+    //None when the index is not a utf8 constant, reading the class file checks both
+    pub fn name(&self, cp: &r/ConstantPool) -> Option<&r/str> {
+        cp.get_utf8(self.name_index)
+    }
+
+    pub fn descriptor(&self, cp: &r/ConstantPool) -> Option<&r/str> {
+        cp.get_utf8(self.descriptor_index)
+    }
+}
+
+fn MethodInfo<P: ConstantLookup>(constant_pool: &P, reader: io::Reader, options: &ParseOptions)
+        -> Result<MethodInfo, ClassFormatError> {
+    let access_flags = try!(read_u16(reader, "method_info"));
+    let name_index = try!(read_constant_index(constant_pool, reader, PoolTag_Utf8, "method_info"));
+    let descriptor_index = try!(read_constant_index(constant_pool, reader, PoolTag_Utf8, "method_info"));
+    let attributes = try!(read_attributes(constant_pool, reader, AttrLocation_Method, options));
+    Ok(MethodInfo {
+        access_flags: access_flags,
+        name_index: name_index,
+        descriptor_index: descriptor_index,
+        attributes: attributes
+    })
+}
+
+fn read_methods<P: ConstantLookup>(constant_pool: &P, reader: io::Reader, options: &ParseOptions)
+        -> Result<~[MethodInfo], ClassFormatError> {
+    let method_count = try!(read_u16(reader, "methods_count"));
+    let mut methods: ~[MethodInfo] = ~[];
+    vec::reserve(&mut methods, method_count as uint);
+    for iter::repeat(method_count as uint) {
+        methods.push(try!(MethodInfo(constant_pool, reader, options)));
+    }
+    Ok(methods)
+}
+
 enum MethodAccessFlag {
     MethodAccess_Public = 0x0001,
     MethodAccess_Private = 0x0002,
@@ -192,60 +712,126 @@ struct AttributeInfo {
     info: AttributeInfoStructure
 }
 
-fn read_attributes(constant_pool: &~[ConstantPoolInfo], reader: io::ReaderUtil) -> ~[AttributeInfo] {
-    let attribute_count = read_u16(reader);
+//where an attribute table sits, some attributes are only meaningful in certain places
+enum AttributeLocation {
+    AttrLocation_ClassFile,
+    AttrLocation_Field,
+    AttrLocation_Method,
+    AttrLocation_Code,
+    AttrLocation_RecordComponent
+}
+
+fn read_attributes<P: ConstantLookup>(constant_pool: &P, reader: io::Reader, location: AttributeLocation,
+        options: &ParseOptions) -> Result<~[AttributeInfo], ClassFormatError> {
+    let attribute_count = try!(read_u16(reader, "attributes_count"));
     let mut attributes: ~[AttributeInfo] = ~[];
     vec::reserve(&mut attributes, attribute_count as uint);
     for iter::repeat(attribute_count as uint) {
-        attributes.push(AttributeInfo(constant_pool, reader));
+        attributes.push(try!(AttributeInfo(constant_pool, reader, location, options)));
     }
-    attributes
+    Ok(attributes)
 }
 
-fn AttributeInfo(constant_pool: &~[ConstantPoolInfo], reader: io::ReaderUtil) -> AttributeInfo {
-    let attribute_name_index = read_u16(reader);
-    let attribute_length = read_u32(reader);
+fn AttributeInfo<P: ConstantLookup>(constant_pool: &P, reader: io::Reader, location: AttributeLocation,
+        options: &ParseOptions) -> Result<AttributeInfo, ClassFormatError> {
+    let offset = reader.tell();
+    let attribute_name_index = try!(read_u16(reader, "attribute_info"));
+    let attribute_length = try!(read_u32(reader, "attribute_info"));
     //lookup the name
-    let attribute_name = &constant_pool[attribute_name_index];
-    let inf: AttributeInfoStructure = match attribute_name.info {
-        PoolStruct_Utf8(strval) => {
-            match strval {
-                //TODO: byte length verification please
-                ~"ConstantValue" => AttrStruct_ConstantValue(read_u16(reader)),
-                ~"Code" => AttrStruct_Code(CodeAttributeInfo(constant_pool, reader)),
-                ~"StackMapTable" => AttrStruct_StackMapTable(read_stack_map_table(reader)),
-                ~"Exceptions" => AttrStruct_Exceptions(read_u16_vec(reader)),
-                ~"InnerClasses" => AttrStruct_InnerClasses(read_inner_class_attributes(reader)),
-                ~"EnclosingMethod" => AttrStruct_EnclosingMethod(read_u16(reader), read_u16(reader)),
-                ~"Synthetic" => AttrStruct_Synthetic,
-                ~"Signature" => AttrStruct_Signature(read_u16(reader)),
-                ~"SourceFile" => AttrStruct_SourceFile(read_u16(reader)),
-                ~"SourceDebugExtension" => AttrStruct_SourceDebugExtension(reader.read_bytes(attribute_length as uint)),
-                ~"LineNumberTable" => AttrStruct_LineNumberTable(read_line_number_table(reader)),
-                ~"LocalVariableTable" => AttrStruct_LocalVariableTable(read_local_variable_table(reader)),
-                ~"LocalVariableTypeTable" => AttrStruct_LocalVariableTypeTable(read_local_variable_type_table(reader)),
-                ~"Deprecated" => AttrStruct_Deprecated,
-                ~"RuntimeVisibleAnnotations" => AttrStruct_RuntimeVisibleAnnotations(read_annotations(reader)),
-                ~"RuntimeInvisibleAnnotations" => AttrStruct_RuntimeInvisibleAnnotations(read_annotations(reader)),
-                ~"RuntimeVisibleParameterAnnotations" => AttrStruct_RuntimeVisibleParameterAnnotations(
-                    read_parameter_annotations(reader)),
-                ~"RuntimeInvisibleParameterAnnotations" => AttrStruct_RuntimeInvisibleParameterAnnotations(
-                    read_parameter_annotations(reader)),
-                ~"AnnotationDefault" => AttrStruct_AnnotationDefault(AnnotationElementValue(reader)),
-                ~"BootstrapMethods" => AttrStruct_BootstrapMethods(read_bootstrap_methods(reader)),
-                _ => {
-                    debug!("Warning, unrecognized annotation: %?", strval);
-                    AttrStruct_Other(reader.read_bytes(attribute_length as uint))
-                }
-            }
+    let attribute_name = match (constant_pool.tag_of(attribute_name_index),
+            constant_pool.utf8_of(attribute_name_index)) {
+        (Some(_), Some(strval)) => strval,
+        (Some(_), None) => return Err(FormatError_Invalid(offset, ~"attribute_info", ~"attribute name not a utf8")),
+        (None, _) => return Err(FormatError_BadConstantIndex(offset, ~"attribute_info", attribute_name_index))
+    };
+    //parse the body from exactly attribute_length bytes so a bad attribute cannot desynchronize the rest
+    let body_offset = reader.tell();
+    let bytes = try!(read_bytes(reader, attribute_length as uint, "attribute_info"));
+    let parsed = do io::with_bytes_reader(bytes) |body| {
+        read_attribute_body(constant_pool, copy attribute_name, body, bytes.len(), location, options)
+    };
+    let inf = match parsed {
+        Ok(inf) => inf,
+        Err(ref err) if options.lenient_attributes => {
+            debug!("Warning, keeping malformed %s attribute as raw bytes: %s", attribute_name,
+                err.rebase(body_offset).to_str());
+            AttrStruct_Other(bytes)
         },
-        _ => fail ~"Attribute not a utf8"
+        Err(ref err) => return Err(err.rebase(body_offset))
     };
-    AttributeInfo {
+    Ok(AttributeInfo {
         attribute_name_index: attribute_name_index,
         attribute_length: attribute_length,
         info: inf
+    })
+}
+
+fn read_attribute_body<P: ConstantLookup>(constant_pool: &P, attribute_name: ~str, reader: io::Reader, length: uint,
+        location: AttributeLocation, options: &ParseOptions) -> Result<AttributeInfoStructure, ClassFormatError> {
+    let inf = match attribute_name {
+        ~"ConstantValue" => AttrStruct_ConstantValue(try!(read_constant_index_in(constant_pool, reader,
+            [PoolTag_Integer, PoolTag_Float, PoolTag_Long, PoolTag_Double, PoolTag_String],
+            "ConstantValue_attribute"))),
+        ~"Code" => AttrStruct_Code(try!(CodeAttributeInfo(constant_pool, reader, options))),
+        ~"StackMapTable" => AttrStruct_StackMapTable(try!(read_stack_map_table(constant_pool, reader))),
+        ~"Exceptions" => AttrStruct_Exceptions(try!(read_constant_index_vec(constant_pool, reader,
+            PoolTag_Class, "Exceptions_attribute"))),
+        ~"InnerClasses" => AttrStruct_InnerClasses(try!(read_inner_class_attributes(constant_pool, reader))),
+        ~"EnclosingMethod" => AttrStruct_EnclosingMethod(try!(read_constant_index(constant_pool, reader,
+            PoolTag_Class, "EnclosingMethod_attribute")), try!(read_optional_constant_index(constant_pool, reader,
+            PoolTag_NameAndType, "EnclosingMethod_attribute"))),
+        ~"Synthetic" => AttrStruct_Synthetic,
+        ~"Signature" => AttrStruct_Signature(try!(read_constant_index(constant_pool, reader, PoolTag_Utf8,
+            "Signature_attribute"))),
+        ~"SourceFile" => AttrStruct_SourceFile(try!(read_constant_index(constant_pool, reader, PoolTag_Utf8,
+            "SourceFile_attribute"))),
+        ~"SourceDebugExtension" => AttrStruct_SourceDebugExtension(try!(read_bytes(reader,
+            length, "SourceDebugExtension_attribute"))),
+        ~"LineNumberTable" => AttrStruct_LineNumberTable(try!(read_line_number_table(reader))),
+        ~"LocalVariableTable" => AttrStruct_LocalVariableTable(try!(read_local_variable_table(constant_pool,
+            reader))),
+        ~"LocalVariableTypeTable" => AttrStruct_LocalVariableTypeTable(
+            try!(read_local_variable_type_table(constant_pool, reader))),
+        ~"Deprecated" => AttrStruct_Deprecated,
+        ~"RuntimeVisibleAnnotations" => AttrStruct_RuntimeVisibleAnnotations(try!(read_annotations(constant_pool,
+            reader))),
+        ~"RuntimeInvisibleAnnotations" => AttrStruct_RuntimeInvisibleAnnotations(
+            try!(read_annotations(constant_pool, reader))),
+        ~"RuntimeVisibleParameterAnnotations" => AttrStruct_RuntimeVisibleParameterAnnotations(
+            try!(read_parameter_annotations(constant_pool, reader))),
+        ~"RuntimeInvisibleParameterAnnotations" => AttrStruct_RuntimeInvisibleParameterAnnotations(
+            try!(read_parameter_annotations(constant_pool, reader))),
+        ~"RuntimeVisibleTypeAnnotations" => AttrStruct_RuntimeVisibleTypeAnnotations(
+            try!(read_type_annotations(constant_pool, reader, location))),
+        ~"RuntimeInvisibleTypeAnnotations" => AttrStruct_RuntimeInvisibleTypeAnnotations(
+            try!(read_type_annotations(constant_pool, reader, location))),
+        ~"AnnotationDefault" => AttrStruct_AnnotationDefault(try!(AnnotationElementValue(constant_pool, reader))),
+        ~"BootstrapMethods" => AttrStruct_BootstrapMethods(try!(read_bootstrap_methods(constant_pool, reader))),
+        ~"MethodParameters" => AttrStruct_MethodParameters(try!(read_method_parameters(constant_pool,
+            reader))),
+        ~"Module" => AttrStruct_Module(try!(ModuleAttributeInfo(constant_pool, reader))),
+        ~"ModulePackages" => AttrStruct_ModulePackages(try!(read_constant_index_vec(constant_pool, reader,
+            PoolTag_Package, "ModulePackages_attribute"))),
+        ~"ModuleMainClass" => AttrStruct_ModuleMainClass(try!(read_constant_index(constant_pool, reader,
+            PoolTag_Class, "ModuleMainClass_attribute"))),
+        ~"NestHost" => AttrStruct_NestHost(try!(read_constant_index(constant_pool, reader, PoolTag_Class,
+            "NestHost_attribute"))),
+        ~"NestMembers" => AttrStruct_NestMembers(try!(read_constant_index_vec(constant_pool, reader,
+            PoolTag_Class, "NestMembers_attribute"))),
+        ~"Record" => AttrStruct_Record(try!(read_record_components(constant_pool, reader, options))),
+        ~"PermittedSubclasses" => AttrStruct_PermittedSubclasses(try!(read_constant_index_vec(constant_pool,
+            reader, PoolTag_Class, "PermittedSubclasses_attribute"))),
+        _ => {
+            debug!("Warning, unrecognized attribute: %?", attribute_name);
+            AttrStruct_Other(try!(read_bytes(reader, length, "attribute_info")))
+        }
+    };
+    let consumed = reader.tell();
+    if consumed != length {
+        return Err(FormatError_Invalid(consumed, fmt!("%s_attribute", attribute_name),
+            fmt!("%u of attribute_length %u bytes left unread", length - consumed, length)));
     }
+    Ok(inf)
 }
 
 enum AttributeInfoStructure {
@@ -267,8 +853,18 @@ enum AttributeInfoStructure {
     AttrStruct_RuntimeInvisibleAnnotations(~[AnnotationInfo] /*annotations*/),
     AttrStruct_RuntimeVisibleParameterAnnotations(~[ParameterAnnotationInfo] /*parameter_annotations*/),
     AttrStruct_RuntimeInvisibleParameterAnnotations(~[ParameterAnnotationInfo] /*parameter_annotations*/),
+    AttrStruct_RuntimeVisibleTypeAnnotations(~[TypeAnnotationInfo] /*annotations*/),
+    AttrStruct_RuntimeInvisibleTypeAnnotations(~[TypeAnnotationInfo] /*annotations*/),
     AttrStruct_AnnotationDefault(AnnotationElementValue /*default_value*/),
     AttrStruct_BootstrapMethods(~[BootstrapMethodInfo] /*bootstrap_methods*/),
+    AttrStruct_MethodParameters(~[MethodParameterInfo] /*parameters*/),
+    AttrStruct_Module(ModuleAttributeInfo),
+    AttrStruct_ModulePackages(~[u16] /*package_index*/),
+    AttrStruct_ModuleMainClass(u16 /*main_class_index*/),
+    AttrStruct_NestHost(u16 /*host_class_index*/),
+    AttrStruct_NestMembers(~[u16] /*classes*/),
+    AttrStruct_Record(~[RecordComponentInfo] /*components*/),
+    AttrStruct_PermittedSubclasses(~[u16] /*classes*/),
     AttrStruct_Other(~[u8] /*bytes*/)
 }
 
@@ -277,22 +873,24 @@ struct CodeAttributeInfo {
     max_locals: u16,
     code: ~[u8],
     exception_table: ~[ExceptionTableInfo],
-    attributes: ~[AttributeInfo]    
+    attributes: ~[AttributeInfo]
 }
 
-fn CodeAttributeInfo(constant_pool: &~[ConstantPoolInfo], reader: io::ReaderUtil) -> CodeAttributeInfo {
-    let max_stack = read_u16(reader);
-    let max_locals = read_u16(reader);
-    let code = reader.read_bytes(read_u32(reader) as uint);
-    let exception_table = read_exception_table(reader);
-    let attributes = read_attributes(constant_pool, reader);
-    CodeAttributeInfo {
+fn CodeAttributeInfo<P: ConstantLookup>(constant_pool: &P, reader: io::Reader, options: &ParseOptions)
+        -> Result<CodeAttributeInfo, ClassFormatError> {
+    let max_stack = try!(read_u16(reader, "Code_attribute"));
+    let max_locals = try!(read_u16(reader, "Code_attribute"));
+    let code_length = try!(read_u32(reader, "Code_attribute"));
+    let code = try!(read_bytes(reader, code_length as uint, "Code_attribute"));
+    let exception_table = try!(read_exception_table(reader));
+    let attributes = try!(read_attributes(constant_pool, reader, AttrLocation_Code, options));
+    Ok(CodeAttributeInfo {
         max_stack: max_stack,
         max_locals: max_locals,
         code: code,
         exception_table: exception_table,
         attributes: attributes
-    }
+    })
 }
 
 struct ExceptionTableInfo {
@@ -302,19 +900,20 @@ struct ExceptionTableInfo {
     catch_type: u16
 }
 
-fn read_exception_table(reader: io::ReaderUtil) -> ~[ExceptionTableInfo] {
-    let exception_table_length = read_u16(reader);
+fn read_exception_table(reader: io::Reader) -> Result<~[ExceptionTableInfo], ClassFormatError> {
+    let s = "exception_table";
+    let exception_table_length = try!(read_u16(reader, s));
     let mut exception_table: ~[ExceptionTableInfo] = ~[];
     vec::reserve(&mut exception_table, exception_table_length as uint);
     for iter::repeat(exception_table_length as uint) {
         exception_table.push(ExceptionTableInfo {
-            start_pc: read_u16(reader),
-            end_pc: read_u16(reader),
-            handler_pc: read_u16(reader),
-            catch_type: read_u16(reader)
+            start_pc: try!(read_u16(reader, s)),
+            end_pc: try!(read_u16(reader, s)),
+            handler_pc: try!(read_u16(reader, s)),
+            catch_type: try!(read_u16(reader, s))
         });
     }
-    exception_table
+    Ok(exception_table)
 }
 
 struct StackMapFrame {
@@ -322,31 +921,36 @@ struct StackMapFrame {
     info: StackMapFrameType
 }
 
-fn read_stack_map_table(reader: io::ReaderUtil) -> ~[StackMapFrame] {
-    let stack_map_length = read_u16(reader);
+fn read_stack_map_table<P: ConstantLookup>(constant_pool: &P, reader: io::Reader)
+        -> Result<~[StackMapFrame], ClassFormatError> {
+    let stack_map_length = try!(read_u16(reader, "StackMapTable_attribute"));
     let mut stack_map_table: ~[StackMapFrame] = ~[];
     vec::reserve(&mut stack_map_table, stack_map_length as uint);
     for iter::repeat(stack_map_length as uint) {
-        stack_map_table.push(StackMapFrame(reader));
+        stack_map_table.push(try!(StackMapFrame(constant_pool, reader)));
     }
-    stack_map_table
+    Ok(stack_map_table)
 }
 
-fn StackMapFrame(reader: io::ReaderUtil) -> StackMapFrame {
-    let frame_type = read_u8(reader);
+fn StackMapFrame<P: ConstantLookup>(constant_pool: &P, reader: io::Reader) -> Result<StackMapFrame, ClassFormatError> {
+    let s = "stack_map_frame";
+    let cp = constant_pool;
+    let offset = reader.tell();
+    let frame_type = try!(read_u8(reader, s));
     let inf = match frame_type {
         0..63 => StackFrame_Same,
-        64..127 => StackFrame_SameLocalsStackItem(VerificationTypeInfo(reader)),
-        247 => StackFrame_SameLocalsStackItemExtended(read_u16(reader), VerificationTypeInfo(reader)),
-        248..250 => StackFrame_Chop(read_u16(reader)),
-        251 => StackFrame_SameExtended(read_u16(reader)),
-        252..254 => StackFrame_Append(read_u16(reader), read_verification_type_infos_with_count(
-            frame_type as uint - 251, reader)),
-        255 => StackFrame_Full(read_u16(reader), read_verification_type_infos(reader),
-            read_verification_type_infos(reader)),
-        _ => fail fmt!("Unrecognized stack frame type: %?", frame_type)
+        64..127 => StackFrame_SameLocalsStackItem(try!(VerificationTypeInfo(cp, reader))),
+        247 => StackFrame_SameLocalsStackItemExtended(try!(read_u16(reader, s)),
+            try!(VerificationTypeInfo(cp, reader))),
+        248..250 => StackFrame_Chop(try!(read_u16(reader, s))),
+        251 => StackFrame_SameExtended(try!(read_u16(reader, s))),
+        252..254 => StackFrame_Append(try!(read_u16(reader, s)), try!(read_verification_type_infos_with_count(cp,
+            frame_type as uint - 251, reader))),
+        255 => StackFrame_Full(try!(read_u16(reader, s)), try!(read_verification_type_infos(cp, reader)),
+            try!(read_verification_type_infos(cp, reader))),
+        _ => return Err(FormatError_UnrecognizedTag(offset, ~"stack_map_frame", frame_type))
     };
-    StackMapFrame { frame_type: frame_type, info: inf }
+    Ok(StackMapFrame { frame_type: frame_type, info: inf })
 }
 
 enum StackMapFrameType {
@@ -364,21 +968,27 @@ struct VerificationTypeInfo {
     info: VariableInfo
 }
 
-fn read_verification_type_infos(reader: io::ReaderUtil) -> ~[VerificationTypeInfo] {
-    read_verification_type_infos_with_count(read_u16(reader) as uint, reader)
+fn read_verification_type_infos<P: ConstantLookup>(constant_pool: &P, reader: io::Reader)
+        -> Result<~[VerificationTypeInfo], ClassFormatError> {
+    let count = try!(read_u16(reader, "stack_map_frame"));
+    read_verification_type_infos_with_count(constant_pool, count as uint, reader)
 }
 
-fn read_verification_type_infos_with_count(count: uint, reader: io::ReaderUtil) -> ~[VerificationTypeInfo] {
+fn read_verification_type_infos_with_count<P: ConstantLookup>(constant_pool: &P, count: uint, reader: io::Reader)
+        -> Result<~[VerificationTypeInfo], ClassFormatError> {
     let mut infos: ~[VerificationTypeInfo] = ~[];
     vec::reserve(&mut infos, count as uint);
     for iter::repeat(count as uint) {
-        infos.push(VerificationTypeInfo(reader));
+        infos.push(try!(VerificationTypeInfo(constant_pool, reader)));
     }
-    infos
+    Ok(infos)
 }
 
-fn VerificationTypeInfo(reader: io::ReaderUtil) -> VerificationTypeInfo {
-    let tag = read_u8(reader);
+fn VerificationTypeInfo<P: ConstantLookup>(constant_pool: &P, reader: io::Reader)
+        -> Result<VerificationTypeInfo, ClassFormatError> {
+    let s = "verification_type_info";
+    let offset = reader.tell();
+    let tag = try!(read_u8(reader, s));
     let inf = match tag {
         0 => Var_Top,
         1 => Var_Integer,
@@ -387,11 +997,11 @@ fn VerificationTypeInfo(reader: io::ReaderUtil) -> VerificationTypeInfo {
         3 => Var_Double,
         5 => Var_Null,
         6 => Var_UninitializedThis,
-        7 => Var_Object(read_u16(reader)),
-        8 => Var_Uninitialized(read_u16(reader)),
-        _ => fail fmt!("Unrecognized verification type tag: %?", tag)
+        7 => Var_Object(try!(read_constant_index(constant_pool, reader, PoolTag_Class, s))),
+        8 => Var_Uninitialized(try!(read_u16(reader, s))),
+        _ => return Err(FormatError_UnrecognizedTag(offset, ~"verification_type_info", tag))
     };
-    VerificationTypeInfo { tag: tag, info: inf }
+    Ok(VerificationTypeInfo { tag: tag, info: inf })
 }
 
 enum VariableInfo {
@@ -413,19 +1023,28 @@ struct InnerClassAttributeInfo {
     inner_class_access_flags: u16
 }
 
-fn read_inner_class_attributes(reader: io::ReaderUtil) -> ~[InnerClassAttributeInfo] {
-    let count = read_u16(reader);
+impl InnerClassAttributeInfo {
+    pub fn flags(&self) -> access_flags::InnerClassAccessFlags {
+        access_flags::InnerClassAccessFlags { bits: self.inner_class_access_flags }
+    }
+}
+
+fn read_inner_class_attributes<P: ConstantLookup>(constant_pool: &P, reader: io::Reader)
+        -> Result<~[InnerClassAttributeInfo], ClassFormatError> {
+    let s = "InnerClasses_attribute";
+    let count = try!(read_u16(reader, s));
     let mut classes: ~[InnerClassAttributeInfo] = ~[];
     vec::reserve(&mut classes, count as uint);
     for iter::repeat(count as uint) {
         classes.push(InnerClassAttributeInfo {
-            inner_class_info_index: read_u16(reader),
-            outer_class_info_index: read_u16(reader),
-            inner_name_index: read_u16(reader),
-            inner_class_access_flags: read_u16(reader)
+            inner_class_info_index: try!(read_constant_index(constant_pool, reader, PoolTag_Class, s)),
+            //both 0 for anonymous classes
+            outer_class_info_index: try!(read_optional_constant_index(constant_pool, reader, PoolTag_Class, s)),
+            inner_name_index: try!(read_optional_constant_index(constant_pool, reader, PoolTag_Utf8, s)),
+            inner_class_access_flags: try!(read_u16(reader, s))
         });
     }
-    classes
+    Ok(classes)
 }
 
 enum InnerClassAccessFlag {
@@ -446,17 +1065,18 @@ struct LineNumberTableInfo {
     line_number: u16
 }
 
-fn read_line_number_table(reader: io::ReaderUtil) -> ~[LineNumberTableInfo] {
-    let count = read_u16(reader);
+fn read_line_number_table(reader: io::Reader) -> Result<~[LineNumberTableInfo], ClassFormatError> {
+    let s = "LineNumberTable_attribute";
+    let count = try!(read_u16(reader, s));
     let mut table: ~[LineNumberTableInfo] = ~[];
     vec::reserve(&mut table, count as uint);
     for iter::repeat(count as uint) {
         table.push(LineNumberTableInfo {
-            start_pc: read_u16(reader),
-            line_number: read_u16(reader)
+            start_pc: try!(read_u16(reader, s)),
+            line_number: try!(read_u16(reader, s))
         });
     }
-    table
+    Ok(table)
 }
 
 struct LocalVariableTableInfo {
@@ -467,20 +1087,22 @@ struct LocalVariableTableInfo {
     index: u16
 }
 
-fn read_local_variable_table(reader: io::ReaderUtil) -> ~[LocalVariableTableInfo] {
-    let count = read_u16(reader);
+fn read_local_variable_table<P: ConstantLookup>(constant_pool: &P, reader: io::Reader)
+        -> Result<~[LocalVariableTableInfo], ClassFormatError> {
+    let s = "LocalVariableTable_attribute";
+    let count = try!(read_u16(reader, s));
     let mut table: ~[LocalVariableTableInfo] = ~[];
     vec::reserve(&mut table, count as uint);
     for iter::repeat(count as uint) {
         table.push(LocalVariableTableInfo {
-            start_pc: read_u16(reader),
-            length: read_u16(reader),
-            name_index: read_u16(reader),
-            descriptor_index: read_u16(reader),
-            index: read_u16(reader)
+            start_pc: try!(read_u16(reader, s)),
+            length: try!(read_u16(reader, s)),
+            name_index: try!(read_constant_index(constant_pool, reader, PoolTag_Utf8, s)),
+            descriptor_index: try!(read_constant_index(constant_pool, reader, PoolTag_Utf8, s)),
+            index: try!(read_u16(reader, s))
         });
     }
-    table
+    Ok(table)
 }
 
 struct LocalVariableTypeTableInfo {
@@ -491,20 +1113,22 @@ struct LocalVariableTypeTableInfo {
     index: u16
 }
 
-fn read_local_variable_type_table(reader: io::ReaderUtil) -> ~[LocalVariableTypeTableInfo] {
-    let count = read_u16(reader);
+fn read_local_variable_type_table<P: ConstantLookup>(constant_pool: &P, reader: io::Reader)
+        -> Result<~[LocalVariableTypeTableInfo], ClassFormatError> {
+    let s = "LocalVariableTypeTable_attribute";
+    let count = try!(read_u16(reader, s));
     let mut table: ~[LocalVariableTypeTableInfo] = ~[];
     vec::reserve(&mut table, count as uint);
     for iter::repeat(count as uint) {
         table.push(LocalVariableTypeTableInfo {
-            start_pc: read_u16(reader),
-            length: read_u16(reader),
-            name_index: read_u16(reader),
-            signature_index: read_u16(reader),
-            index: read_u16(reader)
+            start_pc: try!(read_u16(reader, s)),
+            length: try!(read_u16(reader, s)),
+            name_index: try!(read_constant_index(constant_pool, reader, PoolTag_Utf8, s)),
+            signature_index: try!(read_constant_index(constant_pool, reader, PoolTag_Utf8, s)),
+            index: try!(read_u16(reader, s))
         });
     }
-    table
+    Ok(table)
 }
 
 struct AnnotationInfo {
@@ -512,21 +1136,23 @@ struct AnnotationInfo {
     element_value_pairs: ~[AnnotationElementValuePair]
 }
 
-fn read_annotations(reader: io::ReaderUtil) -> ~[AnnotationInfo] {
-    let count = read_u16(reader);
+fn read_annotations<P: ConstantLookup>(constant_pool: &P, reader: io::Reader)
+        -> Result<~[AnnotationInfo], ClassFormatError> {
+    let count = try!(read_u16(reader, "annotations"));
     let mut annotations: ~[AnnotationInfo] = ~[];
     vec::reserve(&mut annotations, count as uint);
     for iter::repeat(count as uint) {
-        annotations.push(AnnotationInfo(reader));
+        annotations.push(try!(AnnotationInfo(constant_pool, reader)));
     }
-    annotations
+    Ok(annotations)
 }
 
-fn AnnotationInfo(reader: io::ReaderUtil) -> AnnotationInfo {
-    AnnotationInfo {
-        type_index: read_u16(reader),
-        element_value_pairs: read_element_value_pairs(reader)
-    }
+fn AnnotationInfo<P: ConstantLookup>(constant_pool: &P, reader: io::Reader)
+        -> Result<AnnotationInfo, ClassFormatError> {
+    Ok(AnnotationInfo {
+        type_index: try!(read_constant_index(constant_pool, reader, PoolTag_Utf8, "annotation")),
+        element_value_pairs: try!(read_element_value_pairs(constant_pool, reader))
+    })
 }
 
 struct AnnotationElementValuePair {
@@ -534,21 +1160,23 @@ struct AnnotationElementValuePair {
     value: AnnotationElementValue
 }
 
-fn read_element_value_pairs(reader: io::ReaderUtil) -> ~[AnnotationElementValuePair] {
-    let count = read_u16(reader);
+fn read_element_value_pairs<P: ConstantLookup>(constant_pool: &P, reader: io::Reader)
+        -> Result<~[AnnotationElementValuePair], ClassFormatError> {
+    let count = try!(read_u16(reader, "annotation"));
     let mut pairs: ~[AnnotationElementValuePair] = ~[];
     vec::reserve(&mut pairs, count as uint);
     for iter::repeat(count as uint) {
-        pairs.push(AnnotationElementValuePair(reader));
+        pairs.push(try!(AnnotationElementValuePair(constant_pool, reader)));
     }
-    pairs
+    Ok(pairs)
 }
 
-fn AnnotationElementValuePair(reader: io::ReaderUtil) -> AnnotationElementValuePair {
-    AnnotationElementValuePair {
-        element_name_index: read_u16(reader),
-        value: AnnotationElementValue(reader)
-    }
+fn AnnotationElementValuePair<P: ConstantLookup>(constant_pool: &P, reader: io::Reader)
+        -> Result<AnnotationElementValuePair, ClassFormatError> {
+    Ok(AnnotationElementValuePair {
+        element_name_index: try!(read_constant_index(constant_pool, reader, PoolTag_Utf8, "element_value_pair")),
+        value: try!(AnnotationElementValue(constant_pool, reader))
+    })
 }
 
 struct AnnotationElementValue {
@@ -556,27 +1184,38 @@ struct AnnotationElementValue {
     value: AnnotationElementValueType
 }
 
-fn read_element_values(reader: io::ReaderUtil) -> ~[AnnotationElementValue] {
-    let count = read_u16(reader);
+fn read_element_values<P: ConstantLookup>(constant_pool: &P, reader: io::Reader)
+        -> Result<~[AnnotationElementValue], ClassFormatError> {
+    let count = try!(read_u16(reader, "element_value"));
     let mut values: ~[AnnotationElementValue] = ~[];
     vec::reserve(&mut values, count as uint);
     for iter::repeat(count as uint) {
-        values.push(AnnotationElementValue(reader));
+        values.push(try!(AnnotationElementValue(constant_pool, reader)));
     }
-    values
+    Ok(values)
 }
 
-fn AnnotationElementValue(reader: io::ReaderUtil) -> AnnotationElementValue {
-    let tag = read_u8(reader);
+fn AnnotationElementValue<P: ConstantLookup>(constant_pool: &P, reader: io::Reader)
+        -> Result<AnnotationElementValue, ClassFormatError> {
+    let s = "element_value";
+    let cp = constant_pool;
+    let offset = reader.tell();
+    let tag = try!(read_u8(reader, s));
     let value = match tag as char {
-        'B'|'C'|'D'|'F'|'I'|'J'|'S'|'Z'|'s' => ElementValueType_Const(read_u16(reader)),
-        'e' => ElementValueType_EnumConst(read_u16(reader), read_u16(reader)),
-        'c' => ElementValueType_ClassInfo(read_u16(reader)),
-        '@' => ElementValueType_Annotation(AnnotationInfo(reader)),
-        '[' => ElementValueType_Array(read_element_values(reader)),
-        _ => fail fmt!("Unrecognized element value tag: %?", tag) 
+        'B'|'C'|'I'|'S'|'Z' => ElementValueType_Const(try!(read_constant_index(cp, reader, PoolTag_Integer, s))),
+        'D' => ElementValueType_Const(try!(read_constant_index(cp, reader, PoolTag_Double, s))),
+        'F' => ElementValueType_Const(try!(read_constant_index(cp, reader, PoolTag_Float, s))),
+        'J' => ElementValueType_Const(try!(read_constant_index(cp, reader, PoolTag_Long, s))),
+        's' => ElementValueType_Const(try!(read_constant_index(cp, reader, PoolTag_Utf8, s))),
+        'e' => ElementValueType_EnumConst(try!(read_constant_index(cp, reader, PoolTag_Utf8, s)),
+            try!(read_constant_index(cp, reader, PoolTag_Utf8, s))),
+        //a return descriptor such as Ljava/lang/String; or V, not a Class constant
+        'c' => ElementValueType_ClassInfo(try!(read_constant_index(cp, reader, PoolTag_Utf8, s))),
+        '@' => ElementValueType_Annotation(try!(AnnotationInfo(cp, reader))),
+        '[' => ElementValueType_Array(try!(read_element_values(cp, reader))),
+        _ => return Err(FormatError_UnrecognizedTag(offset, ~"element_value", tag))
     };
-    AnnotationElementValue { tag: tag, value: value }
+    Ok(AnnotationElementValue { tag: tag, value: value })
 }
 
 enum AnnotationElementValueType {
@@ -591,16 +1230,134 @@ struct ParameterAnnotationInfo {
     annotations: ~[AnnotationInfo]
 }
 
-fn read_parameter_annotations(reader: io::ReaderUtil) -> ~[ParameterAnnotationInfo] {
-    let count = read_u16(reader);
+fn read_parameter_annotations<P: ConstantLookup>(constant_pool: &P, reader: io::Reader)
+        -> Result<~[ParameterAnnotationInfo], ClassFormatError> {
+    //num_parameters is a single byte
+    let count = try!(read_u8(reader, "parameter_annotations"));
     let mut annotations: ~[ParameterAnnotationInfo] = ~[];
     vec::reserve(&mut annotations, count as uint);
     for iter::repeat(count as uint) {
-        annotations.push(ParameterAnnotationInfo { 
-            annotations: read_annotations(reader)
+        annotations.push(ParameterAnnotationInfo {
+            annotations: try!(read_annotations(constant_pool, reader))
         });
     }
-    annotations
+    Ok(annotations)
+}
+
+struct TypeAnnotationInfo {
+    target_type: u8,
+    target_info: TypeAnnotationTarget,
+    target_path: ~[TypePathEntry],
+    //type_index and element_value_pairs, laid out exactly like a plain annotation
+    annotation: AnnotationInfo
+}
+
+enum TypeAnnotationTarget {
+    Target_TypeParameter(u8 /*type_parameter_index*/),
+    Target_Supertype(u16 /*supertype_index*/),
+    Target_TypeParameterBound(u8 /*type_parameter_index*/, u8 /*bound_index*/),
+    Target_Empty,
+    Target_FormalParameter(u8 /*formal_parameter_index*/),
+    Target_Throws(u16 /*throws_type_index*/),
+    Target_LocalVar(~[LocalVarTargetInfo] /*table*/),
+    Target_Catch(u16 /*exception_table_index*/),
+    Target_Offset(u16 /*offset*/),
+    Target_TypeArgument(u16 /*offset*/, u8 /*type_argument_index*/)
+}
+
+struct LocalVarTargetInfo {
+    start_pc: u16,
+    length: u16,
+    index: u16
+}
+
+struct TypePathEntry {
+    type_path_kind: u8,
+    type_argument_index: u8
+}
+
+fn read_type_annotations<P: ConstantLookup>(constant_pool: &P, reader: io::Reader, location: AttributeLocation)
+        -> Result<~[TypeAnnotationInfo], ClassFormatError> {
+    let count = try!(read_u16(reader, "type_annotation"));
+    let mut annotations: ~[TypeAnnotationInfo] = ~[];
+    vec::reserve(&mut annotations, count as uint);
+    for iter::repeat(count as uint) {
+        annotations.push(try!(TypeAnnotationInfo(constant_pool, reader, location)));
+    }
+    Ok(annotations)
+}
+
+fn TypeAnnotationInfo<P: ConstantLookup>(constant_pool: &P, reader: io::Reader, location: AttributeLocation)
+        -> Result<TypeAnnotationInfo, ClassFormatError> {
+    let s = "type_annotation";
+    let offset = reader.tell();
+    let target_type = try!(read_u8(reader, s));
+    let target_info = match target_type {
+        0x00 | 0x01 => Target_TypeParameter(try!(read_u8(reader, s))),
+        0x10 => Target_Supertype(try!(read_u16(reader, s))),
+        0x11 | 0x12 => Target_TypeParameterBound(try!(read_u8(reader, s)), try!(read_u8(reader, s))),
+        0x13..0x15 => Target_Empty,
+        0x16 => Target_FormalParameter(try!(read_u8(reader, s))),
+        0x17 => Target_Throws(try!(read_u16(reader, s))),
+        0x40 | 0x41 => Target_LocalVar(try!(read_localvar_target(reader))),
+        0x42 => Target_Catch(try!(read_u16(reader, s))),
+        0x43..0x46 => Target_Offset(try!(read_u16(reader, s))),
+        0x47..0x4B => Target_TypeArgument(try!(read_u16(reader, s)), try!(read_u8(reader, s))),
+        _ => return Err(FormatError_UnrecognizedTag(offset, ~"type_annotation", target_type))
+    };
+    //JVMS 4.7.20 table 4.7.20-A/B/C
+    let allowed = match location {
+        AttrLocation_ClassFile => target_type == 0x00 || target_type == 0x10 || target_type == 0x11,
+        AttrLocation_Field | AttrLocation_RecordComponent => target_type == 0x13,
+        AttrLocation_Method => target_type == 0x01 || (target_type >= 0x12 && target_type <= 0x17
+            && target_type != 0x13),
+        AttrLocation_Code => target_type >= 0x40
+    };
+    if !allowed {
+        return Err(FormatError_Invalid(offset, ~"type_annotation",
+            fmt!("target_type 0x%02x not allowed here", target_type as uint)));
+    }
+    let target_path = try!(read_type_path(reader));
+    Ok(TypeAnnotationInfo {
+        target_type: target_type,
+        target_info: target_info,
+        target_path: target_path,
+        annotation: try!(AnnotationInfo(constant_pool, reader))
+    })
+}
+
+fn read_localvar_target(reader: io::Reader) -> Result<~[LocalVarTargetInfo], ClassFormatError> {
+    let s = "localvar_target";
+    let count = try!(read_u16(reader, s));
+    let mut table: ~[LocalVarTargetInfo] = ~[];
+    vec::reserve(&mut table, count as uint);
+    for iter::repeat(count as uint) {
+        table.push(LocalVarTargetInfo {
+            start_pc: try!(read_u16(reader, s)),
+            length: try!(read_u16(reader, s)),
+            index: try!(read_u16(reader, s))
+        });
+    }
+    Ok(table)
+}
+
+fn read_type_path(reader: io::Reader) -> Result<~[TypePathEntry], ClassFormatError> {
+    let s = "type_path";
+    let count = try!(read_u8(reader, s));
+    let mut path: ~[TypePathEntry] = ~[];
+    vec::reserve(&mut path, count as uint);
+    for iter::repeat(count as uint) {
+        let offset = reader.tell();
+        let type_path_kind = try!(read_u8(reader, s));
+        if type_path_kind > 3 {
+            return Err(FormatError_UnrecognizedTag(offset, ~"type_path", type_path_kind));
+        }
+        path.push(TypePathEntry {
+            type_path_kind: type_path_kind,
+            type_argument_index: try!(read_u8(reader, s))
+        });
+    }
+    Ok(path)
 }
 
 struct BootstrapMethodInfo {
@@ -608,61 +1365,346 @@ struct BootstrapMethodInfo {
     bootstrap_arguments: ~[u16]
 }
 
-fn read_bootstrap_methods(reader: io::ReaderUtil) -> ~[BootstrapMethodInfo] {
-    let count = read_u16(reader);
+fn read_bootstrap_methods<P: ConstantLookup>(constant_pool: &P, reader: io::Reader)
+        -> Result<~[BootstrapMethodInfo], ClassFormatError> {
+    let s = "BootstrapMethods_attribute";
+    let loadable = loadable_tags();
+    let count = try!(read_u16(reader, s));
     let mut methods: ~[BootstrapMethodInfo] = ~[];
     vec::reserve(&mut methods, count as uint);
     for iter::repeat(count as uint) {
+        let bootstrap_method_ref = try!(read_constant_index(constant_pool, reader, PoolTag_MethodHandle, s));
+        let argument_count = try!(read_u16(reader, s));
+        let mut bootstrap_arguments: ~[u16] = ~[];
+        vec::reserve(&mut bootstrap_arguments, argument_count as uint);
+        for iter::repeat(argument_count as uint) {
+            bootstrap_arguments.push(try!(read_constant_index_in(constant_pool, reader, loadable, s)));
+        }
         methods.push(BootstrapMethodInfo {
-            bootstrap_method_ref: read_u16(reader),
-            bootstrap_arguments: read_u16_vec(reader)
+            bootstrap_method_ref: bootstrap_method_ref,
+            bootstrap_arguments: bootstrap_arguments
         });
     }
-    methods
+    Ok(methods)
 }
 
-fn read_u8(reader: io::ReaderUtil) -> u8 { reader.read_be_uint(1) as u8 }
-fn read_u16(reader: io::ReaderUtil) -> u16 { reader.read_be_uint(2) as u16 }
-fn read_u32(reader: io::ReaderUtil) -> u32 { reader.read_be_uint(4) as u32 }
-fn read_u16_vec(reader: io::ReaderUtil) -> ~[u16] {
-    let count = read_u16(reader);
-    let mut vec: ~[u16] = ~[];
-    vec::reserve(&mut vec, count as uint);
+struct MethodParameterInfo {
+    //0 when the parameter has no name
+    name_index: u16,
+    access_flags: u16
+}
+
+fn read_method_parameters<P: ConstantLookup>(constant_pool: &P, reader: io::Reader)
+        -> Result<~[MethodParameterInfo], ClassFormatError> {
+    let s = "MethodParameters_attribute";
+    //parameters_count is a single byte
+    let count = try!(read_u8(reader, s));
+    let mut parameters: ~[MethodParameterInfo] = ~[];
+    vec::reserve(&mut parameters, count as uint);
     for iter::repeat(count as uint) {
-        vec.push(read_u16(reader));
+        let offset = reader.tell();
+        let name_index = try!(read_u16(reader, s));
+        if name_index != 0 {
+            try!(check_constant(constant_pool, name_index, PoolTag_Utf8, offset, s));
+        }
+        parameters.push(MethodParameterInfo {
+            name_index: name_index,
+            access_flags: try!(read_u16(reader, s))
+        });
     }
-    vec
+    Ok(parameters)
+}
+
+struct ModuleAttributeInfo {
+    module_name_index: u16,
+    module_flags: u16,
+    //0 when no version is recorded
+    module_version_index: u16,
+    requires: ~[ModuleRequiresInfo],
+    exports: ~[ModuleExportsInfo],
+    opens: ~[ModuleExportsInfo],
+    uses: ~[u16],
+    provides: ~[ModuleProvidesInfo]
+}
+
+struct ModuleRequiresInfo {
+    requires_index: u16,
+    requires_flags: u16,
+    requires_version_index: u16
+}
+
+//shared by exports and opens, which have the same layout
+struct ModuleExportsInfo {
+    package_index: u16,
+    flags: u16,
+    to_index: ~[u16]
+}
+
+struct ModuleProvidesInfo {
+    provides_index: u16,
+    provides_with_index: ~[u16]
 }
 
-pub fn ClassFile(reader: io::ReaderUtil) {
+fn ModuleAttributeInfo<P: ConstantLookup>(constant_pool: &P, reader: io::Reader)
+        -> Result<ModuleAttributeInfo, ClassFormatError> {
+    let s = "Module_attribute";
+    let module_name_index = try!(read_constant_index(constant_pool, reader, PoolTag_Module, s));
+    let module_flags = try!(read_u16(reader, s));
+    let module_version_index = try!(read_optional_constant_index(constant_pool, reader, PoolTag_Utf8, s));
+
+    let requires_count = try!(read_u16(reader, s));
+    let mut requires: ~[ModuleRequiresInfo] = ~[];
+    vec::reserve(&mut requires, requires_count as uint);
+    for iter::repeat(requires_count as uint) {
+        requires.push(ModuleRequiresInfo {
+            requires_index: try!(read_constant_index(constant_pool, reader, PoolTag_Module, s)),
+            requires_flags: try!(read_u16(reader, s)),
+            requires_version_index: try!(read_optional_constant_index(constant_pool, reader, PoolTag_Utf8, s))
+        });
+    }
+    let exports = try!(read_module_exports(constant_pool, reader));
+    let opens = try!(read_module_exports(constant_pool, reader));
+    let uses = try!(read_constant_index_vec(constant_pool, reader, PoolTag_Class, s));
+
+    let provides_count = try!(read_u16(reader, s));
+    let mut provides: ~[ModuleProvidesInfo] = ~[];
+    vec::reserve(&mut provides, provides_count as uint);
+    for iter::repeat(provides_count as uint) {
+        provides.push(ModuleProvidesInfo {
+            provides_index: try!(read_constant_index(constant_pool, reader, PoolTag_Class, s)),
+            provides_with_index: try!(read_constant_index_vec(constant_pool, reader, PoolTag_Class, s))
+        });
+    }
+    Ok(ModuleAttributeInfo {
+        module_name_index: module_name_index,
+        module_flags: module_flags,
+        module_version_index: module_version_index,
+        requires: requires,
+        exports: exports,
+        opens: opens,
+        uses: uses,
+        provides: provides
+    })
+}
+
+fn read_module_exports<P: ConstantLookup>(constant_pool: &P, reader: io::Reader)
+        -> Result<~[ModuleExportsInfo], ClassFormatError> {
+    let s = "Module_attribute";
+    let count = try!(read_u16(reader, s));
+    let mut exports: ~[ModuleExportsInfo] = ~[];
+    vec::reserve(&mut exports, count as uint);
+    for iter::repeat(count as uint) {
+        exports.push(ModuleExportsInfo {
+            package_index: try!(read_constant_index(constant_pool, reader, PoolTag_Package, s)),
+            flags: try!(read_u16(reader, s)),
+            to_index: try!(read_constant_index_vec(constant_pool, reader, PoolTag_Module, s))
+        });
+    }
+    Ok(exports)
+}
+
+struct RecordComponentInfo {
+    name_index: u16,
+    descriptor_index: u16,
+    attributes: ~[AttributeInfo]
+}
+
+fn read_record_components<P: ConstantLookup>(constant_pool: &P, reader: io::Reader, options: &ParseOptions)
+        -> Result<~[RecordComponentInfo], ClassFormatError> {
+    let s = "record_component_info";
+    let count = try!(read_u16(reader, "Record_attribute"));
+    let mut components: ~[RecordComponentInfo] = ~[];
+    vec::reserve(&mut components, count as uint);
+    for iter::repeat(count as uint) {
+        components.push(RecordComponentInfo {
+            name_index: try!(read_constant_index(constant_pool, reader, PoolTag_Utf8, s)),
+            descriptor_index: try!(read_constant_index(constant_pool, reader, PoolTag_Utf8, s)),
+            attributes: try!(read_attributes(constant_pool, reader, AttrLocation_RecordComponent, options))
+        });
+    }
+    Ok(components)
+}
+
+fn read_bytes(reader: io::Reader, len: uint, structure: &str) -> Result<~[u8], ClassFormatError> {
+    let offset = reader.tell();
+    let byts = reader.read_bytes(len);
+    if byts.len() != len {
+        return Err(FormatError_UnexpectedEof(offset, str::from_slice(structure)));
+    }
+    Ok(byts)
+}
+fn read_u8(reader: io::Reader, structure: &str) -> Result<u8, ClassFormatError> {
+    let byts = try!(read_bytes(reader, 1, structure));
+    Ok(byts[0])
+}
+fn read_u16(reader: io::Reader, structure: &str) -> Result<u16, ClassFormatError> {
+    let byts = try!(read_bytes(reader, 2, structure));
+    Ok((byts[0] as u16 << 8) | byts[1] as u16)
+}
+fn read_u32(reader: io::Reader, structure: &str) -> Result<u32, ClassFormatError> {
+    let byts = try!(read_bytes(reader, 4, structure));
+    Ok((byts[0] as u32 << 24) | (byts[1] as u32 << 16) | (byts[2] as u32 << 8) | byts[3] as u32)
+}
+
+pub fn ClassFile(reader: io::Reader) -> Result<ClassFile, ClassFormatError> {
+    read_class_file(reader, &ParseOptions())
+}
+
+pub fn read_class_file(reader: io::Reader, options: &ParseOptions) -> Result<ClassFile, ClassFormatError> {
+    let ClassPrefix { magic, minor_version, major_version, constant_pool, access_flags, this_class, super_class,
+        interfaces, _ } = try!(read_class_prefix(reader));
+
+    //fields
+    let fields = try!(read_fields(&constant_pool, reader, options));
+
+    //methods
+    let methods = try!(read_methods(&constant_pool, reader, options));
+
+    //attributes
+    let attributes = try!(read_attributes(&constant_pool, reader, AttrLocation_ClassFile, options));
+
+    Ok(ClassFile {
+        magic: magic,
+        minor_version: minor_version,
+        major_version: major_version,
+        constant_pool: constant_pool,
+        access_flags: access_flags,
+        this_class: this_class,
+        super_class: super_class,
+        interfaces: interfaces,
+        fields: fields,
+        methods: methods,
+        attributes: attributes
+    })
+}
+
+//everything before the fields, which ClassFile and ClassHeader read alike
+struct ClassPrefix {
+    magic: u32,
+    minor_version: u16,
+    major_version: u16,
+    constant_pool: ConstantPool,
+    access_flags: u16,
+    this_class: u16,
+    super_class: u16,
+    interfaces: ~[u16],
+    //of access_flags, the class indices follow it at fixed offsets
+    access_flags_offset: uint
+}
+
+fn read_class_prefix(reader: io::Reader) -> Result<ClassPrefix, ClassFormatError> {
     //magic
-    let magic = read_u32(reader);
-    assert magic == 0xCAFEBABE;
+    let magic = try!(read_u32(reader, "magic"));
+    if magic != 0xCAFEBABE {
+        return Err(FormatError_BadMagic(0, magic));
+    }
 
     //versions
-    let minor_version = read_u16(reader);
-    let major_version = read_u16(reader);
+    let minor_version = try!(read_u16(reader, "minor_version"));
+    let major_version = try!(read_u16(reader, "major_version"));
     debug!("Major: %?, Minor: %?", major_version, minor_version);
 
     //constant pool
-    let mut constant_pool = read_constant_pool(reader);
+    let constant_pool = try!(read_constant_pool(reader));
     debug!("Consts: %?", constant_pool);
 
     //access flags
-    let access_flags = read_u16(reader);
-    
+    let access_flags_offset = reader.tell();
+    let access_flags = try!(read_u16(reader, "access_flags"));
+
     //this class
-    let this_class = read_u16(reader);
+    let this_class = try!(read_constant_index(&constant_pool, reader, PoolTag_Class, "this_class"));
 
-    //super class
-    let super_class = read_u16(reader);
+    //super class (only java/lang/Object has none)
+    let super_offset = reader.tell();
+    let super_class = try!(read_u16(reader, "super_class"));
+    if super_class != 0 {
+        try!(check_constant(&constant_pool, super_class, PoolTag_Class, super_offset, "super_class"));
+    }
 
     //interfaces
-    let interfaces = read_u16_vec(reader);
+    let interfaces = try!(read_constant_index_vec(&constant_pool, reader, PoolTag_Class, "interfaces"));
     debug!("Interfaces: %?", interfaces);
 
-    //fields
-    let mut fields = read_fields(constant_pool, reader);
+    Ok(ClassPrefix {
+        magic: magic,
+        minor_version: minor_version,
+        major_version: major_version,
+        constant_pool: constant_pool,
+        access_flags: access_flags,
+        this_class: this_class,
+        super_class: super_class,
+        interfaces: interfaces,
+        access_flags_offset: access_flags_offset
+    })
 }
 
+//just enough of a class to index it, the rest of the class file is only walked by its lengths
+pub struct ClassHeader {
+    minor_version: u16,
+    major_version: u16,
+    access_flags: u16,
+    this_class: ~str,
+    //None for java/lang/Object and module-info
+    super_class: Option<~str>,
+    interfaces: ~[~str]
+}
+
+pub fn ClassHeader(reader: io::Reader) -> Result<ClassHeader, ClassFormatError> {
+    let prefix = try!(read_class_prefix(reader));
+    let cp = &prefix.constant_pool;
+    let base = prefix.access_flags_offset;
+    let this_class = try!(class_name_at(cp, prefix.this_class, base + 2, "this_class"));
+    let super_class = if prefix.super_class == 0 {
+        None
+    } else {
+        Some(try!(class_name_at(cp, prefix.super_class, base + 4, "super_class")))
+    };
+    let mut interfaces = ~[];
+    for prefix.interfaces.eachi |i, index| {
+        interfaces.push(try!(class_name_at(cp, *index, base + 8 + 2 * i, "interfaces")));
+    }
+
+    //skip fields, methods and attributes so the reader ends up just past the class file
+    try!(skip_members(reader, "field_info"));
+    try!(skip_members(reader, "method_info"));
+    try!(skip_attributes(reader));
+
+    Ok(ClassHeader {
+        minor_version: prefix.minor_version,
+        major_version: prefix.major_version,
+        access_flags: prefix.access_flags,
+        this_class: this_class,
+        super_class: super_class,
+        interfaces: interfaces
+    })
+}
+
+//the prefix already checked the tag, this only fails when the Class constant's name is not a utf8 constant
+fn class_name_at(constant_pool: &ConstantPool, index: u16, offset: uint, structure: &str)
+        -> Result<~str, ClassFormatError> {
+    match constant_pool.get_class_name(index) {
+        Some(name) => Ok(str::from_slice(name)),
+        None => Err(FormatError_BadConstantIndex(offset, str::from_slice(structure), index))
+    }
+}
+
+fn skip_members(reader: io::Reader, structure: &str) -> Result<(), ClassFormatError> {
+    let count = try!(read_u16(reader, structure));
+    for iter::repeat(count as uint) {
+        //access_flags, name_index and descriptor_index
+        try!(read_bytes(reader, 6, structure));
+        try!(skip_attributes(reader));
+    }
+    Ok(())
+}
 
+fn skip_attributes(reader: io::Reader) -> Result<(), ClassFormatError> {
+    let count = try!(read_u16(reader, "attributes_count"));
+    for iter::repeat(count as uint) {
+        try!(read_u16(reader, "attribute_info"));
+        let attribute_length = try!(read_u32(reader, "attribute_info"));
+        try!(read_bytes(reader, attribute_length as uint, "attribute_info"));
+    }
+    Ok(())
+}
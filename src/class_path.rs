@@ -0,0 +1,198 @@
+use class_file::{ClassFile, ClassFormatError};
+use archive::{Archive, ArchiveError};
+use jimage::{JImage, JImageError};
+use jmod::JMod;
+use core::send_map::linear::LinearMap;
+
+//directories, archives, jmods and JDK runtime images searched in order for classes by internal name,
+//e.g. java/util/List
+
+pub enum ClassPathEntry {
+    ClassPathEntry_Directory(Path),
+    ClassPathEntry_Archive(Path, Archive),
+    ClassPathEntry_JMod(Path, JMod),
+    ClassPathEntry_Image(Path, JImage)
+}
+
+pub struct ClassPath {
+    entries: ~[ClassPathEntry],
+    //the Java feature release multi-release jars are read as, e.g. 17
+    release: uint,
+    //misses are cached too
+    mut cache: LinearMap<~str, Option<@ClassFile>>
+}
+
+pub enum LoadError {
+    LoadError_Io(~str /*path*/, ~str /*reason*/),
+    LoadError_Archive(~str /*path*/, ArchiveError),
+    LoadError_Image(~str /*path*/, JImageError),
+    LoadError_Class(~str /*path*/, ClassFormatError),
+    //the class file found for a name declares a different this_class
+    LoadError_WrongName(~str /*path*/, ~str /*expected*/, ~str /*found*/)
+}
+
+impl LoadError {
+    pub fn to_str(&self) -> ~str {
+        match *self {
+            LoadError_Io(ref path, ref reason) => fmt!("%s: %s", *path, *reason),
+            LoadError_Archive(ref path, ref err) => fmt!("%s: %s", *path, err.to_str()),
+            LoadError_Image(ref path, ref err) => fmt!("%s: %s", *path, err.to_str()),
+            LoadError_Class(ref path, ref err) => fmt!("%s: %s", *path, err.to_str()),
+            LoadError_WrongName(ref path, ref expected, ref found) => fmt!("%s: expected class %s but found %s",
+                *path, *expected, *found)
+        }
+    }
+}
+
+pub fn ClassPath(release: uint) -> ClassPath {
+    ClassPath { entries: ~[], release: release, cache: LinearMap() }
+}
+
+//a class path string split on separator (':' or ';'), entries ending in .jar or .zip are read as archives
+//and ones ending in .jmod as jmods
+pub fn parse_class_path(class_path: &str, separator: char, release: uint) -> Result<ClassPath, LoadError> {
+    let mut result = ClassPath(release);
+    for str::split_char_nonempty(class_path, separator).each |element| {
+        let path = path::Path(*element);
+        match path.filetype() {
+            Some(ref filetype) if *filetype == ~".jar" || *filetype == ~".zip" => try!(result.add_archive(&path)),
+            Some(ref filetype) if *filetype == ~".jmod" => try!(result.add_jmod(&path)),
+            _ => result.add_directory(path)
+        }
+    }
+    Ok(result)
+}
+
+impl ClassPath {
+    pub fn add_directory(&mut self, path: Path) {
+        self.entries.push(ClassPathEntry_Directory(path));
+        self.cache = LinearMap();
+    }
+
+    pub fn add_archive(&mut self, path: &Path) -> Result<(), LoadError> {
+        match archive::open_archive(path) {
+            Ok(archive) => {
+                self.entries.push(ClassPathEntry_Archive(copy *path, archive));
+                self.cache = LinearMap();
+                Ok(())
+            },
+            Err(err) => Err(LoadError_Archive(path.to_str(), err))
+        }
+    }
+
+    pub fn add_jmod(&mut self, path: &Path) -> Result<(), LoadError> {
+        match jmod::open_jmod(path) {
+            Ok(jmod) => {
+                self.entries.push(ClassPathEntry_JMod(copy *path, jmod));
+                self.cache = LinearMap();
+                Ok(())
+            },
+            Err(err) => Err(LoadError_Archive(path.to_str(), err))
+        }
+    }
+
+    pub fn add_image(&mut self, path: &Path) -> Result<(), LoadError> {
+        match jimage::open_jimage(path) {
+            Ok(image) => {
+                self.entries.push(ClassPathEntry_Image(copy *path, image));
+                self.cache = LinearMap();
+                Ok(())
+            },
+            Err(err) => Err(LoadError_Image(path.to_str(), err))
+        }
+    }
+
+    //the platform classes of a JDK 9+ install
+    pub fn add_java_home(&mut self, java_home: &Path) -> Result<(), LoadError> {
+        self.add_image(&java_home.push_many([~"lib", ~"modules"]))
+    }
+
+    //Ok(None) when no entry has the class, a class that fails to parse is an error rather than skipped
+    pub fn load(&self, internal_name: &str) -> Result<Option<@ClassFile>, LoadError> {
+        let key = str::from_slice(internal_name);
+        match self.cache.find(&key) {
+            Some(cached) => return Ok(*cached),
+            None => ()
+        }
+        let loaded = match try!(self.find(internal_name)) {
+            Some(class_file) => Some(@class_file),
+            None => None
+        };
+        self.cache.insert(key, loaded);
+        Ok(loaded)
+    }
+
+    //uncached, the first entry that has the class wins. Names that are not valid internal names, which
+    //includes any with . or .. segments, are never looked up, and the class found has to declare the name
+    //it was looked up by
+    pub fn find(&self, internal_name: &str) -> Result<Option<ClassFile>, LoadError> {
+        if !descriptor::is_valid_internal_name(internal_name) {
+            return Ok(None);
+        }
+        match try!(self.search(internal_name)) {
+            Some((path, class_file)) => {
+                let found = match class_file.class_name() {
+                    Some(name) => str::from_slice(name),
+                    None => ~"an invalid this_class"
+                };
+                if str::eq_slice(found, internal_name) {
+                    Ok(Some(class_file))
+                } else {
+                    Err(LoadError_WrongName(path, str::from_slice(internal_name), found))
+                }
+            },
+            None => Ok(None)
+        }
+    }
+
+    fn search(&self, internal_name: &str) -> Result<Option<(~str, ClassFile)>, LoadError> {
+        for self.entries.each |entry| {
+            match *entry {
+                ClassPathEntry_Directory(ref dir) => {
+                    let path = dir.push(internal_name + ~".class");
+                    if os::path_exists(&path) {
+                        return match read_class_file(&path) {
+                            Ok(class_file) => Ok(Some((path.to_str(), class_file))),
+                            Err(err) => Err(err)
+                        };
+                    }
+                },
+                ClassPathEntry_Archive(ref path, ref archive) => {
+                    match archive.class_entry(internal_name, self.release) {
+                        Some(archive_entry) => return match archive.read_class(archive_entry) {
+                            Ok(class_file) => Ok(Some((path.to_str(), class_file))),
+                            Err(err) => Err(LoadError_Archive(path.to_str(), err))
+                        },
+                        None => ()
+                    }
+                },
+                ClassPathEntry_JMod(ref path, ref jmod) => {
+                    match jmod.read_class(internal_name) {
+                        Ok(Some(class_file)) => return Ok(Some((path.to_str(), class_file))),
+                        Ok(None) => (),
+                        Err(err) => return Err(LoadError_Archive(path.to_str(), err))
+                    }
+                },
+                ClassPathEntry_Image(ref path, ref image) => {
+                    match image.read_class(internal_name) {
+                        Ok(Some(class_file)) => return Ok(Some((path.to_str(), class_file))),
+                        Ok(None) => (),
+                        Err(err) => return Err(LoadError_Image(path.to_str(), err))
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn read_class_file(path: &Path) -> Result<ClassFile, LoadError> {
+    let reader = match io::file_reader(path) {
+        Ok(reader) => reader,
+        Err(reason) => return Err(LoadError_Io(path.to_str(), reason))
+    };
+    match class_file::ClassFile(reader) {
+        Ok(class_file) => Ok(class_file),
+        Err(err) => Err(LoadError_Class(path.to_str(), err))
+    }
+}
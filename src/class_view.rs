@@ -0,0 +1,486 @@
+use class_file::*;
+
+//a class file parser over a byte slice: names, code and attribute bodies borrow from the input instead of
+//being copied, and attributes are only checked for their length until they are asked for
+
+pub struct ClassView {
+    bytes: &self/[u8],
+    minor_version: u16,
+    major_version: u16,
+    constant_pool: ConstantPoolView/&self,
+    access_flags: u16,
+    this_class: u16,
+    super_class: u16,
+    //interfaces_count big endian u16 indexes
+    interfaces: &self/[u8],
+    fields: ~[MemberView/&self],
+    methods: ~[MemberView/&self],
+    attributes: AttributesView/&self
+}
+
+pub struct ConstantPoolView {
+    bytes: &self/[u8],
+    //offset of each entry's tag, 0 for index 0 and the slot following a Long or Double
+    offsets: ~[uint],
+    //offset just past the last entry
+    end: uint
+}
+
+pub struct MemberView {
+    access_flags: u16,
+    name_index: u16,
+    descriptor_index: u16,
+    attributes: AttributesView/&self
+}
+
+pub struct AttributesView {
+    bytes: &self/[u8],
+    //offset of the first attribute_info
+    offset: uint,
+    count: u16
+}
+
+pub struct AttributeView {
+    attribute_name_index: u16,
+    //offset of info in the enclosing bytes, used to rebase errors
+    offset: uint,
+    info: &self/[u8]
+}
+
+pub struct CodeView {
+    max_stack: u16,
+    max_locals: u16,
+    code: &self/[u8],
+    exception_table: ~[ExceptionTableInfo],
+    attributes: AttributesView/&self
+}
+
+impl ClassView {
+    pub fn class_name(&self) -> Option<&self/str> {
+        self.constant_pool.get_class_name(self.this_class)
+    }
+
+    //None for java/lang/Object and module-info, or when the name is not plain utf-8
+    pub fn super_name(&self) -> Option<&self/str> {
+        if self.super_class == 0 { None } else { self.constant_pool.get_class_name(self.super_class) }
+    }
+
+    pub fn interface_count(&self) -> uint { self.interfaces.len() / 2 }
+
+    pub fn interface_index(&self, i: uint) -> u16 { be_u16(self.interfaces, i * 2) }
+}
+
+impl ConstantPoolView {
+    //the constant_pool_count, one more than the highest valid index
+    pub fn count(&self) -> uint { self.offsets.len() }
+
+    pub fn is_tag(&self, index: u16, tag: ConstantPoolTag) -> bool {
+        match self.entry(index) {
+            Some(offset) => self.bytes[offset] as int == tag as int,
+            None => false
+        }
+    }
+
+    pub fn get_utf8_bytes(&self, index: u16) -> Option<&self/[u8]> {
+        match self.tagged(index, PoolTag_Utf8) {
+            Some(offset) => {
+                let length = be_u16(self.bytes, offset + 1) as uint;
+                Some(vec::view(self.bytes, offset + 3, offset + 3 + length))
+            },
+            None => None
+        }
+    }
+
+    //borrowed only when the modified utf-8 is also plain utf-8, use decode_utf8 otherwise
+    pub fn get_utf8(&self, index: u16) -> Option<&self/str> {
+        match self.get_utf8_bytes(index) {
+            Some(bytes) => as_str(bytes),
+            None => None
+        }
+    }
+
+    pub fn decode_utf8(&self, index: u16) -> Option<~str> {
+        match self.get_utf8_bytes(index) {
+            Some(bytes) => mutf8::decode(bytes),
+            None => None
+        }
+    }
+
+    pub fn get_class_name(&self, index: u16) -> Option<&self/str> {
+        match self.tagged(index, PoolTag_Class) {
+            Some(offset) => self.get_utf8(be_u16(self.bytes, offset + 1)),
+            None => None
+        }
+    }
+
+    //(name, descriptor)
+    pub fn get_name_and_type(&self, index: u16) -> Option<(&self/str, &self/str)> {
+        match self.tagged(index, PoolTag_NameAndType) {
+            Some(offset) => match (self.get_utf8(be_u16(self.bytes, offset + 1)),
+                    self.get_utf8(be_u16(self.bytes, offset + 3))) {
+                (Some(name), Some(descriptor)) => Some((name, descriptor)),
+                _ => None
+            },
+            None => None
+        }
+    }
+
+    //resolves a FieldRef, MethodRef or InterfaceMethodRef
+    pub fn get_member_ref(&self, index: u16) -> Option<MemberRef> {
+        let offset = match self.entry(index) {
+            Some(offset) => offset,
+            None => return None
+        };
+        let kind = match ConstantPoolTag_from_int(self.bytes[offset] as int) {
+            Some(PoolTag_FieldRef) => MemberRef_Field,
+            Some(PoolTag_MethodRef) => MemberRef_Method,
+            Some(PoolTag_InterfaceMethodRef) => MemberRef_InterfaceMethod,
+            _ => return None
+        };
+        match (self.get_class_name(be_u16(self.bytes, offset + 1)),
+                self.get_name_and_type(be_u16(self.bytes, offset + 3))) {
+            (Some(owner), Some((name, descriptor))) => Some(MemberRef {
+                kind: kind,
+                owner: str::from_slice(owner),
+                name: str::from_slice(name),
+                descriptor: str::from_slice(descriptor)
+            }),
+            _ => None
+        }
+    }
+
+    //copies the pool into the owned form, attributes decode against the view itself
+    pub fn to_constant_pool(&self) -> Result<ConstantPool, ClassFormatError> {
+        let bytes = vec::view(self.bytes, 8, self.end);
+        match io::with_bytes_reader(bytes, |reader| read_constant_pool(reader)) {
+            Ok(constant_pool) => Ok(constant_pool),
+            Err(ref err) => Err(err.rebase(8))
+        }
+    }
+
+    fn entry(&self, index: u16) -> Option<uint> {
+        if index as uint >= self.offsets.len() || self.offsets[index] == 0 {
+            None
+        } else {
+            Some(self.offsets[index])
+        }
+    }
+
+    fn tagged(&self, index: u16, tag: ConstantPoolTag) -> Option<uint> {
+        if self.is_tag(index, tag) { self.entry(index) } else { None }
+    }
+}
+
+impl ConstantPoolView: ConstantLookup {
+    fn tag_of(&self, index: u16) -> Option<ConstantPoolTag> {
+        match self.entry(index) {
+            Some(offset) => ConstantPoolTag_from_int(self.bytes[offset] as int),
+            None => None
+        }
+    }
+
+    //decoded so names that are valid modified utf-8 but not plain utf-8 still resolve
+    fn utf8_of(&self, index: u16) -> Option<~str> {
+        self.decode_utf8(index)
+    }
+}
+
+impl MemberView {
+    pub fn name(&self, cp: &r/ConstantPoolView) -> Option<&r/str> {
+        cp.get_utf8(self.name_index)
+    }
+
+    pub fn descriptor(&self, cp: &r/ConstantPoolView) -> Option<&r/str> {
+        cp.get_utf8(self.descriptor_index)
+    }
+
+    //Ok(None) for abstract and native methods
+    pub fn code(&self, cp: &ConstantPoolView) -> Result<Option<CodeView/&self>, ClassFormatError> {
+        match self.attributes.find(cp, "Code") {
+            Some(attribute) => Ok(Some(try!(attribute.code()))),
+            None => Ok(None)
+        }
+    }
+}
+
+impl AttributesView {
+    pub fn len(&self) -> uint { self.count as uint }
+
+    pub fn each(&self, f: fn(&AttributeView/&self) -> bool) {
+        let mut pos = self.offset;
+        for iter::repeat(self.count as uint) {
+            let length = be_u32(self.bytes, pos + 2) as uint;
+            let attribute = AttributeView {
+                attribute_name_index: be_u16(self.bytes, pos),
+                offset: pos + 6,
+                info: vec::view(self.bytes, pos + 6, pos + 6 + length)
+            };
+            if !f(&attribute) {
+                break;
+            }
+            pos += 6 + length;
+        }
+    }
+
+    pub fn find(&self, cp: &ConstantPoolView, name: &str) -> Option<AttributeView/&self> {
+        let mut found = None;
+        for self.each |attribute| {
+            match cp.get_utf8(attribute.attribute_name_index) {
+                Some(attribute_name) if attribute_name == name => {
+                    found = Some(*attribute);
+                    break;
+                },
+                _ => ()
+            }
+        }
+        found
+    }
+}
+
+impl AttributeView {
+    pub fn name(&self, cp: &r/ConstantPoolView) -> Option<&r/str> {
+        cp.get_utf8(self.attribute_name_index)
+    }
+
+    //the code array and nested attributes stay borrowed
+    pub fn code(&self) -> Result<CodeView/&self, ClassFormatError> {
+        match read_code_view(self.info) {
+            Ok(code) => Ok(code),
+            Err(ref err) => Err(err.rebase(self.offset))
+        }
+    }
+
+    //the fully decoded owned form, constant indices in it are checked against the view without copying the pool
+    pub fn decode(&self, cp: &ConstantPoolView, location: AttributeLocation)
+            -> Result<AttributeInfoStructure, ClassFormatError> {
+        let attribute_name = match cp.decode_utf8(self.attribute_name_index) {
+            Some(attribute_name) => attribute_name,
+            None => return Err(FormatError_BadConstantIndex(self.offset - 6, ~"attribute_info",
+                self.attribute_name_index))
+        };
+        let decoded = do io::with_bytes_reader(self.info) |reader| {
+            read_attribute_body(cp, copy attribute_name, reader, self.info.len(), location, &ParseOptions())
+        };
+        match decoded {
+            Ok(inf) => Ok(inf),
+            Err(ref err) => Err(err.rebase(self.offset))
+        }
+    }
+}
+
+pub fn ClassView(bytes: &r/[u8]) -> Result<ClassView/&r, ClassFormatError> {
+    //magic
+    let magic = try!(u32_at(bytes, 0, "magic"));
+    if magic != 0xCAFEBABE {
+        return Err(FormatError_BadMagic(0, magic));
+    }
+
+    //versions
+    let minor_version = try!(u16_at(bytes, 4, "minor_version"));
+    let major_version = try!(u16_at(bytes, 6, "major_version"));
+
+    //constant pool
+    let constant_pool = try!(read_constant_pool_view(bytes));
+    let mut pos = constant_pool.end;
+
+    //access flags, this class and super class
+    let access_flags = try!(u16_at(bytes, pos, "access_flags"));
+    let this_class = try!(u16_at(bytes, pos + 2, "this_class"));
+    if !constant_pool.is_tag(this_class, PoolTag_Class) {
+        return Err(FormatError_BadConstantIndex(pos + 2, ~"this_class", this_class));
+    }
+    let super_class = try!(u16_at(bytes, pos + 4, "super_class"));
+    if super_class != 0 && !constant_pool.is_tag(super_class, PoolTag_Class) {
+        return Err(FormatError_BadConstantIndex(pos + 4, ~"super_class", super_class));
+    }
+    pos += 6;
+
+    //interfaces
+    let interfaces_count = try!(u16_at(bytes, pos, "interfaces_count")) as uint;
+    if pos + 2 + interfaces_count * 2 > bytes.len() {
+        return Err(FormatError_UnexpectedEof(pos + 2, ~"interfaces"));
+    }
+    let interfaces = vec::view(bytes, pos + 2, pos + 2 + interfaces_count * 2);
+    for uint::range(0, interfaces_count) |i| {
+        let index = be_u16(interfaces, i * 2);
+        if !constant_pool.is_tag(index, PoolTag_Class) {
+            return Err(FormatError_BadConstantIndex(pos + 2 + i * 2, ~"interfaces", index));
+        }
+    }
+    pos += 2 + interfaces_count * 2;
+
+    //fields, methods and attributes
+    let (fields, fields_end) = try!(read_member_views(bytes, pos, &constant_pool, "field_info"));
+    let (methods, methods_end) = try!(read_member_views(bytes, fields_end, &constant_pool, "method_info"));
+    let (attributes, _) = try!(read_attributes_view(bytes, methods_end));
+
+    Ok(ClassView {
+        bytes: bytes,
+        minor_version: minor_version,
+        major_version: major_version,
+        constant_pool: constant_pool,
+        access_flags: access_flags,
+        this_class: this_class,
+        super_class: super_class,
+        interfaces: interfaces,
+        fields: fields,
+        methods: methods,
+        attributes: attributes
+    })
+}
+
+fn read_constant_pool_view(bytes: &r/[u8]) -> Result<ConstantPoolView/&r, ClassFormatError> {
+    let s = "cp_info";
+    let constant_pool_count = try!(u16_at(bytes, 8, "constant_pool_count")) as uint;
+    if constant_pool_count == 0 {
+        return Err(FormatError_Invalid(8, ~"constant_pool_count", ~"must be at least 1"));
+    }
+    let mut offsets = vec::from_elem(constant_pool_count, 0u);
+    let mut pos = 10u;
+    let mut index = 1u;
+    while index < constant_pool_count {
+        let tag = try!(u8_at(bytes, pos, s));
+        offsets[index] = pos;
+        let size = match ConstantPoolTag_from_int(tag as int) {
+            Some(PoolTag_Utf8) => 3 + try!(u16_at(bytes, pos + 1, s)) as uint,
+            Some(PoolTag_Class) | Some(PoolTag_String) | Some(PoolTag_MethodType) | Some(PoolTag_Module) |
+                Some(PoolTag_Package) => 3,
+            Some(PoolTag_MethodHandle) => 4,
+            Some(PoolTag_Long) | Some(PoolTag_Double) => {
+                //takes two slots
+                if index + 1 >= constant_pool_count {
+                    return Err(FormatError_Invalid(pos, ~"cp_info", ~"8-byte constant in the last slot"));
+                }
+                index += 1;
+                9
+            },
+            Some(_) => 5,
+            None => return Err(FormatError_UnrecognizedTag(pos, ~"cp_info", tag))
+        };
+        if pos + size > bytes.len() {
+            return Err(FormatError_UnexpectedEof(pos, ~"cp_info"));
+        }
+        pos += size;
+        index += 1;
+    }
+    Ok(ConstantPoolView { bytes: bytes, offsets: offsets, end: pos })
+}
+
+fn read_member_views(bytes: &r/[u8], pos: uint, cp: &ConstantPoolView, structure: &str)
+        -> Result<(~[MemberView/&r], uint), ClassFormatError> {
+    let count = try!(u16_at(bytes, pos, structure));
+    let mut members: ~[MemberView/&r] = ~[];
+    vec::reserve(&mut members, count as uint);
+    let mut pos = pos + 2;
+    for iter::repeat(count as uint) {
+        let access_flags = try!(u16_at(bytes, pos, structure));
+        let name_index = try!(u16_at(bytes, pos + 2, structure));
+        if !cp.is_tag(name_index, PoolTag_Utf8) {
+            return Err(FormatError_BadConstantIndex(pos + 2, str::from_slice(structure), name_index));
+        }
+        let descriptor_index = try!(u16_at(bytes, pos + 4, structure));
+        if !cp.is_tag(descriptor_index, PoolTag_Utf8) {
+            return Err(FormatError_BadConstantIndex(pos + 4, str::from_slice(structure), descriptor_index));
+        }
+        let (attributes, next) = try!(read_attributes_view(bytes, pos + 6));
+        members.push(MemberView {
+            access_flags: access_flags,
+            name_index: name_index,
+            descriptor_index: descriptor_index,
+            attributes: attributes
+        });
+        pos = next;
+    }
+    Ok((members, pos))
+}
+
+//only the attribute lengths are checked, returns the view and the offset past the table
+fn read_attributes_view(bytes: &r/[u8], pos: uint) -> Result<(AttributesView/&r, uint), ClassFormatError> {
+    let count = try!(u16_at(bytes, pos, "attributes_count"));
+    let mut next = pos + 2;
+    for iter::repeat(count as uint) {
+        let length = try!(u32_at(bytes, next + 2, "attribute_info")) as uint;
+        if next + 6 + length > bytes.len() {
+            return Err(FormatError_UnexpectedEof(next, ~"attribute_info"));
+        }
+        next += 6 + length;
+    }
+    Ok((AttributesView { bytes: bytes, offset: pos + 2, count: count }, next))
+}
+
+fn read_code_view(info: &r/[u8]) -> Result<CodeView/&r, ClassFormatError> {
+    let s = "Code_attribute";
+    let max_stack = try!(u16_at(info, 0, s));
+    let max_locals = try!(u16_at(info, 2, s));
+    let code_length = try!(u32_at(info, 4, s)) as uint;
+    if 8 + code_length > info.len() {
+        return Err(FormatError_UnexpectedEof(8, ~"Code_attribute"));
+    }
+    let code = vec::view(info, 8, 8 + code_length);
+    let mut pos = 8 + code_length;
+    let exception_table_length = try!(u16_at(info, pos, s));
+    let mut exception_table: ~[ExceptionTableInfo] = ~[];
+    vec::reserve(&mut exception_table, exception_table_length as uint);
+    pos += 2;
+    for iter::repeat(exception_table_length as uint) {
+        exception_table.push(ExceptionTableInfo {
+            start_pc: try!(u16_at(info, pos, "exception_table")),
+            end_pc: try!(u16_at(info, pos + 2, "exception_table")),
+            handler_pc: try!(u16_at(info, pos + 4, "exception_table")),
+            catch_type: try!(u16_at(info, pos + 6, "exception_table"))
+        });
+        pos += 8;
+    }
+    let (attributes, end) = try!(read_attributes_view(info, pos));
+    if end != info.len() {
+        return Err(FormatError_Invalid(end, ~"Code_attribute",
+            fmt!("%u of attribute_length %u bytes left unread", info.len() - end, info.len())));
+    }
+    Ok(CodeView {
+        max_stack: max_stack,
+        max_locals: max_locals,
+        code: code,
+        exception_table: exception_table,
+        attributes: attributes
+    })
+}
+
+//modified utf-8 is plain utf-8 unless it encodes NUL as C0 80 or has surrogates, ED A0..ED BF
+fn as_str(bytes: &r/[u8]) -> Option<&r/str> {
+    for bytes.eachi |i, b| {
+        if *b == 0xC0 || (*b == 0xED && i + 1 < bytes.len() && bytes[i + 1] >= 0xA0) {
+            return None;
+        }
+    }
+    if !str::is_utf8(bytes) {
+        return None;
+    }
+    Some(unsafe { cast::transmute(bytes) })
+}
+
+fn u8_at(bytes: &[u8], pos: uint, structure: &str) -> Result<u8, ClassFormatError> {
+    if pos >= bytes.len() {
+        return Err(FormatError_UnexpectedEof(pos, str::from_slice(structure)));
+    }
+    Ok(bytes[pos])
+}
+fn u16_at(bytes: &[u8], pos: uint, structure: &str) -> Result<u16, ClassFormatError> {
+    if pos + 2 > bytes.len() {
+        return Err(FormatError_UnexpectedEof(pos, str::from_slice(structure)));
+    }
+    Ok(be_u16(bytes, pos))
+}
+fn u32_at(bytes: &[u8], pos: uint, structure: &str) -> Result<u32, ClassFormatError> {
+    if pos + 4 > bytes.len() {
+        return Err(FormatError_UnexpectedEof(pos, str::from_slice(structure)));
+    }
+    Ok(be_u32(bytes, pos))
+}
+
+//unchecked, for offsets already validated while parsing
+fn be_u16(bytes: &[u8], pos: uint) -> u16 {
+    (bytes[pos] as u16 << 8) | bytes[pos + 1] as u16
+}
+fn be_u32(bytes: &[u8], pos: uint) -> u32 {
+    (bytes[pos] as u32 << 24) | (bytes[pos + 1] as u32 << 16) | (bytes[pos + 2] as u32 << 8) | bytes[pos + 3] as u32
+}
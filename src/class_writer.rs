@@ -0,0 +1,406 @@
+use class_file::*;
+
+//counts and lengths that do not fit their field are errors rather than being truncated into a corrupt class file
+pub fn write_class_file(class_file: &ClassFile, writer: io::Writer) -> Result<(), ClassFormatError> {
+    writer.write_be_u32(class_file.magic);
+    writer.write_be_u16(class_file.minor_version);
+    writer.write_be_u16(class_file.major_version);
+    try!(write_constant_pool(&class_file.constant_pool, writer));
+    writer.write_be_u16(class_file.access_flags);
+    writer.write_be_u16(class_file.this_class);
+    writer.write_be_u16(class_file.super_class);
+    try!(write_u16_vec(class_file.interfaces, writer, "interfaces"));
+    try!(write_u16_count(class_file.fields.len(), writer, "fields_count"));
+    for class_file.fields.each |field| {
+        try!(write_member(field.access_flags, field.name_index, field.descriptor_index, field.attributes, writer));
+    }
+    try!(write_u16_count(class_file.methods.len(), writer, "methods_count"));
+    for class_file.methods.each |method| {
+        try!(write_member(method.access_flags, method.name_index, method.descriptor_index, method.attributes,
+            writer));
+    }
+    write_attributes(class_file.attributes, writer)
+}
+
+pub fn class_file_to_bytes(class_file: &ClassFile) -> Result<~[u8], ClassFormatError> {
+    let mut result = Ok(());
+    let bytes = io::with_bytes_writer(|writer| result = write_class_file(class_file, writer));
+    match result {
+        Ok(()) => Ok(bytes),
+        Err(err) => Err(err)
+    }
+}
+
+fn write_constant_pool(constant_pool: &ConstantPool, writer: io::Writer) -> Result<(), ClassFormatError> {
+    try!(write_u16_count(constant_pool.count(), writer, "constant_pool_count"));
+    //unusable slots are skipped since they have no bytes of their own
+    for constant_pool.each |_, info| {
+        writer.write_u8(info.tag as u8);
+        match info.info {
+            PoolStruct_Class(name_index) => writer.write_be_u16(name_index),
+            PoolStruct_FieldRef(class_index, name_and_type_index) |
+            PoolStruct_MethodRef(class_index, name_and_type_index) |
+            PoolStruct_InterfaceMethodRef(class_index, name_and_type_index) => {
+                writer.write_be_u16(class_index);
+                writer.write_be_u16(name_and_type_index);
+            },
+            PoolStruct_String(string_index) => writer.write_be_u16(string_index),
+            PoolStruct_Integer(bytes) | PoolStruct_Float(bytes) => writer.write_be_u32(bytes),
+            PoolStruct_Long(high_bytes, low_bytes) | PoolStruct_Double(high_bytes, low_bytes) => {
+                writer.write_be_u32(high_bytes);
+                writer.write_be_u32(low_bytes);
+            },
+            PoolStruct_NameAndType(name_index, descriptor_index) => {
+                writer.write_be_u16(name_index);
+                writer.write_be_u16(descriptor_index);
+            },
+            PoolStruct_Utf8(_, ref bytes) => {
+                try!(write_u16_count(bytes.len(), writer, "CONSTANT_Utf8_info"));
+                writer.write(*bytes);
+            },
+            PoolStruct_MethodHandle(reference_kind, reference_index) => {
+                writer.write_u8(reference_kind);
+                writer.write_be_u16(reference_index);
+            },
+            PoolStruct_MethodType(descriptor_index) => writer.write_be_u16(descriptor_index),
+            PoolStruct_Dynamic(bootstrap_method_attr_index, name_and_type_index) |
+            PoolStruct_InvokeDynamic(bootstrap_method_attr_index, name_and_type_index) => {
+                writer.write_be_u16(bootstrap_method_attr_index);
+                writer.write_be_u16(name_and_type_index);
+            },
+            PoolStruct_Module(name_index) | PoolStruct_Package(name_index) => writer.write_be_u16(name_index)
+        }
+    }
+    Ok(())
+}
+
+fn write_member(access_flags: u16, name_index: u16, descriptor_index: u16, attributes: &[AttributeInfo],
+        writer: io::Writer) -> Result<(), ClassFormatError> {
+    writer.write_be_u16(access_flags);
+    writer.write_be_u16(name_index);
+    writer.write_be_u16(descriptor_index);
+    write_attributes(attributes, writer)
+}
+
+fn write_attributes(attributes: &[AttributeInfo], writer: io::Writer) -> Result<(), ClassFormatError> {
+    try!(write_u16_count(attributes.len(), writer, "attributes_count"));
+    for attributes.each |attribute| {
+        try!(write_attribute(attribute, writer));
+    }
+    Ok(())
+}
+
+fn write_attribute(attribute: &AttributeInfo, writer: io::Writer) -> Result<(), ClassFormatError> {
+    //the body is written first so attribute_length reflects what is actually emitted
+    let body_offset = writer.tell() + 6;
+    let mut result = Ok(());
+    let body = io::with_bytes_writer(|body_writer| result = write_attribute_body(&attribute.info, body_writer));
+    match result {
+        Ok(()) => (),
+        Err(ref err) => return Err(err.rebase(body_offset))
+    }
+    if body.len() as u64 > 0xFFFFFFFF {
+        return Err(FormatError_Invalid(writer.tell(), ~"attribute_info",
+            fmt!("attribute_length %u does not fit in a u4", body.len())));
+    }
+    writer.write_be_u16(attribute.attribute_name_index);
+    writer.write_be_u32(body.len() as u32);
+    writer.write(body);
+    Ok(())
+}
+
+fn write_attribute_body(info: &AttributeInfoStructure, writer: io::Writer) -> Result<(), ClassFormatError> {
+    match *info {
+        AttrStruct_ConstantValue(index) => writer.write_be_u16(index),
+        AttrStruct_Code(ref code) => try!(write_code(code, writer)),
+        AttrStruct_StackMapTable(ref frames) => {
+            try!(write_u16_count(frames.len(), writer, "StackMapTable_attribute"));
+            for frames.each |frame| {
+                try!(write_stack_map_frame(frame, writer));
+            }
+        },
+        AttrStruct_Exceptions(ref exception_index_table) =>
+            try!(write_u16_vec(*exception_index_table, writer, "Exceptions_attribute")),
+        AttrStruct_InnerClasses(ref classes) => {
+            try!(write_u16_count(classes.len(), writer, "InnerClasses_attribute"));
+            for classes.each |class| {
+                writer.write_be_u16(class.inner_class_info_index);
+                writer.write_be_u16(class.outer_class_info_index);
+                writer.write_be_u16(class.inner_name_index);
+                writer.write_be_u16(class.inner_class_access_flags);
+            }
+        },
+        AttrStruct_EnclosingMethod(class_index, method_index) => {
+            writer.write_be_u16(class_index);
+            writer.write_be_u16(method_index);
+        },
+        AttrStruct_Synthetic => (),
+        AttrStruct_Signature(signature_index) => writer.write_be_u16(signature_index),
+        AttrStruct_SourceFile(sourcefile_index) => writer.write_be_u16(sourcefile_index),
+        AttrStruct_SourceDebugExtension(ref debug_extension) => writer.write(*debug_extension),
+        AttrStruct_LineNumberTable(ref table) => {
+            try!(write_u16_count(table.len(), writer, "LineNumberTable_attribute"));
+            for table.each |entry| {
+                writer.write_be_u16(entry.start_pc);
+                writer.write_be_u16(entry.line_number);
+            }
+        },
+        AttrStruct_LocalVariableTable(ref table) => {
+            try!(write_u16_count(table.len(), writer, "LocalVariableTable_attribute"));
+            for table.each |entry| {
+                writer.write_be_u16(entry.start_pc);
+                writer.write_be_u16(entry.length);
+                writer.write_be_u16(entry.name_index);
+                writer.write_be_u16(entry.descriptor_index);
+                writer.write_be_u16(entry.index);
+            }
+        },
+        AttrStruct_LocalVariableTypeTable(ref table) => {
+            try!(write_u16_count(table.len(), writer, "LocalVariableTypeTable_attribute"));
+            for table.each |entry| {
+                writer.write_be_u16(entry.start_pc);
+                writer.write_be_u16(entry.length);
+                writer.write_be_u16(entry.name_index);
+                writer.write_be_u16(entry.signature_index);
+                writer.write_be_u16(entry.index);
+            }
+        },
+        AttrStruct_Deprecated => (),
+        AttrStruct_RuntimeVisibleAnnotations(ref annotations) |
+        AttrStruct_RuntimeInvisibleAnnotations(ref annotations) => try!(write_annotations(*annotations, writer)),
+        AttrStruct_RuntimeVisibleParameterAnnotations(ref parameter_annotations) |
+        AttrStruct_RuntimeInvisibleParameterAnnotations(ref parameter_annotations) => {
+            try!(write_u8_count(parameter_annotations.len(), writer, "RuntimeParameterAnnotations_attribute"));
+            for parameter_annotations.each |parameter| {
+                try!(write_annotations(parameter.annotations, writer));
+            }
+        },
+        AttrStruct_RuntimeVisibleTypeAnnotations(ref annotations) |
+        AttrStruct_RuntimeInvisibleTypeAnnotations(ref annotations) => {
+            try!(write_u16_count(annotations.len(), writer, "RuntimeTypeAnnotations_attribute"));
+            for annotations.each |annotation| {
+                try!(write_type_annotation(annotation, writer));
+            }
+        },
+        AttrStruct_AnnotationDefault(ref default_value) => try!(write_element_value(default_value, writer)),
+        AttrStruct_BootstrapMethods(ref bootstrap_methods) => {
+            try!(write_u16_count(bootstrap_methods.len(), writer, "BootstrapMethods_attribute"));
+            for bootstrap_methods.each |method| {
+                writer.write_be_u16(method.bootstrap_method_ref);
+                try!(write_u16_vec(method.bootstrap_arguments, writer, "bootstrap_method"));
+            }
+        },
+        AttrStruct_MethodParameters(ref parameters) => {
+            try!(write_u8_count(parameters.len(), writer, "MethodParameters_attribute"));
+            for parameters.each |parameter| {
+                writer.write_be_u16(parameter.name_index);
+                writer.write_be_u16(parameter.access_flags);
+            }
+        },
+        AttrStruct_Module(ref module) => try!(write_module(module, writer)),
+        AttrStruct_ModulePackages(ref package_index) =>
+            try!(write_u16_vec(*package_index, writer, "ModulePackages_attribute")),
+        AttrStruct_ModuleMainClass(main_class_index) => writer.write_be_u16(main_class_index),
+        AttrStruct_NestHost(host_class_index) => writer.write_be_u16(host_class_index),
+        AttrStruct_NestMembers(ref classes) => try!(write_u16_vec(*classes, writer, "NestMembers_attribute")),
+        AttrStruct_PermittedSubclasses(ref classes) =>
+            try!(write_u16_vec(*classes, writer, "PermittedSubclasses_attribute")),
+        AttrStruct_Record(ref components) => {
+            try!(write_u16_count(components.len(), writer, "Record_attribute"));
+            for components.each |component| {
+                writer.write_be_u16(component.name_index);
+                writer.write_be_u16(component.descriptor_index);
+                try!(write_attributes(component.attributes, writer));
+            }
+        },
+        AttrStruct_Other(ref bytes) => writer.write(*bytes)
+    }
+    Ok(())
+}
+
+fn write_module(module: &ModuleAttributeInfo, writer: io::Writer) -> Result<(), ClassFormatError> {
+    writer.write_be_u16(module.module_name_index);
+    writer.write_be_u16(module.module_flags);
+    writer.write_be_u16(module.module_version_index);
+    try!(write_u16_count(module.requires.len(), writer, "Module_attribute"));
+    for module.requires.each |requires| {
+        writer.write_be_u16(requires.requires_index);
+        writer.write_be_u16(requires.requires_flags);
+        writer.write_be_u16(requires.requires_version_index);
+    }
+    for (~[&module.exports, &module.opens]).each |exports| {
+        try!(write_u16_count(exports.len(), writer, "Module_attribute"));
+        for exports.each |export| {
+            writer.write_be_u16(export.package_index);
+            writer.write_be_u16(export.flags);
+            try!(write_u16_vec(export.to_index, writer, "Module_attribute"));
+        }
+    }
+    try!(write_u16_vec(module.uses, writer, "Module_attribute"));
+    try!(write_u16_count(module.provides.len(), writer, "Module_attribute"));
+    for module.provides.each |provides| {
+        writer.write_be_u16(provides.provides_index);
+        try!(write_u16_vec(provides.provides_with_index, writer, "Module_attribute"));
+    }
+    Ok(())
+}
+
+fn write_code(code: &CodeAttributeInfo, writer: io::Writer) -> Result<(), ClassFormatError> {
+    writer.write_be_u16(code.max_stack);
+    writer.write_be_u16(code.max_locals);
+    //code_length is a u4 but has to be below 65536
+    if code.code.is_empty() || code.code.len() > 0xFFFF {
+        return Err(FormatError_Invalid(writer.tell(), ~"Code_attribute",
+            fmt!("code_length %u is not between 1 and 65535", code.code.len())));
+    }
+    writer.write_be_u32(code.code.len() as u32);
+    writer.write(code.code);
+    try!(write_u16_count(code.exception_table.len(), writer, "Code_attribute"));
+    for code.exception_table.each |entry| {
+        writer.write_be_u16(entry.start_pc);
+        writer.write_be_u16(entry.end_pc);
+        writer.write_be_u16(entry.handler_pc);
+        writer.write_be_u16(entry.catch_type);
+    }
+    write_attributes(code.attributes, writer)
+}
+
+fn write_stack_map_frame(frame: &StackMapFrame, writer: io::Writer) -> Result<(), ClassFormatError> {
+    writer.write_u8(frame.frame_type);
+    match frame.info {
+        StackFrame_Same => (),
+        StackFrame_SameLocalsStackItem(ref stack) => write_verification_type_info(stack, writer),
+        StackFrame_SameLocalsStackItemExtended(offset_delta, ref stack) => {
+            writer.write_be_u16(offset_delta);
+            write_verification_type_info(stack, writer);
+        },
+        StackFrame_Chop(offset_delta) | StackFrame_SameExtended(offset_delta) => writer.write_be_u16(offset_delta),
+        StackFrame_Append(offset_delta, ref locals) => {
+            //the local count is implied by the frame type
+            writer.write_be_u16(offset_delta);
+            for locals.each |local| {
+                write_verification_type_info(local, writer);
+            }
+        },
+        StackFrame_Full(offset_delta, ref locals, ref stack) => {
+            writer.write_be_u16(offset_delta);
+            try!(write_u16_count(locals.len(), writer, "full_frame"));
+            for locals.each |local| {
+                write_verification_type_info(local, writer);
+            }
+            try!(write_u16_count(stack.len(), writer, "full_frame"));
+            for stack.each |item| {
+                write_verification_type_info(item, writer);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_verification_type_info(info: &VerificationTypeInfo, writer: io::Writer) {
+    writer.write_u8(info.tag);
+    match info.info {
+        Var_Object(cpool_index) => writer.write_be_u16(cpool_index),
+        Var_Uninitialized(offset) => writer.write_be_u16(offset),
+        _ => ()
+    }
+}
+
+fn write_annotations(annotations: &[AnnotationInfo], writer: io::Writer) -> Result<(), ClassFormatError> {
+    try!(write_u16_count(annotations.len(), writer, "annotations"));
+    for annotations.each |annotation| {
+        try!(write_annotation(annotation, writer));
+    }
+    Ok(())
+}
+
+fn write_annotation(annotation: &AnnotationInfo, writer: io::Writer) -> Result<(), ClassFormatError> {
+    writer.write_be_u16(annotation.type_index);
+    try!(write_u16_count(annotation.element_value_pairs.len(), writer, "annotation"));
+    for annotation.element_value_pairs.each |pair| {
+        writer.write_be_u16(pair.element_name_index);
+        try!(write_element_value(&pair.value, writer));
+    }
+    Ok(())
+}
+
+fn write_type_annotation(annotation: &TypeAnnotationInfo, writer: io::Writer) -> Result<(), ClassFormatError> {
+    writer.write_u8(annotation.target_type);
+    match annotation.target_info {
+        Target_TypeParameter(type_parameter_index) => writer.write_u8(type_parameter_index),
+        Target_Supertype(supertype_index) => writer.write_be_u16(supertype_index),
+        Target_TypeParameterBound(type_parameter_index, bound_index) => {
+            writer.write_u8(type_parameter_index);
+            writer.write_u8(bound_index);
+        },
+        Target_Empty => (),
+        Target_FormalParameter(formal_parameter_index) => writer.write_u8(formal_parameter_index),
+        Target_Throws(throws_type_index) => writer.write_be_u16(throws_type_index),
+        Target_LocalVar(ref table) => {
+            try!(write_u16_count(table.len(), writer, "localvar_target"));
+            for table.each |entry| {
+                writer.write_be_u16(entry.start_pc);
+                writer.write_be_u16(entry.length);
+                writer.write_be_u16(entry.index);
+            }
+        },
+        Target_Catch(exception_table_index) => writer.write_be_u16(exception_table_index),
+        Target_Offset(offset) => writer.write_be_u16(offset),
+        Target_TypeArgument(offset, type_argument_index) => {
+            writer.write_be_u16(offset);
+            writer.write_u8(type_argument_index);
+        }
+    }
+    try!(write_u8_count(annotation.target_path.len(), writer, "type_path"));
+    for annotation.target_path.each |entry| {
+        writer.write_u8(entry.type_path_kind);
+        writer.write_u8(entry.type_argument_index);
+    }
+    write_annotation(&annotation.annotation, writer)
+}
+
+fn write_element_value(value: &AnnotationElementValue, writer: io::Writer) -> Result<(), ClassFormatError> {
+    writer.write_u8(value.tag);
+    match value.value {
+        ElementValueType_Const(const_value_index) => writer.write_be_u16(const_value_index),
+        ElementValueType_EnumConst(type_name_index, const_name_index) => {
+            writer.write_be_u16(type_name_index);
+            writer.write_be_u16(const_name_index);
+        },
+        ElementValueType_ClassInfo(class_info_index) => writer.write_be_u16(class_info_index),
+        ElementValueType_Annotation(ref annotation) => try!(write_annotation(annotation, writer)),
+        ElementValueType_Array(ref values) => {
+            try!(write_u16_count(values.len(), writer, "array_value"));
+            for values.each |value| {
+                try!(write_element_value(value, writer));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_u16_vec(vec: &[u16], writer: io::Writer, structure: &str) -> Result<(), ClassFormatError> {
+    try!(write_u16_count(vec.len(), writer, structure));
+    for vec.each |value| {
+        writer.write_be_u16(*value);
+    }
+    Ok(())
+}
+
+fn write_u16_count(count: uint, writer: io::Writer, structure: &str) -> Result<(), ClassFormatError> {
+    if count > 0xFFFF {
+        return Err(FormatError_Invalid(writer.tell(), str::from_slice(structure),
+            fmt!("%u does not fit in a u2", count)));
+    }
+    writer.write_be_u16(count as u16);
+    Ok(())
+}
+
+fn write_u8_count(count: uint, writer: io::Writer, structure: &str) -> Result<(), ClassFormatError> {
+    if count > 0xFF {
+        return Err(FormatError_Invalid(writer.tell(), str::from_slice(structure),
+            fmt!("%u does not fit in a u1", count)));
+    }
+    writer.write_u8(count as u8);
+    Ok(())
+}
@@ -0,0 +1,241 @@
+use class_file::{ClassFormatError, FormatError_BadDescriptor};
+
+//field and method descriptors per JVMS 4.3, errors are FormatError_BadDescriptor with the char position in the
+//descriptor
+
+pub enum FieldType {
+    Type_Byte,
+    Type_Char,
+    Type_Double,
+    Type_Float,
+    Type_Int,
+    Type_Long,
+    Type_Short,
+    Type_Boolean,
+    Type_Object(~str /*internal_name*/),
+    Type_Array(~FieldType /*component_type*/)
+}
+
+pub struct MethodDescriptor {
+    parameters: ~[FieldType],
+    //None for void
+    return_type: Option<FieldType>
+}
+
+impl FieldType {
+    //local variable and operand stack slots taken by a value of this type
+    pub fn slot_size(&self) -> uint {
+        match *self {
+            Type_Long | Type_Double => 2,
+            _ => 1
+        }
+    }
+
+    pub fn is_primitive(&self) -> bool {
+        match *self {
+            Type_Object(_) | Type_Array(_) => false,
+            _ => true
+        }
+    }
+
+    pub fn array_dimensions(&self) -> uint {
+        match *self {
+            Type_Array(ref component_type) => 1 + component_type.array_dimensions(),
+            _ => 0
+        }
+    }
+
+    //the descriptor form, e.g. [Ljava/lang/String;
+    pub fn to_str(&self) -> ~str {
+        match *self {
+            Type_Byte => ~"B",
+            Type_Char => ~"C",
+            Type_Double => ~"D",
+            Type_Float => ~"F",
+            Type_Int => ~"I",
+            Type_Long => ~"J",
+            Type_Short => ~"S",
+            Type_Boolean => ~"Z",
+            Type_Object(ref internal_name) => ~"L" + *internal_name + ~";",
+            Type_Array(ref component_type) => ~"[" + component_type.to_str()
+        }
+    }
+
+    //the Java source form, e.g. java.lang.String[]
+    pub fn to_java_str(&self) -> ~str {
+        match *self {
+            Type_Byte => ~"byte",
+            Type_Char => ~"char",
+            Type_Double => ~"double",
+            Type_Float => ~"float",
+            Type_Int => ~"int",
+            Type_Long => ~"long",
+            Type_Short => ~"short",
+            Type_Boolean => ~"boolean",
+            Type_Object(ref internal_name) => str::replace(*internal_name, "/", "."),
+            Type_Array(ref component_type) => component_type.to_java_str() + ~"[]"
+        }
+    }
+}
+
+impl MethodDescriptor {
+    pub fn argument_count(&self) -> uint { self.parameters.len() }
+
+    //slots taken by the arguments, not counting the receiver of an instance method
+    pub fn argument_slots(&self) -> uint {
+        let mut slots = 0u;
+        for self.parameters.each |parameter| {
+            slots += parameter.slot_size();
+        }
+        slots
+    }
+
+    pub fn return_slots(&self) -> uint {
+        match self.return_type {
+            Some(ref return_type) => return_type.slot_size(),
+            None => 0
+        }
+    }
+
+    pub fn to_str(&self) -> ~str {
+        let mut strval = ~"(";
+        for self.parameters.each |parameter| {
+            str::push_str(&mut strval, parameter.to_str());
+        }
+        str::push_char(&mut strval, ')');
+        match self.return_type {
+            Some(ref return_type) => str::push_str(&mut strval, return_type.to_str()),
+            None => str::push_char(&mut strval, 'V')
+        }
+        strval
+    }
+
+    //the Java source form of the parameter list, e.g. (int, java.lang.String[])
+    pub fn java_parameters_str(&self) -> ~str {
+        let parameters = do self.parameters.map |parameter| { parameter.to_java_str() };
+        ~"(" + str::connect(parameters, ", ") + ~")"
+    }
+
+    //the Java source form of a method with this descriptor, e.g. void main(java.lang.String[])
+    pub fn to_java_str(&self, name: &str) -> ~str {
+        let return_type = match self.return_type {
+            Some(ref return_type) => return_type.to_java_str(),
+            None => ~"void"
+        };
+        fmt!("%s %s%s", return_type, name, self.java_parameters_str())
+    }
+}
+
+pub fn parse_field_descriptor(descriptor: &str) -> Result<FieldType, ClassFormatError> {
+    let chars = str::chars(descriptor);
+    let (field_type, pos) = try!(parse_field_type(chars, 0, "field descriptor"));
+    if pos != chars.len() {
+        return Err(invalid(pos, "field descriptor", ~"trailing characters"));
+    }
+    Ok(field_type)
+}
+
+pub fn parse_method_descriptor(descriptor: &str) -> Result<MethodDescriptor, ClassFormatError> {
+    let s = "method descriptor";
+    let chars = str::chars(descriptor);
+    if chars.is_empty() || chars[0] != '(' {
+        return Err(invalid(0, s, ~"expected '('"));
+    }
+    let mut pos = 1u;
+    let mut parameters: ~[FieldType] = ~[];
+    let mut slots = 0u;
+    loop {
+        if pos >= chars.len() {
+            return Err(invalid(pos, s, ~"missing ')'"));
+        }
+        if chars[pos] == ')' {
+            pos += 1;
+            break;
+        }
+        let (parameter, next) = try!(parse_field_type(chars, pos, s));
+        slots += parameter.slot_size();
+        parameters.push(parameter);
+        pos = next;
+    }
+    //JVMS 4.3.3, the receiver slot is checked by the caller since only it knows if the method is static
+    if slots > 255 {
+        return Err(invalid(pos, s, fmt!("%u parameter slots exceeds 255", slots)));
+    }
+    let return_type = if pos < chars.len() && chars[pos] == 'V' {
+        pos += 1;
+        None
+    } else {
+        let (return_type, next) = try!(parse_field_type(chars, pos, s));
+        pos = next;
+        Some(return_type)
+    };
+    if pos != chars.len() {
+        return Err(invalid(pos, s, ~"trailing characters"));
+    }
+    Ok(MethodDescriptor { parameters: parameters, return_type: return_type })
+}
+
+fn parse_field_type(chars: &[char], start: uint, structure: &str) -> Result<(FieldType, uint), ClassFormatError> {
+    if start >= chars.len() {
+        return Err(invalid(start, structure, ~"expected a type"));
+    }
+    match chars[start] {
+        'B' => Ok((Type_Byte, start + 1)),
+        'C' => Ok((Type_Char, start + 1)),
+        'D' => Ok((Type_Double, start + 1)),
+        'F' => Ok((Type_Float, start + 1)),
+        'I' => Ok((Type_Int, start + 1)),
+        'J' => Ok((Type_Long, start + 1)),
+        'S' => Ok((Type_Short, start + 1)),
+        'Z' => Ok((Type_Boolean, start + 1)),
+        'L' => {
+            let mut pos = start + 1;
+            let mut internal_name = ~"";
+            while pos < chars.len() && chars[pos] != ';' {
+                str::push_char(&mut internal_name, chars[pos]);
+                pos += 1;
+            }
+            if pos >= chars.len() {
+                return Err(invalid(start, structure, ~"unterminated class name"));
+            }
+            if !is_valid_internal_name(internal_name) {
+                return Err(invalid(start + 1, structure, fmt!("invalid class name '%s'", internal_name)));
+            }
+            Ok((Type_Object(internal_name), pos + 1))
+        },
+        '[' => {
+            let mut pos = start;
+            while pos < chars.len() && chars[pos] == '[' {
+                pos += 1;
+            }
+            if pos - start > 255 {
+                return Err(invalid(start, structure, ~"more than 255 array dimensions"));
+            }
+            let (component_type, next) = try!(parse_field_type(chars, pos, structure));
+            let mut field_type = component_type;
+            for iter::repeat(pos - start) {
+                field_type = Type_Array(~field_type);
+            }
+            Ok((field_type, next))
+        },
+        c => Err(invalid(start, structure, fmt!("unexpected '%c'", c)))
+    }
+}
+
+//JVMS 4.2.1, slash separated unqualified names
+pub fn is_valid_internal_name(internal_name: &str) -> bool {
+    if internal_name.is_empty() {
+        return false;
+    }
+    for str::split_char(internal_name, '/').each |part| {
+        //'<' and '>' are only allowed in the special method names, which are not class names
+        if part.is_empty() || str::any(*part, |c| c == '.' || c == ';' || c == '[' || c == '<' || c == '>') {
+            return false;
+        }
+    }
+    true
+}
+
+fn invalid(pos: uint, structure: &str, reason: ~str) -> ClassFormatError {
+    FormatError_BadDescriptor(pos, str::from_slice(structure), reason)
+}
@@ -0,0 +1,643 @@
+use class_file::*;
+use bytecode::*;
+
+//prints a listing along the lines of javap -c -v
+pub fn print_class_file(class_file: &ClassFile, writer: io::Writer) {
+    let cp = &class_file.constant_pool;
+    writer.write_line(fmt!("class %s", class_name(cp, class_file.this_class)));
+    writer.write_line(fmt!("  minor version: %u", class_file.minor_version as uint));
+    writer.write_line(fmt!("  major version: %u", class_file.major_version as uint));
+    writer.write_line(fmt!("  flags: %s", flags_to_str(class_file.access_flags, class_file.flags().acc_names())));
+    writer.write_line(fmt!("  this_class: #%u%s", class_file.this_class as uint,
+        comment(constant_to_str(cp, class_file.this_class), 22)));
+    if class_file.super_class == 0 {
+        writer.write_line(~"  super_class: none");
+    } else {
+        writer.write_line(fmt!("  super_class: #%u%s", class_file.super_class as uint,
+            comment(constant_to_str(cp, class_file.super_class), 21)));
+    }
+    writer.write_line(fmt!("  interfaces: %u, fields: %u, methods: %u, attributes: %u",
+        class_file.interfaces.len(), class_file.fields.len(), class_file.methods.len(),
+        class_file.attributes.len()));
+    for class_file.interfaces.each |interface| {
+        writer.write_line(fmt!("    #%u%s", *interface as uint, comment(constant_to_str(cp, *interface), 20)));
+    }
+
+    writer.write_line(~"Constant pool:");
+    for cp.each |index, info| {
+        print_constant(cp, index, info, writer);
+    }
+
+    writer.write_line(~"{");
+    for class_file.fields.each |field| {
+        writer.write_line(fmt!("  %s;", field_declaration(cp, field)));
+        writer.write_line(fmt!("    descriptor: %s", utf8(cp, field.descriptor_index)));
+        writer.write_line(fmt!("    flags: %s", flags_to_str(field.access_flags, field.flags().acc_names())));
+        print_attributes(cp, field.attributes, 4, writer);
+        writer.write_line(~"");
+    }
+    for class_file.methods.each |method| {
+        writer.write_line(fmt!("  %s;", method_declaration(cp, class_file.this_class, method)));
+        writer.write_line(fmt!("    descriptor: %s", utf8(cp, method.descriptor_index)));
+        writer.write_line(fmt!("    flags: %s", flags_to_str(method.access_flags, method.flags().acc_names())));
+        print_attributes(cp, method.attributes, 4, writer);
+        writer.write_line(~"");
+    }
+    writer.write_line(~"}");
+    print_attributes(cp, class_file.attributes, 0, writer);
+}
+
+//modifiers, type, name and for methods the parameters, as javap prints member headers
+fn field_declaration(cp: &ConstantPool, field: &FieldInfo) -> ~str {
+    let descriptor = utf8(cp, field.descriptor_index);
+    let field_type = match descriptor::parse_field_descriptor(descriptor) {
+        Ok(field_type) => field_type.to_java_str(),
+        Err(_) => descriptor
+    };
+    with_modifiers(field.flags().to_str(), field_type + ~" " + utf8(cp, field.name_index))
+}
+
+fn method_declaration(cp: &ConstantPool, this_class: u16, method: &MethodInfo) -> ~str {
+    let name = utf8(cp, method.name_index);
+    let descriptor = utf8(cp, method.descriptor_index);
+    let method_descriptor = match descriptor::parse_method_descriptor(descriptor) {
+        Ok(method_descriptor) => method_descriptor,
+        Err(_) => return with_modifiers(method.flags().to_str(), name + descriptor)
+    };
+    let declaration = if name == ~"<clinit>" {
+        ~"{}"
+    } else if name == ~"<init>" {
+        //constructors are named after the class and have no return type
+        str::replace(class_name(cp, this_class), "/", ".") + method_descriptor.java_parameters_str()
+    } else {
+        method_descriptor.to_java_str(name)
+    };
+    with_modifiers(method.flags().to_str(), declaration)
+}
+
+fn with_modifiers(modifiers: ~str, declaration: ~str) -> ~str {
+    if modifiers.is_empty() { declaration } else { modifiers + ~" " + declaration }
+}
+
+fn flags_to_str(flags: u16, names: &[&static/str]) -> ~str {
+    fmt!("(0x%04x) %s", flags as uint, str::connect_slices(names, ", "))
+}
+
+fn comment(text: ~str, column: uint) -> ~str {
+    let mut padding = ~"";
+    for iter::repeat(column) {
+        str::push_char(&mut padding, ' ');
+    }
+    padding + ~"// " + text
+}
+
+fn utf8(cp: &ConstantPool, index: u16) -> ~str {
+    match cp.get_utf8(index) {
+        Some(strval) => str::from_slice(strval),
+        None => fmt!("<invalid #%u>", index as uint)
+    }
+}
+
+fn class_name(cp: &ConstantPool, index: u16) -> ~str {
+    match cp.get_class_name(index) {
+        Some(name) => str::from_slice(name),
+        None => fmt!("<invalid #%u>", index as uint)
+    }
+}
+
+fn name_and_type(cp: &ConstantPool, index: u16) -> ~str {
+    match cp.get(index) {
+        Some(&ConstantPoolInfo { info: PoolStruct_NameAndType(name_index, descriptor_index), _ }) => {
+            let name = utf8(cp, name_index);
+            //javap quotes special method names
+            let name = if name.starts_with("<") { fmt!("\"%s\"", name) } else { name };
+            name + ~":" + utf8(cp, descriptor_index)
+        },
+        _ => fmt!("<invalid #%u>", index as uint)
+    }
+}
+
+//the resolved form of a constant as shown in javap comments
+fn constant_to_str(cp: &ConstantPool, index: u16) -> ~str {
+    let info = match cp.get(index) {
+        Some(info) => info,
+        None => return fmt!("<invalid #%u>", index as uint)
+    };
+    match info.info {
+        PoolStruct_Class(_) => class_name(cp, index),
+        PoolStruct_FieldRef(class_index, name_and_type_index) |
+        PoolStruct_MethodRef(class_index, name_and_type_index) |
+        PoolStruct_InterfaceMethodRef(class_index, name_and_type_index) =>
+            class_name(cp, class_index) + ~"." + name_and_type(cp, name_and_type_index),
+        PoolStruct_String(string_index) => utf8(cp, string_index),
+        PoolStruct_Integer(_) => fmt!("%d", info.info.integer_value().get() as int),
+        PoolStruct_Float(_) => fmt!("%ff", info.info.float_value().get() as float),
+        PoolStruct_Long(_, _) => i64::to_str(info.info.long_value().get(), 10) + ~"l",
+        PoolStruct_Double(_, _) => fmt!("%fd", info.info.double_value().get() as float),
+        PoolStruct_NameAndType(_, _) => name_and_type(cp, index),
+        PoolStruct_Utf8(ref strval, _) => copy *strval,
+        PoolStruct_MethodHandle(reference_kind, reference_index) =>
+            fmt!("%s %s", reference_kind_name(reference_kind), constant_to_str(cp, reference_index)),
+        PoolStruct_MethodType(descriptor_index) => utf8(cp, descriptor_index),
+        PoolStruct_Dynamic(bootstrap_method_attr_index, name_and_type_index) |
+        PoolStruct_InvokeDynamic(bootstrap_method_attr_index, name_and_type_index) =>
+            fmt!("#%u:%s", bootstrap_method_attr_index as uint, name_and_type(cp, name_and_type_index)),
+        PoolStruct_Module(name_index) | PoolStruct_Package(name_index) => utf8(cp, name_index)
+    }
+}
+
+//the kind prefix javap puts on instruction operand comments
+fn constant_kind(cp: &ConstantPool, index: u16) -> &static/str {
+    match cp.get(index) {
+        Some(&ConstantPoolInfo { info: PoolStruct_Class(_), _ }) => "class",
+        Some(&ConstantPoolInfo { info: PoolStruct_FieldRef(_, _), _ }) => "Field",
+        Some(&ConstantPoolInfo { info: PoolStruct_MethodRef(_, _), _ }) => "Method",
+        Some(&ConstantPoolInfo { info: PoolStruct_InterfaceMethodRef(_, _), _ }) => "InterfaceMethod",
+        Some(&ConstantPoolInfo { info: PoolStruct_String(_), _ }) => "String",
+        Some(&ConstantPoolInfo { info: PoolStruct_Integer(_), _ }) => "int",
+        Some(&ConstantPoolInfo { info: PoolStruct_Float(_), _ }) => "float",
+        Some(&ConstantPoolInfo { info: PoolStruct_Long(_, _), _ }) => "long",
+        Some(&ConstantPoolInfo { info: PoolStruct_Double(_, _), _ }) => "double",
+        Some(&ConstantPoolInfo { info: PoolStruct_MethodHandle(_, _), _ }) => "MethodHandle",
+        Some(&ConstantPoolInfo { info: PoolStruct_MethodType(_), _ }) => "MethodType",
+        Some(&ConstantPoolInfo { info: PoolStruct_Dynamic(_, _), _ }) => "Dynamic",
+        Some(&ConstantPoolInfo { info: PoolStruct_InvokeDynamic(_, _), _ }) => "InvokeDynamic",
+        _ => ""
+    }
+}
+
+fn reference_kind_name(reference_kind: u8) -> &static/str {
+    match reference_kind {
+        1 => "REF_getField",
+        2 => "REF_getStatic",
+        3 => "REF_putField",
+        4 => "REF_putStatic",
+        5 => "REF_invokeVirtual",
+        6 => "REF_invokeStatic",
+        7 => "REF_invokeSpecial",
+        8 => "REF_newInvokeSpecial",
+        9 => "REF_invokeInterface",
+        _ => "REF_unknown"
+    }
+}
+
+fn print_constant(cp: &ConstantPool, index: u16, info: &ConstantPoolInfo, writer: io::Writer) {
+    let (kind, args) = match info.info {
+        PoolStruct_Class(name_index) => ("Class", fmt!("#%u", name_index as uint)),
+        PoolStruct_FieldRef(class_index, name_and_type_index) =>
+            ("Fieldref", fmt!("#%u.#%u", class_index as uint, name_and_type_index as uint)),
+        PoolStruct_MethodRef(class_index, name_and_type_index) =>
+            ("Methodref", fmt!("#%u.#%u", class_index as uint, name_and_type_index as uint)),
+        PoolStruct_InterfaceMethodRef(class_index, name_and_type_index) =>
+            ("InterfaceMethodref", fmt!("#%u.#%u", class_index as uint, name_and_type_index as uint)),
+        PoolStruct_String(string_index) => ("String", fmt!("#%u", string_index as uint)),
+        PoolStruct_Integer(_) => ("Integer", constant_to_str(cp, index)),
+        PoolStruct_Float(_) => ("Float", constant_to_str(cp, index)),
+        PoolStruct_Long(_, _) => ("Long", constant_to_str(cp, index)),
+        PoolStruct_Double(_, _) => ("Double", constant_to_str(cp, index)),
+        PoolStruct_NameAndType(name_index, descriptor_index) =>
+            ("NameAndType", fmt!("#%u:#%u", name_index as uint, descriptor_index as uint)),
+        PoolStruct_Utf8(ref strval, _) => ("Utf8", copy *strval),
+        PoolStruct_MethodHandle(reference_kind, reference_index) =>
+            ("MethodHandle", fmt!("%u:#%u", reference_kind as uint, reference_index as uint)),
+        PoolStruct_MethodType(descriptor_index) => ("MethodType", fmt!("#%u", descriptor_index as uint)),
+        PoolStruct_Dynamic(bootstrap_method_attr_index, name_and_type_index) =>
+            ("Dynamic", fmt!("#%u:#%u", bootstrap_method_attr_index as uint, name_and_type_index as uint)),
+        PoolStruct_InvokeDynamic(bootstrap_method_attr_index, name_and_type_index) =>
+            ("InvokeDynamic", fmt!("#%u:#%u", bootstrap_method_attr_index as uint, name_and_type_index as uint)),
+        PoolStruct_Module(name_index) => ("Module", fmt!("#%u", name_index as uint)),
+        PoolStruct_Package(name_index) => ("Package", fmt!("#%u", name_index as uint))
+    };
+    let line = fmt!("%5s = %-18s %s", fmt!("#%u", index as uint), kind, args);
+    match info.info {
+        PoolStruct_Utf8(_, _) | PoolStruct_Integer(_) | PoolStruct_Float(_) | PoolStruct_Long(_, _) |
+        PoolStruct_Double(_, _) => writer.write_line(line),
+        _ => writer.write_line(fmt!("%-42s // %s", line, constant_to_str(cp, index)))
+    }
+}
+
+fn indent(level: uint) -> ~str {
+    let mut padding = ~"";
+    for iter::repeat(level) {
+        str::push_char(&mut padding, ' ');
+    }
+    padding
+}
+
+fn print_attributes(cp: &ConstantPool, attributes: &[AttributeInfo], level: uint, writer: io::Writer) {
+    let pad = indent(level);
+    for attributes.each |attribute| {
+        let name = utf8(cp, attribute.attribute_name_index);
+        match attribute.info {
+            AttrStruct_ConstantValue(index) => writer.write_line(fmt!("%sConstantValue: %s %s", pad,
+                constant_kind(cp, index), constant_to_str(cp, index))),
+            AttrStruct_Code(ref code) => print_code(cp, code, level, writer),
+            AttrStruct_StackMapTable(ref frames) => print_stack_map_table(cp, *frames, level, writer),
+            AttrStruct_Exceptions(ref exception_index_table) => {
+                writer.write_line(fmt!("%sExceptions:", pad));
+                for exception_index_table.each |index| {
+                    writer.write_line(fmt!("%s  throws %s", pad, class_name(cp, *index)));
+                }
+            },
+            AttrStruct_InnerClasses(ref classes) => {
+                writer.write_line(fmt!("%sInnerClasses:", pad));
+                for classes.each |class| {
+                    let inner_name = if class.inner_name_index == 0 { ~"<anonymous>" }
+                        else { utf8(cp, class.inner_name_index) };
+                    let outer = if class.outer_class_info_index == 0 { ~"" }
+                        else { ~" of " + class_name(cp, class.outer_class_info_index) };
+                    writer.write_line(fmt!("%s  %s = %s%s %s", pad, inner_name,
+                        class_name(cp, class.inner_class_info_index), outer,
+                        flags_to_str(class.inner_class_access_flags, class.flags().acc_names())));
+                }
+            },
+            AttrStruct_EnclosingMethod(class_index, method_index) => {
+                let method = if method_index == 0 { ~"" } else { ~"." + name_and_type(cp, method_index) };
+                writer.write_line(fmt!("%sEnclosingMethod: %s%s", pad, class_name(cp, class_index), method));
+            },
+            AttrStruct_Synthetic => writer.write_line(fmt!("%sSynthetic: true", pad)),
+            AttrStruct_Signature(signature_index) => writer.write_line(fmt!("%sSignature: %s", pad,
+                utf8(cp, signature_index))),
+            AttrStruct_SourceFile(sourcefile_index) => writer.write_line(fmt!("%sSourceFile: \"%s\"", pad,
+                utf8(cp, sourcefile_index))),
+            AttrStruct_LineNumberTable(ref table) => {
+                writer.write_line(fmt!("%sLineNumberTable:", pad));
+                for table.each |entry| {
+                    writer.write_line(fmt!("%s  line %u: %u", pad, entry.line_number as uint,
+                        entry.start_pc as uint));
+                }
+            },
+            AttrStruct_LocalVariableTable(ref table) => {
+                writer.write_line(fmt!("%sLocalVariableTable:", pad));
+                writer.write_line(fmt!("%s  Start  Length  Slot  Name   Signature", pad));
+                for table.each |entry| {
+                    writer.write_line(fmt!("%s  %5u  %6u  %4u  %5s   %s", pad, entry.start_pc as uint,
+                        entry.length as uint, entry.index as uint, utf8(cp, entry.name_index),
+                        utf8(cp, entry.descriptor_index)));
+                }
+            },
+            AttrStruct_LocalVariableTypeTable(ref table) => {
+                writer.write_line(fmt!("%sLocalVariableTypeTable:", pad));
+                writer.write_line(fmt!("%s  Start  Length  Slot  Name   Signature", pad));
+                for table.each |entry| {
+                    writer.write_line(fmt!("%s  %5u  %6u  %4u  %5s   %s", pad, entry.start_pc as uint,
+                        entry.length as uint, entry.index as uint, utf8(cp, entry.name_index),
+                        utf8(cp, entry.signature_index)));
+                }
+            },
+            AttrStruct_Deprecated => writer.write_line(fmt!("%sDeprecated: true", pad)),
+            AttrStruct_RuntimeVisibleAnnotations(ref annotations) |
+            AttrStruct_RuntimeInvisibleAnnotations(ref annotations) => {
+                writer.write_line(fmt!("%s%s:", pad, name));
+                for annotations.each |annotation| {
+                    writer.write_line(fmt!("%s  %s", pad, annotation_to_str(cp, annotation)));
+                }
+            },
+            AttrStruct_RuntimeVisibleParameterAnnotations(ref parameters) |
+            AttrStruct_RuntimeInvisibleParameterAnnotations(ref parameters) => {
+                writer.write_line(fmt!("%s%s:", pad, name));
+                for parameters.eachi |i, parameter| {
+                    writer.write_line(fmt!("%s  parameter %u:", pad, i));
+                    for parameter.annotations.each |annotation| {
+                        writer.write_line(fmt!("%s    %s", pad, annotation_to_str(cp, annotation)));
+                    }
+                }
+            },
+            AttrStruct_RuntimeVisibleTypeAnnotations(ref annotations) |
+            AttrStruct_RuntimeInvisibleTypeAnnotations(ref annotations) => {
+                writer.write_line(fmt!("%s%s:", pad, name));
+                for annotations.each |annotation| {
+                    writer.write_line(fmt!("%s  %s: %s", pad, annotation_to_str(cp, &annotation.annotation),
+                        type_annotation_target_to_str(annotation)));
+                }
+            },
+            AttrStruct_AnnotationDefault(ref default_value) => writer.write_line(fmt!("%sAnnotationDefault: %s",
+                pad, element_value_to_str(cp, default_value))),
+            AttrStruct_BootstrapMethods(ref methods) => {
+                writer.write_line(fmt!("%sBootstrapMethods:", pad));
+                for methods.eachi |i, method| {
+                    writer.write_line(fmt!("%s  %u: #%u %s", pad, i, method.bootstrap_method_ref as uint,
+                        constant_to_str(cp, method.bootstrap_method_ref)));
+                    writer.write_line(fmt!("%s    Method arguments:", pad));
+                    for method.bootstrap_arguments.each |argument| {
+                        writer.write_line(fmt!("%s      #%u %s", pad, *argument as uint,
+                            constant_to_str(cp, *argument)));
+                    }
+                }
+            },
+            AttrStruct_MethodParameters(ref parameters) => {
+                writer.write_line(fmt!("%sMethodParameters:", pad));
+                writer.write_line(fmt!("%s  Name                           Flags", pad));
+                for parameters.each |parameter| {
+                    let parameter_name = if parameter.name_index == 0 { ~"<no name>" }
+                        else { utf8(cp, parameter.name_index) };
+                    writer.write_line(fmt!("%s  %-30s 0x%04x", pad, parameter_name, parameter.access_flags as uint));
+                }
+            },
+            AttrStruct_Module(ref module) => print_module(cp, module, level, writer),
+            AttrStruct_ModulePackages(ref package_index) => {
+                writer.write_line(fmt!("%sModulePackages:", pad));
+                for package_index.each |index| {
+                    writer.write_line(fmt!("%s  #%u%s", pad, *index as uint, comment(constant_to_str(cp, *index), 10)));
+                }
+            },
+            AttrStruct_ModuleMainClass(main_class_index) => writer.write_line(fmt!("%sModuleMainClass: #%u%s",
+                pad, main_class_index as uint, comment(class_name(cp, main_class_index), 10))),
+            AttrStruct_NestHost(host_class_index) => writer.write_line(fmt!("%sNestHost: class %s", pad,
+                class_name(cp, host_class_index))),
+            AttrStruct_NestMembers(ref classes) | AttrStruct_PermittedSubclasses(ref classes) => {
+                writer.write_line(fmt!("%s%s:", pad, name));
+                for classes.each |index| {
+                    writer.write_line(fmt!("%s  %s", pad, class_name(cp, *index)));
+                }
+            },
+            AttrStruct_Record(ref components) => {
+                writer.write_line(fmt!("%sRecord:", pad));
+                for components.each |component| {
+                    writer.write_line(fmt!("%s  %s %s;", pad, utf8(cp, component.descriptor_index),
+                        utf8(cp, component.name_index)));
+                    print_attributes(cp, component.attributes, level + 4, writer);
+                }
+            },
+            AttrStruct_SourceDebugExtension(ref bytes) | AttrStruct_Other(ref bytes) =>
+                writer.write_line(fmt!("%s%s: length = 0x%x", pad, name, bytes.len()))
+        }
+    }
+}
+
+fn print_module(cp: &ConstantPool, module: &ModuleAttributeInfo, level: uint, writer: io::Writer) {
+    let pad = indent(level);
+    let version = if module.module_version_index == 0 { ~"" }
+        else { ~"@" + utf8(cp, module.module_version_index) };
+    writer.write_line(fmt!("%sModule: %s%s flags: 0x%04x", pad, constant_to_str(cp, module.module_name_index),
+        version, module.module_flags as uint));
+    for module.requires.each |requires| {
+        let version = if requires.requires_version_index == 0 { ~"" }
+            else { ~"@" + utf8(cp, requires.requires_version_index) };
+        writer.write_line(fmt!("%s  requires %s%s flags: 0x%04x", pad, constant_to_str(cp, requires.requires_index),
+            version, requires.requires_flags as uint));
+    }
+    for (~[("exports", &module.exports), ("opens", &module.opens)]).each |pair| {
+        let (kind, exports) = *pair;
+        for exports.each |export| {
+            let mut targets: ~[~str] = ~[];
+            for export.to_index.each |index| {
+                targets.push(constant_to_str(cp, *index));
+            }
+            let to = if targets.is_empty() { ~"" } else { ~" to " + str::connect(targets, ", ") };
+            writer.write_line(fmt!("%s  %s %s%s flags: 0x%04x", pad, kind, constant_to_str(cp, export.package_index),
+                to, export.flags as uint));
+        }
+    }
+    for module.uses.each |index| {
+        writer.write_line(fmt!("%s  uses %s", pad, class_name(cp, *index)));
+    }
+    for module.provides.each |provides| {
+        let mut implementations: ~[~str] = ~[];
+        for provides.provides_with_index.each |index| {
+            implementations.push(class_name(cp, *index));
+        }
+        writer.write_line(fmt!("%s  provides %s with %s", pad, class_name(cp, provides.provides_index),
+            str::connect(implementations, ", ")));
+    }
+}
+
+fn annotation_to_str(cp: &ConstantPool, annotation: &AnnotationInfo) -> ~str {
+    let mut pairs: ~[~str] = ~[];
+    for annotation.element_value_pairs.each |pair| {
+        pairs.push(utf8(cp, pair.element_name_index) + ~"=" + element_value_to_str(cp, &pair.value));
+    }
+    fmt!("%s(%s)", utf8(cp, annotation.type_index), str::connect(pairs, ","))
+}
+
+fn type_annotation_target_to_str(annotation: &TypeAnnotationInfo) -> ~str {
+    let kind = match annotation.target_type {
+        0x00 => "CLASS_TYPE_PARAMETER",
+        0x01 => "METHOD_TYPE_PARAMETER",
+        0x10 => "CLASS_EXTENDS",
+        0x11 => "CLASS_TYPE_PARAMETER_BOUND",
+        0x12 => "METHOD_TYPE_PARAMETER_BOUND",
+        0x13 => "FIELD",
+        0x14 => "METHOD_RETURN",
+        0x15 => "METHOD_RECEIVER",
+        0x16 => "METHOD_FORMAL_PARAMETER",
+        0x17 => "THROWS",
+        0x40 => "LOCAL_VARIABLE",
+        0x41 => "RESOURCE_VARIABLE",
+        0x42 => "EXCEPTION_PARAMETER",
+        0x43 => "INSTANCEOF",
+        0x44 => "NEW",
+        0x45 => "CONSTRUCTOR_REFERENCE",
+        0x46 => "METHOD_REFERENCE",
+        0x47 => "CAST",
+        0x48 => "CONSTRUCTOR_INVOCATION_TYPE_ARGUMENT",
+        0x49 => "METHOD_INVOCATION_TYPE_ARGUMENT",
+        0x4A => "CONSTRUCTOR_REFERENCE_TYPE_ARGUMENT",
+        0x4B => "METHOD_REFERENCE_TYPE_ARGUMENT",
+        _ => "UNKNOWN"
+    };
+    let mut strval = str::from_slice(kind);
+    match annotation.target_info {
+        Target_TypeParameter(type_parameter_index) =>
+            str::push_str(&mut strval, fmt!(", param_index=%u", type_parameter_index as uint)),
+        Target_Supertype(supertype_index) =>
+            str::push_str(&mut strval, fmt!(", type_index=%u", supertype_index as uint)),
+        Target_TypeParameterBound(type_parameter_index, bound_index) =>
+            str::push_str(&mut strval, fmt!(", param_index=%u, bound_index=%u", type_parameter_index as uint,
+                bound_index as uint)),
+        Target_Empty => (),
+        Target_FormalParameter(formal_parameter_index) =>
+            str::push_str(&mut strval, fmt!(", param_index=%u", formal_parameter_index as uint)),
+        Target_Throws(throws_type_index) =>
+            str::push_str(&mut strval, fmt!(", type_index=%u", throws_type_index as uint)),
+        Target_LocalVar(ref table) => {
+            let mut entries: ~[~str] = ~[];
+            for table.each |entry| {
+                entries.push(fmt!("start_pc=%u, length=%u, index=%u", entry.start_pc as uint,
+                    entry.length as uint, entry.index as uint));
+            }
+            str::push_str(&mut strval, ~", {" + str::connect(entries, "; ") + ~"}");
+        },
+        Target_Catch(exception_table_index) =>
+            str::push_str(&mut strval, fmt!(", exception_index=%u", exception_table_index as uint)),
+        Target_Offset(offset) => str::push_str(&mut strval, fmt!(", offset=%u", offset as uint)),
+        Target_TypeArgument(offset, type_argument_index) =>
+            str::push_str(&mut strval, fmt!(", offset=%u, type_index=%u", offset as uint,
+                type_argument_index as uint))
+    }
+    if !annotation.target_path.is_empty() {
+        let mut path: ~[~str] = ~[];
+        for annotation.target_path.each |entry| {
+            path.push(match entry.type_path_kind {
+                0 => ~"ARRAY",
+                1 => ~"INNER_TYPE",
+                2 => ~"WILDCARD",
+                _ => fmt!("TYPE_ARGUMENT(%u)", entry.type_argument_index as uint)
+            });
+        }
+        str::push_str(&mut strval, ~", location=[" + str::connect(path, ", ") + ~"]");
+    }
+    strval
+}
+
+fn element_value_to_str(cp: &ConstantPool, value: &AnnotationElementValue) -> ~str {
+    match value.value {
+        ElementValueType_Const(const_value_index) => match value.tag as char {
+            's' => fmt!("\"%s\"", utf8(cp, const_value_index)),
+            _ => constant_to_str(cp, const_value_index)
+        },
+        ElementValueType_EnumConst(type_name_index, const_name_index) =>
+            utf8(cp, type_name_index) + ~"." + utf8(cp, const_name_index),
+        ElementValueType_ClassInfo(class_info_index) => utf8(cp, class_info_index) + ~".class",
+        ElementValueType_Annotation(ref annotation) => ~"@" + annotation_to_str(cp, annotation),
+        ElementValueType_Array(ref values) => {
+            let mut items: ~[~str] = ~[];
+            for values.each |value| {
+                items.push(element_value_to_str(cp, value));
+            }
+            ~"[" + str::connect(items, ",") + ~"]"
+        }
+    }
+}
+
+fn print_code(cp: &ConstantPool, code: &CodeAttributeInfo, level: uint, writer: io::Writer) {
+    let pad = indent(level);
+    writer.write_line(fmt!("%sCode:", pad));
+    writer.write_line(fmt!("%s  stack=%u, locals=%u", pad, code.max_stack as uint, code.max_locals as uint));
+    let result = do bytecode::each_instruction(code.code) |pc, insn| {
+        writer.write_line(fmt!("%s  %5u: %s", pad, pc, instruction_to_str(cp, insn, level + 9)));
+        true
+    };
+    match result {
+        Err(err) => writer.write_line(fmt!("%s  <invalid code: %s>", pad, err.to_str())),
+        Ok(_) => ()
+    }
+    if !code.exception_table.is_empty() {
+        writer.write_line(fmt!("%s  Exception table:", pad));
+        writer.write_line(fmt!("%s     from    to  target type", pad));
+        for code.exception_table.each |entry| {
+            let catch_type = if entry.catch_type == 0 { ~"any" }
+                else { ~"Class " + class_name(cp, entry.catch_type) };
+            writer.write_line(fmt!("%s    %5u %5u %5u   %s", pad, entry.start_pc as uint, entry.end_pc as uint,
+                entry.handler_pc as uint, catch_type));
+        }
+    }
+    print_attributes(cp, code.attributes, level + 2, writer);
+}
+
+fn instruction_to_str(cp: &ConstantPool, insn: &Instruction, level: uint) -> ~str {
+    let mnemonic = str::from_slice(insn.mnemonic());
+    let with_constant = |index: u16, extra: ~str| {
+        let operands = fmt!("#%u%s", index as uint, extra);
+        fmt!("%-14s%-19s// %s %s", mnemonic, operands, constant_kind(cp, index), constant_to_str(cp, index))
+    };
+    match *insn {
+        Insn_Bipush(value) => fmt!("%-14s%d", mnemonic, value as int),
+        Insn_Sipush(value) => fmt!("%-14s%d", mnemonic, value as int),
+        Insn_Ldc(index) | Insn_LdcW(index) | Insn_Ldc2W(index) | Insn_Getstatic(index) |
+        Insn_Putstatic(index) | Insn_Getfield(index) | Insn_Putfield(index) | Insn_Invokevirtual(index) |
+        Insn_Invokespecial(index) | Insn_Invokestatic(index) | Insn_Invokedynamic(index) | Insn_New(index) |
+        Insn_Anewarray(index) | Insn_Checkcast(index) | Insn_Instanceof(index) => with_constant(index, ~""),
+        Insn_Invokeinterface(index, count) => with_constant(index, fmt!(",  %u", count as uint)),
+        Insn_Multianewarray(index, dimensions) => with_constant(index, fmt!(",  %u", dimensions as uint)),
+        Insn_Iload(index) | Insn_Lload(index) | Insn_Fload(index) | Insn_Dload(index) | Insn_Aload(index) |
+        Insn_Istore(index) | Insn_Lstore(index) | Insn_Fstore(index) | Insn_Dstore(index) | Insn_Astore(index) |
+        Insn_Ret(index) => fmt!("%-14s%u", mnemonic, index as uint),
+        Insn_Iinc(index, value) => fmt!("%-14s%u, %d", mnemonic, index as uint, value as int),
+        Insn_Newarray(atype) => fmt!("%-14s%s", mnemonic, array_type_name(atype)),
+        Insn_Tableswitch(default, low, _, ref targets) => {
+            let pad = indent(level);
+            let mut text = fmt!("%s{ // %d to %d", mnemonic, low as int, (low as int) + targets.len() as int - 1);
+            for targets.eachi |i, target| {
+                text += fmt!("\n%s%12d: %u", pad, low as int + i as int, *target);
+            }
+            text + fmt!("\n%s%12s: %u\n%s}", pad, "default", default, pad)
+        },
+        Insn_Lookupswitch(default, ref pairs) => {
+            let pad = indent(level);
+            let mut text = fmt!("%s{ // %u", mnemonic, pairs.len());
+            for pairs.each |pair| {
+                let (key, target) = *pair;
+                text += fmt!("\n%s%12d: %u", pad, key as int, target);
+            }
+            text + fmt!("\n%s%12s: %u\n%s}", pad, "default", default, pad)
+        },
+        _ => {
+            let targets = insn.branch_targets();
+            if targets.len() == 1 { fmt!("%-14s%u", mnemonic, targets[0]) } else { mnemonic }
+        }
+    }
+}
+
+fn array_type_name(atype: u8) -> ~str {
+    match atype {
+        4 => ~"boolean",
+        5 => ~"char",
+        6 => ~"float",
+        7 => ~"double",
+        8 => ~"byte",
+        9 => ~"short",
+        10 => ~"int",
+        11 => ~"long",
+        _ => fmt!("<unknown %u>", atype as uint)
+    }
+}
+
+fn print_stack_map_table(cp: &ConstantPool, frames: &[StackMapFrame], level: uint, writer: io::Writer) {
+    let pad = indent(level);
+    writer.write_line(fmt!("%sStackMapTable: number_of_entries = %u", pad, frames.len()));
+    for frames.each |frame| {
+        let kind = match frame.info {
+            StackFrame_Same => "same",
+            StackFrame_SameLocalsStackItem(_) => "same_locals_1_stack_item",
+            StackFrame_SameLocalsStackItemExtended(_, _) => "same_locals_1_stack_item_frame_extended",
+            StackFrame_Chop(_) => "chop",
+            StackFrame_SameExtended(_) => "same_frame_extended",
+            StackFrame_Append(_, _) => "append",
+            StackFrame_Full(_, _, _) => "full_frame"
+        };
+        writer.write_line(fmt!("%s  frame_type = %u /* %s */", pad, frame.frame_type as uint, kind));
+        match frame.info {
+            StackFrame_Same => (),
+            StackFrame_SameLocalsStackItem(ref stack) =>
+                writer.write_line(fmt!("%s    stack = [ %s ]", pad, verification_type_to_str(cp, stack))),
+            StackFrame_SameLocalsStackItemExtended(offset_delta, ref stack) => {
+                writer.write_line(fmt!("%s    offset_delta = %u", pad, offset_delta as uint));
+                writer.write_line(fmt!("%s    stack = [ %s ]", pad, verification_type_to_str(cp, stack)));
+            },
+            StackFrame_Chop(offset_delta) | StackFrame_SameExtended(offset_delta) =>
+                writer.write_line(fmt!("%s    offset_delta = %u", pad, offset_delta as uint)),
+            StackFrame_Append(offset_delta, ref locals) => {
+                writer.write_line(fmt!("%s    offset_delta = %u", pad, offset_delta as uint));
+                writer.write_line(fmt!("%s    locals = [ %s ]", pad, verification_types_to_str(cp, *locals)));
+            },
+            StackFrame_Full(offset_delta, ref locals, ref stack) => {
+                writer.write_line(fmt!("%s    offset_delta = %u", pad, offset_delta as uint));
+                writer.write_line(fmt!("%s    locals = [ %s ]", pad, verification_types_to_str(cp, *locals)));
+                writer.write_line(fmt!("%s    stack = [ %s ]", pad, verification_types_to_str(cp, *stack)));
+            }
+        }
+    }
+}
+
+fn verification_types_to_str(cp: &ConstantPool, infos: &[VerificationTypeInfo]) -> ~str {
+    let mut items: ~[~str] = ~[];
+    for infos.each |info| {
+        items.push(verification_type_to_str(cp, info));
+    }
+    str::connect(items, ", ")
+}
+
+fn verification_type_to_str(cp: &ConstantPool, info: &VerificationTypeInfo) -> ~str {
+    match info.info {
+        Var_Top => ~"top",
+        Var_Integer => ~"int",
+        Var_Float => ~"float",
+        Var_Long => ~"long",
+        Var_Double => ~"double",
+        Var_Null => ~"null",
+        Var_UninitializedThis => ~"this",
+        Var_Object(cpool_index) => ~"class " + class_name(cp, cpool_index),
+        Var_Uninitialized(offset) => fmt!("uninitialized %u", offset as uint)
+    }
+}
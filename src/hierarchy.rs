@@ -0,0 +1,334 @@
+use class_file::{ClassFile, ClassHeader, ClassAccess_Interface};
+use class_path::{ClassPath, LoadError};
+use core::send_map::linear::LinearMap;
+
+//the type hierarchy of a set of classes by internal name, supertypes that are referenced but were never added are
+//phantom nodes so queries still work on a partial class set
+
+pub struct ClassNode {
+    name: ~str,
+    //0 for phantoms
+    access_flags: u16,
+    //indices into the hierarchy's nodes
+    super_class: Option<uint>,
+    interfaces: ~[uint],
+    //classes whose super_class is this one
+    subclasses: ~[uint],
+    //classes and interfaces listing this one among their interfaces
+    implementors: ~[uint],
+    phantom: bool
+}
+
+impl ClassNode {
+    pub fn is_interface(&self) -> bool {
+        self.access_flags & (ClassAccess_Interface as u16) != 0
+    }
+}
+
+pub struct ClassHierarchy {
+    nodes: ~[ClassNode],
+    indices: LinearMap<~str, uint>
+}
+
+pub fn ClassHierarchy() -> ClassHierarchy {
+    ClassHierarchy { nodes: ~[], indices: LinearMap() }
+}
+
+//classes whose names are not valid class constants are left out
+pub fn build_hierarchy(classes: &[ClassFile]) -> ClassHierarchy {
+    let mut hierarchy = ClassHierarchy();
+    for classes.each |class_file| {
+        hierarchy.add_class(class_file);
+    }
+    hierarchy
+}
+
+impl ClassHierarchy {
+    //like a class path the first class added under a name wins, later ones are ignored
+    pub fn add(&mut self, name: &str, access_flags: u16, super_name: Option<&str>, interfaces: &[&str]) {
+        let index = self.node_index(name);
+        if !self.nodes[index].phantom {
+            return;
+        }
+        let super_class = match super_name {
+            Some(super_name) => {
+                let super_index = self.node_index(super_name);
+                self.nodes[super_index].subclasses.push(index);
+                Some(super_index)
+            },
+            None => None
+        };
+        let mut interface_indices = ~[];
+        for interfaces.each |interface| {
+            let interface_index = self.node_index(*interface);
+            self.nodes[interface_index].implementors.push(index);
+            interface_indices.push(interface_index);
+        }
+        self.nodes[index].access_flags = access_flags;
+        self.nodes[index].super_class = super_class;
+        self.nodes[index].interfaces = interface_indices;
+        self.nodes[index].phantom = false;
+    }
+
+    //false, adding nothing, when this_class, super_class or one of the interfaces is not a class constant
+    pub fn add_class(&mut self, class_file: &ClassFile) -> bool {
+        let super_name = class_file.super_name();
+        if class_file.super_class != 0 && super_name.is_none() {
+            return false;
+        }
+        match (class_file.class_name(), class_file.interfaces()) {
+            (Some(name), Some(interfaces)) => {
+                self.add(name, class_file.access_flags, super_name, interfaces);
+                true
+            },
+            _ => false
+        }
+    }
+
+    pub fn add_header(&mut self, header: &ClassHeader) {
+        let super_name = match header.super_class {
+            Some(ref super_name) => Some(super_name.as_slice()),
+            None => None
+        };
+        let interfaces = do header.interfaces.map |interface| { interface.as_slice() };
+        self.add(header.this_class, header.access_flags, super_name, interfaces);
+    }
+
+    //replaces phantoms with classes from the class path, including the phantoms those classes bring in, those not
+    //on the class path or with invalid names stay phantoms
+    pub fn load_phantoms(&mut self, class_path: &ClassPath) -> Result<(), LoadError> {
+        let mut index = 0u;
+        while index < self.nodes.len() {
+            if self.nodes[index].phantom {
+                let name = copy self.nodes[index].name;
+                match try!(class_path.load(name)) {
+                    Some(class_file) => { self.add_class(class_file); },
+                    None => ()
+                }
+            }
+            index += 1;
+        }
+        Ok(())
+    }
+
+    fn node_index(&mut self, name: &str) -> uint {
+        let key = str::from_slice(name);
+        match self.indices.find(&key) {
+            Some(index) => return *index,
+            None => ()
+        }
+        let index = self.nodes.len();
+        self.nodes.push(ClassNode {
+            name: copy key,
+            access_flags: 0,
+            super_class: None,
+            interfaces: ~[],
+            subclasses: ~[],
+            implementors: ~[],
+            phantom: true
+        });
+        self.indices.insert(key, index);
+        index
+    }
+
+    fn find_index(&self, name: &str) -> Option<uint> {
+        match self.indices.find(&str::from_slice(name)) {
+            Some(index) => Some(*index),
+            None => None
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&self/ClassNode> {
+        match self.find_index(name) {
+            Some(index) => Some(&self.nodes[index]),
+            None => None
+        }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        match self.get(name) {
+            Some(node) => !node.phantom,
+            None => false
+        }
+    }
+
+    pub fn phantoms(&self) -> ~[&self/str] {
+        let mut names: ~[&self/str] = ~[];
+        for self.nodes.each |node| {
+            if node.phantom {
+                names.push(node.name);
+            }
+        }
+        names
+    }
+
+    fn names(&self, indices: &[uint]) -> ~[&self/str] {
+        let mut names: ~[&self/str] = ~[];
+        for indices.each |index| {
+            names.push(self.nodes[*index].name);
+        }
+        names
+    }
+
+    pub fn direct_subclasses(&self, name: &str) -> ~[&self/str] {
+        match self.find_index(name) {
+            Some(index) => self.names(self.nodes[index].subclasses),
+            None => ~[]
+        }
+    }
+
+    //the superclass chain nearest first, stopping at a phantom or a cycle
+    pub fn superclasses(&self, name: &str) -> ~[&self/str] {
+        let mut chain = ~[];
+        let mut seen = vec::from_elem(self.nodes.len(), false);
+        let mut current = match self.find_index(name) {
+            Some(index) => self.nodes[index].super_class,
+            None => None
+        };
+        loop {
+            match current {
+                Some(index) if !seen[index] => {
+                    seen[index] = true;
+                    chain.push(index);
+                    current = self.nodes[index].super_class;
+                },
+                _ => break
+            }
+        }
+        self.names(chain)
+    }
+
+    //every class and interface the named type extends or implements, directly or not, breadth first and not
+    //including itself
+    pub fn all_supertypes(&self, name: &str) -> ~[&self/str] {
+        let start = match self.find_index(name) {
+            Some(index) => index,
+            None => return ~[]
+        };
+        let mut seen = vec::from_elem(self.nodes.len(), false);
+        seen[start] = true;
+        let mut found = ~[];
+        let mut queue = ~[start];
+        let mut next = 0u;
+        while next < queue.len() {
+            let node = &self.nodes[queue[next]];
+            next += 1;
+            let mut supertypes = match node.super_class {
+                Some(index) => ~[index],
+                None => ~[]
+            };
+            supertypes.push_all(node.interfaces);
+            for supertypes.each |index| {
+                if !seen[*index] {
+                    seen[*index] = true;
+                    found.push(*index);
+                    queue.push(*index);
+                }
+            }
+        }
+        self.names(found)
+    }
+
+    //reflexive, and every type is a subtype of java/lang/Object even when its chain ends in a phantom
+    pub fn is_subtype_of(&self, name: &str, supertype: &str) -> bool {
+        if str::eq_slice(name, supertype) || str::eq_slice(supertype, "java/lang/Object") {
+            return true;
+        }
+        for self.all_supertypes(name).each |found| {
+            if str::eq_slice(*found, supertype) {
+                return true;
+            }
+        }
+        false
+    }
+
+    //the non-interface classes implementing the interface, directly, through a subinterface or by inheriting it
+    //from a superclass
+    pub fn implementors(&self, interface: &str) -> ~[&self/str] {
+        let start = match self.find_index(interface) {
+            Some(index) => index,
+            None => return ~[]
+        };
+        let mut seen = vec::from_elem(self.nodes.len(), false);
+        seen[start] = true;
+        let mut found = ~[];
+        let mut stack = ~[start];
+        while !stack.is_empty() {
+            let node = &self.nodes[stack.pop()];
+            for [node.implementors, node.subclasses].each |subtypes| {
+                for subtypes.each |index| {
+                    if !seen[*index] {
+                        seen[*index] = true;
+                        if !self.nodes[*index].is_interface() {
+                            found.push(*index);
+                        }
+                        stack.push(*index);
+                    }
+                }
+            }
+        }
+        self.names(found)
+    }
+
+    //the nearest class both extend as the verifier and stack map frames see it, java/lang/Object when either is an
+    //interface and None when the chains only meet past a phantom
+    pub fn common_superclass(&self, first: &str, second: &str) -> Option<&self/str> {
+        let (first_index, second_index) = match (self.find_index(first), self.find_index(second)) {
+            (Some(first_index), Some(second_index)) => (first_index, second_index),
+            _ => return None
+        };
+        if self.nodes[first_index].is_interface() || self.nodes[second_index].is_interface() {
+            return Some("java/lang/Object");
+        }
+        let mut first_chain = vec::from_elem(self.nodes.len(), false);
+        first_chain[first_index] = true;
+        for self.superclasses(first).each |name| {
+            first_chain[self.find_index(*name).get()] = true;
+        }
+        if first_chain[second_index] {
+            return Some(self.nodes[second_index].name);
+        }
+        for self.superclasses(second).each |name| {
+            if first_chain[self.find_index(*name).get()] {
+                return Some(*name);
+            }
+        }
+        None
+    }
+
+    //each cycle through superclass or interface edges once, in the order the edges were followed, a well formed
+    //class set has none
+    pub fn cycles(&self) -> ~[~[&self/str]] {
+        //0 unvisited, 1 on the current path, 2 done
+        let mut states = vec::from_elem(self.nodes.len(), 0u8);
+        let mut path = ~[];
+        let mut cycles = ~[];
+        for uint::range(0, self.nodes.len()) |index| {
+            if states[index] == 0 {
+                self.visit(index, &mut states, &mut path, &mut cycles);
+            }
+        }
+        do cycles.map |cycle| { self.names(*cycle) }
+    }
+
+    fn visit(&self, index: uint, states: &mut ~[u8], path: &mut ~[uint], cycles: &mut ~[~[uint]]) {
+        states[index] = 1;
+        path.push(index);
+        let node = &self.nodes[index];
+        let mut supertypes = match node.super_class {
+            Some(super_index) => ~[super_index],
+            None => ~[]
+        };
+        supertypes.push_all(node.interfaces);
+        for supertypes.each |next| {
+            if states[*next] == 0 {
+                self.visit(*next, states, path, cycles);
+            } else if states[*next] == 1 {
+                let start = vec::position_elem(*path, next).get();
+                cycles.push(vec::from_slice(vec::view(*path, start, path.len())));
+            }
+        }
+        path.pop();
+        states[index] = 2;
+    }
+}
@@ -0,0 +1,260 @@
+//a DEFLATE (RFC 1951) decoder for jar and jimage resources, along with the zlib (RFC 1950) wrapper,
+//decoding is canonical huffman one bit at a time in the style of zlib's puff
+
+pub fn inflate(bytes: &[u8]) -> Result<~[u8], ~str> {
+    let (out, _) = try!(inflate_raw(bytes));
+    Ok(out)
+}
+
+pub fn inflate_zlib(bytes: &[u8]) -> Result<~[u8], ~str> {
+    if bytes.len() < 6 {
+        return Err(~"zlib stream too short");
+    }
+    let cmf = bytes[0] as uint;
+    let flg = bytes[1] as uint;
+    if cmf & 0x0F != 8 || (cmf * 256 + flg) % 31 != 0 {
+        return Err(~"bad zlib header");
+    }
+    if flg & 0x20 != 0 {
+        return Err(~"zlib preset dictionaries are not supported");
+    }
+    let (out, consumed) = try!(inflate_raw(vec::view(bytes, 2, bytes.len())));
+    let pos = 2 + consumed;
+    if pos + 4 > bytes.len() {
+        return Err(~"missing zlib adler-32");
+    }
+    let expected = (bytes[pos] as u32 << 24) | (bytes[pos + 1] as u32 << 16) | (bytes[pos + 2] as u32 << 8) |
+        bytes[pos + 3] as u32;
+    if adler32(out) != expected {
+        return Err(~"zlib adler-32 mismatch");
+    }
+    Ok(out)
+}
+
+//the output and how many input bytes the final block ended in
+fn inflate_raw(bytes: &[u8]) -> Result<(~[u8], uint), ~str> {
+    let reader = BitReader { bytes: bytes, pos: 0, bits: 0, count: 0 };
+    let mut out: ~[u8] = ~[];
+    loop {
+        let last = try!(reader.bits(1)) == 1;
+        match try!(reader.bits(2)) {
+            0 => try!(stored(&reader, &mut out)),
+            1 => try!(codes(&reader, &mut out, &fixed_lengths(), &fixed_distances())),
+            2 => {
+                let (lengths, distances) = try!(dynamic_tables(&reader));
+                try!(codes(&reader, &mut out, &lengths, &distances));
+            },
+            _ => return Err(~"invalid deflate block type")
+        }
+        if last {
+            break;
+        }
+    }
+    Ok((out, reader.pos))
+}
+
+struct BitReader {
+    bytes: &self/[u8],
+    mut pos: uint,
+    mut bits: u32,
+    mut count: uint
+}
+
+impl BitReader {
+    //n bits, least significant first
+    fn bits(&self, n: uint) -> Result<uint, ~str> {
+        while self.count < n {
+            if self.pos >= self.bytes.len() {
+                return Err(~"unexpected end of deflate data");
+            }
+            self.bits |= (self.bytes[self.pos] as u32) << self.count;
+            self.pos += 1;
+            self.count += 8;
+        }
+        let value = self.bits & ((1u32 << n) - 1);
+        self.bits >>= n;
+        self.count -= n;
+        Ok(value as uint)
+    }
+
+    fn decode(&self, huffman: &Huffman) -> Result<uint, ~str> {
+        let mut code = 0u;
+        let mut first = 0u;
+        let mut index = 0u;
+        for uint::range(1, 16) |len| {
+            code |= try!(self.bits(1));
+            let count = huffman.counts[len] as uint;
+            if code < first + count {
+                return Ok(huffman.symbols[index + code - first] as uint);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(~"invalid huffman code")
+    }
+}
+
+struct Huffman {
+    //number of codes of each length, 0 through 15
+    counts: ~[u16],
+    //symbols ordered by code
+    symbols: ~[u16]
+}
+
+fn Huffman(lengths: &[u8]) -> Result<Huffman, ~str> {
+    let mut counts = vec::from_elem(16, 0u16);
+    for lengths.each |len| {
+        counts[*len] += 1;
+    }
+    let mut left = 1i;
+    for uint::range(1, 16) |len| {
+        left = (left << 1) - counts[len] as int;
+        if left < 0 {
+            return Err(~"over-subscribed huffman code");
+        }
+    }
+    let mut offsets = vec::from_elem(16, 0u16);
+    for uint::range(1, 15) |len| {
+        offsets[len + 1] = offsets[len] + counts[len];
+    }
+    let mut symbols = vec::from_elem(lengths.len(), 0u16);
+    for lengths.eachi |symbol, len| {
+        if *len != 0 {
+            symbols[offsets[*len]] = symbol as u16;
+            offsets[*len] += 1;
+        }
+    }
+    Ok(Huffman { counts: counts, symbols: symbols })
+}
+
+const LENGTH_BASE: [uint * 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99,
+    115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [uint * 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5,
+    0];
+const DISTANCE_BASE: [uint * 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769,
+    1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DISTANCE_EXTRA: [uint * 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11,
+    12, 12, 13, 13];
+//order the code length code lengths are sent in
+const CODE_LENGTH_ORDER: [uint * 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+fn stored(reader: &BitReader, out: &mut ~[u8]) -> Result<(), ~str> {
+    //the rest of the current byte is padding
+    reader.bits = 0;
+    reader.count = 0;
+    let pos = reader.pos;
+    if pos + 4 > reader.bytes.len() {
+        return Err(~"unexpected end of deflate data");
+    }
+    let len = reader.bytes[pos] as uint | (reader.bytes[pos + 1] as uint << 8);
+    let nlen = reader.bytes[pos + 2] as uint | (reader.bytes[pos + 3] as uint << 8);
+    if len != !nlen & 0xFFFF {
+        return Err(~"stored block length mismatch");
+    }
+    if pos + 4 + len > reader.bytes.len() {
+        return Err(~"unexpected end of deflate data");
+    }
+    out.push_all(vec::view(reader.bytes, pos + 4, pos + 4 + len));
+    reader.pos = pos + 4 + len;
+    Ok(())
+}
+
+fn codes(reader: &BitReader, out: &mut ~[u8], lengths: &Huffman, distances: &Huffman) -> Result<(), ~str> {
+    loop {
+        let symbol = try!(reader.decode(lengths));
+        if symbol < 256 {
+            out.push(symbol as u8);
+        } else if symbol == 256 {
+            return Ok(());
+        } else {
+            let symbol = symbol - 257;
+            if symbol >= 29 {
+                return Err(~"invalid length symbol");
+            }
+            let length = LENGTH_BASE[symbol] + try!(reader.bits(LENGTH_EXTRA[symbol]));
+            let symbol = try!(reader.decode(distances));
+            if symbol >= 30 {
+                return Err(~"invalid distance symbol");
+            }
+            let distance = DISTANCE_BASE[symbol] + try!(reader.bits(DISTANCE_EXTRA[symbol]));
+            if distance > out.len() {
+                return Err(~"distance too far back");
+            }
+            let start = out.len() - distance;
+            for uint::range(0, length) |i| {
+                let b = out[start + i];
+                out.push(b);
+            }
+        }
+    }
+}
+
+fn fixed_lengths() -> Huffman {
+    let mut lengths = vec::from_elem(288, 8u8);
+    for uint::range(144, 256) |symbol| { lengths[symbol] = 9; }
+    for uint::range(256, 280) |symbol| { lengths[symbol] = 7; }
+    result::unwrap(Huffman(lengths))
+}
+
+fn fixed_distances() -> Huffman {
+    result::unwrap(Huffman(vec::from_elem(30, 5u8)))
+}
+
+fn dynamic_tables(reader: &BitReader) -> Result<(Huffman, Huffman), ~str> {
+    let literal_count = try!(reader.bits(5)) + 257;
+    let distance_count = try!(reader.bits(5)) + 1;
+    let code_length_count = try!(reader.bits(4)) + 4;
+    if literal_count > 286 || distance_count > 30 {
+        return Err(~"too many length or distance codes");
+    }
+    let mut code_lengths = vec::from_elem(19, 0u8);
+    for uint::range(0, code_length_count) |i| {
+        code_lengths[CODE_LENGTH_ORDER[i]] = try!(reader.bits(3)) as u8;
+    }
+    let code_length_code = try!(Huffman(code_lengths));
+    let total = literal_count + distance_count;
+    let mut lengths = vec::from_elem(total, 0u8);
+    let mut index = 0u;
+    while index < total {
+        let symbol = try!(reader.decode(&code_length_code));
+        if symbol < 16 {
+            lengths[index] = symbol as u8;
+            index += 1;
+            loop;
+        }
+        let (value, repeat) = match symbol {
+            16 => {
+                if index == 0 {
+                    return Err(~"repeat with no previous length");
+                }
+                (lengths[index - 1], 3 + try!(reader.bits(2)))
+            },
+            17 => (0u8, 3 + try!(reader.bits(3))),
+            _ => (0u8, 11 + try!(reader.bits(7)))
+        };
+        if index + repeat > total {
+            return Err(~"too many code lengths");
+        }
+        for iter::repeat(repeat) {
+            lengths[index] = value;
+            index += 1;
+        }
+    }
+    if lengths[256] == 0 {
+        return Err(~"missing end-of-block code");
+    }
+    let literal_code = try!(Huffman(vec::view(lengths, 0, literal_count)));
+    let distance_code = try!(Huffman(vec::view(lengths, literal_count, total)));
+    Ok((literal_code, distance_code))
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let mut a = 1u32;
+    let mut b = 0u32;
+    for bytes.each |byte| {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
@@ -0,0 +1,509 @@
+use class_file::{ClassFile, ClassFormatError};
+
+//JDK runtime images (lib/modules), read whole into memory. Header fields, the tables and resource headers are in
+//the image's byte order, location attribute values are always big endian and strings are NUL terminated
+//modified utf-8. Classes are named /module/parent/base.extension, e.g. /java.base/java/lang/Object.class
+
+pub struct JImage {
+    bytes: ~[u8],
+    big_endian: bool,
+    major_version: u16,
+    minor_version: u16,
+    flags: u32,
+    resource_count: u32,
+    table_length: u32,
+    locations_size: u32,
+    strings_size: u32
+}
+
+pub struct ImageLocation {
+    module: ~str,
+    parent: ~str,
+    base: ~str,
+    extension: ~str,
+    //from the end of the index
+    offset: u64,
+    //0 when the resource is stored uncompressed
+    compressed_size: u64,
+    uncompressed_size: u64
+}
+
+pub enum JImageError {
+    JImageError_Io(~str /*reason*/),
+    JImageError_Invalid(uint /*offset*/, ~str /*reason*/),
+    JImageError_Corrupt(~str /*name*/, ~str /*reason*/),
+    JImageError_Class(~str /*name*/, ClassFormatError)
+}
+
+impl JImageError {
+    pub fn to_str(&self) -> ~str {
+        match *self {
+            JImageError_Io(ref reason) => copy *reason,
+            JImageError_Invalid(offset, ref reason) => fmt!("invalid jimage at offset %u: %s", offset, *reason),
+            JImageError_Corrupt(ref name, ref reason) => fmt!("%s: %s", *name, *reason),
+            JImageError_Class(ref name, ref err) => fmt!("%s: %s", *name, err.to_str())
+        }
+    }
+}
+
+const IMAGE_MAGIC: u32 = 0xCAFEDADA;
+const HEADER_SIZE: uint = 28;
+const HASH_MULTIPLIER: u32 = 0x01000193;
+
+const ATTRIBUTE_END: uint = 0;
+const ATTRIBUTE_MODULE: uint = 1;
+const ATTRIBUTE_PARENT: uint = 2;
+const ATTRIBUTE_BASE: uint = 3;
+const ATTRIBUTE_EXTENSION: uint = 4;
+const ATTRIBUTE_OFFSET: uint = 5;
+const ATTRIBUTE_COMPRESSED: uint = 6;
+const ATTRIBUTE_UNCOMPRESSED: uint = 7;
+const ATTRIBUTE_COUNT: uint = 8;
+
+const COMPRESSED_MAGIC: u32 = 0xCAFEFAFA;
+const COMPRESSED_HEADER_SIZE: uint = 29;
+
+impl ImageLocation {
+    pub fn full_name(&self) -> ~str {
+        let mut name = ~"";
+        if !self.module.is_empty() {
+            str::push_str(&mut name, fmt!("/%s/", self.module));
+        }
+        if !self.parent.is_empty() {
+            str::push_str(&mut name, fmt!("%s/", self.parent));
+        }
+        str::push_str(&mut name, self.base);
+        if !self.extension.is_empty() {
+            str::push_str(&mut name, fmt!(".%s", self.extension));
+        }
+        name
+    }
+}
+
+impl JImage {
+    //where resources start
+    pub fn index_size(&self) -> uint {
+        HEADER_SIZE + self.table_length as uint * 8 + self.locations_size as uint + self.strings_size as uint
+    }
+
+    //by full name, e.g. /java.base/java/lang/Object.class
+    pub fn find(&self, name: &str) -> Result<Option<ImageLocation>, JImageError> {
+        let length = self.table_length;
+        if length == 0 {
+            return Ok(None);
+        }
+        let mut index = hash_code(name, HASH_MULTIPLIER) % length;
+        let value = self.redirect(index);
+        if value < 0 {
+            //a direct index, for names with no collisions
+            index = (-1 - value) as u32;
+        } else if value > 0 {
+            //the seed that separates this name from the others in its bucket
+            index = hash_code(name, value as u32) % length;
+        } else {
+            return Ok(None);
+        }
+        if index >= length {
+            return Err(JImageError_Invalid(HEADER_SIZE, ~"redirect past the end of the table"));
+        }
+        let location = try!(self.location_at(self.location_offset(index)));
+        //a hash match on a name that is not in the image lands on some other location
+        if str::eq_slice(location.full_name(), name) { Ok(Some(location)) } else { Ok(None) }
+    }
+
+    pub fn each_location(&self, f: fn(&ImageLocation) -> bool) -> Result<(), JImageError> {
+        for uint::range(0, self.table_length as uint) |index| {
+            let location = try!(self.location_at(self.location_offset(index as u32)));
+            if !f(&location) {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    //uncompressed bytes of a resource
+    pub fn read(&self, location: &ImageLocation) -> Result<~[u8], JImageError> {
+        let index_size = self.index_size();
+        //the sizes are compared as u64 before being added so a corrupt location cannot overflow them
+        if index_size > self.bytes.len() || location.offset > (self.bytes.len() - index_size) as u64 {
+            return Err(JImageError_Invalid(index_size, ~"resource past the end of the image"));
+        }
+        let start = index_size + location.offset as uint;
+        let stored_size = if location.compressed_size == 0 { location.uncompressed_size } else {
+            location.compressed_size
+        };
+        if stored_size > (self.bytes.len() - start) as u64 {
+            return Err(JImageError_Invalid(start, ~"resource past the end of the image"));
+        }
+        let end = start + stored_size as uint;
+        let mut bytes = vec::from_slice(vec::view(self.bytes, start, end));
+        //only the location says whether a resource is compressed, an uncompressed one may start with anything.
+        //Compression plugins can be stacked and, as in the JDK's own reader, the layers after the first are
+        //recognized by the header each leaves in front of its output
+        if location.compressed_size != 0 {
+            loop {
+                bytes = try!(self.decompress(location, bytes));
+                if bytes.len() < COMPRESSED_HEADER_SIZE || self.u32_in(bytes, 0) != COMPRESSED_MAGIC {
+                    break;
+                }
+            }
+        }
+        if bytes.len() as u64 != location.uncompressed_size {
+            return Err(JImageError_Corrupt(location.full_name(), ~"uncompressed size mismatch"));
+        }
+        Ok(bytes)
+    }
+
+    //the modules with classes in a package, from the /packages/<package.name> resource
+    pub fn package_modules(&self, package: &str) -> Result<~[~str], JImageError> {
+        let mut modules: ~[~str] = ~[];
+        let location = match try!(self.find(~"/packages/" + str::replace(package, "/", "."))) {
+            Some(location) => location,
+            None => return Ok(modules)
+        };
+        //(is_empty, module name offset) pairs
+        let content = try!(self.read(&location));
+        for uint::range(0, content.len() / 8) |i| {
+            if self.u32_in(content, i * 8) == 0 {
+                modules.push(try!(self.get_string(self.u32_in(content, i * 8 + 4))));
+            }
+        }
+        Ok(modules)
+    }
+
+    //by internal name, e.g. java/lang/Object
+    pub fn find_class(&self, internal_name: &str) -> Result<Option<ImageLocation>, JImageError> {
+        //everything in an image is in a named module so never in the unnamed package
+        let package = match str::rfind_char(internal_name, '/') {
+            Some(slash) => str::slice(internal_name, 0, slash),
+            None => return Ok(None)
+        };
+        for try!(self.package_modules(package)).each |module| {
+            match try!(self.find(fmt!("/%s/%s.class", *module, internal_name))) {
+                Some(location) => return Ok(Some(location)),
+                None => ()
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn read_class(&self, internal_name: &str) -> Result<Option<ClassFile>, JImageError> {
+        let location = match try!(self.find_class(internal_name)) {
+            Some(location) => location,
+            None => return Ok(None)
+        };
+        let bytes = try!(self.read(&location));
+        match io::with_bytes_reader(bytes, |reader| class_file::ClassFile(reader)) {
+            Ok(class_file) => Ok(Some(class_file)),
+            Err(err) => Err(JImageError_Class(location.full_name(), err))
+        }
+    }
+
+    fn redirect(&self, index: u32) -> i32 {
+        self.u32_in(self.bytes, HEADER_SIZE + index as uint * 4) as i32
+    }
+
+    fn location_offset(&self, index: u32) -> u32 {
+        self.u32_in(self.bytes, HEADER_SIZE + self.table_length as uint * 4 + index as uint * 4)
+    }
+
+    fn location_at(&self, offset: u32) -> Result<ImageLocation, JImageError> {
+        let locations = HEADER_SIZE + self.table_length as uint * 8;
+        let end = locations + self.locations_size as uint;
+        let mut pos = locations + offset as uint;
+        let mut attributes = vec::from_elem(ATTRIBUTE_COUNT, 0u64);
+        loop {
+            if pos >= end {
+                return Err(JImageError_Invalid(pos, ~"unterminated location"));
+            }
+            //kind in the top five bits, value length less one in the bottom three
+            let kind = (self.bytes[pos] >> 3) as uint;
+            if kind == ATTRIBUTE_END {
+                break;
+            }
+            if kind >= ATTRIBUTE_COUNT {
+                return Err(JImageError_Invalid(pos, fmt!("unknown location attribute %u", kind)));
+            }
+            let length = (self.bytes[pos] & 7) as uint + 1;
+            if pos + 1 + length > end {
+                return Err(JImageError_Invalid(pos, ~"location attribute past the end of the locations"));
+            }
+            let mut value = 0u64;
+            for uint::range(0, length) |i| {
+                value = (value << 8) | self.bytes[pos + 1 + i] as u64;
+            }
+            attributes[kind] = value;
+            pos += 1 + length;
+        }
+        Ok(ImageLocation {
+            module: try!(self.get_string(attributes[ATTRIBUTE_MODULE] as u32)),
+            parent: try!(self.get_string(attributes[ATTRIBUTE_PARENT] as u32)),
+            base: try!(self.get_string(attributes[ATTRIBUTE_BASE] as u32)),
+            extension: try!(self.get_string(attributes[ATTRIBUTE_EXTENSION] as u32)),
+            offset: attributes[ATTRIBUTE_OFFSET],
+            compressed_size: attributes[ATTRIBUTE_COMPRESSED],
+            uncompressed_size: attributes[ATTRIBUTE_UNCOMPRESSED]
+        })
+    }
+
+    //the raw modified utf-8 of a string table entry
+    fn get_string_bytes(&self, offset: u32) -> Result<&self/[u8], JImageError> {
+        let strings = HEADER_SIZE + self.table_length as uint * 8 + self.locations_size as uint;
+        let end = strings + self.strings_size as uint;
+        let start = strings + offset as uint;
+        let mut pos = start;
+        while pos < end && self.bytes[pos] != 0 {
+            pos += 1;
+        }
+        if pos >= end {
+            return Err(JImageError_Invalid(start, ~"unterminated string"));
+        }
+        Ok(vec::view(self.bytes, start, pos))
+    }
+
+    fn get_string(&self, offset: u32) -> Result<~str, JImageError> {
+        match mutf8::decode(try!(self.get_string_bytes(offset))) {
+            Some(strval) => Ok(strval),
+            None => Err(JImageError_Invalid(offset as uint, ~"bad modified utf-8 in string"))
+        }
+    }
+
+    //strips one compressed resource header and undoes its plugin
+    fn decompress(&self, location: &ImageLocation, bytes: &[u8]) -> Result<~[u8], JImageError> {
+        if bytes.len() < COMPRESSED_HEADER_SIZE || self.u32_in(bytes, 0) != COMPRESSED_MAGIC {
+            return Err(JImageError_Corrupt(location.full_name(), ~"missing compressed resource header"));
+        }
+        let compressed_size = self.u64_in(bytes, 4);
+        let uncompressed_size = self.u64_in(bytes, 12);
+        let decompressor = try!(self.get_string(self.u32_in(bytes, 20)));
+        //the plugin config at 24 and the is_terminal byte at 28 are not needed to decompress
+        if compressed_size > (bytes.len() - COMPRESSED_HEADER_SIZE) as u64 {
+            return Err(JImageError_Corrupt(location.full_name(), ~"compressed resource truncated"));
+        }
+        let compressed_size = compressed_size as uint;
+        let content = vec::view(bytes, COMPRESSED_HEADER_SIZE, COMPRESSED_HEADER_SIZE + compressed_size);
+        let decompressed = if decompressor == ~"zip" {
+            inflate::inflate_zlib(content)
+        } else if decompressor == ~"compact-cp" {
+            self.expand_shared_strings(content)
+        } else {
+            Err(fmt!("unsupported decompressor %s", decompressor))
+        };
+        match decompressed {
+            Ok(decompressed) => if decompressed.len() as u64 == uncompressed_size {
+                Ok(decompressed)
+            } else {
+                Err(JImageError_Corrupt(location.full_name(), decompressor + ~" size mismatch"))
+            },
+            Err(reason) => Err(JImageError_Corrupt(location.full_name(), reason))
+        }
+    }
+
+    //compact-cp replaces Utf8 constants with string table indexes, descriptors are split into the descriptor
+    //with bare L; and the package and class name of each referenced class
+    fn expand_shared_strings(&self, bytes: &[u8]) -> Result<~[u8], ~str> {
+        //magic, versions and constant_pool_count are left as is
+        if bytes.len() < 10 {
+            return Err(~"compact-cp class truncated");
+        }
+        let mut out = vec::from_slice(vec::view(bytes, 0, 10));
+        let count = (bytes[8] as uint << 8) | bytes[9] as uint;
+        let mut pos = 10u;
+        let mut index = 1u;
+        while index < count {
+            if pos >= bytes.len() {
+                return Err(~"compact-cp constant pool truncated");
+            }
+            let tag = bytes[pos];
+            pos += 1;
+            let size = match tag {
+                //constant pool tags compact-cp adds for strings moved into the image string table
+                23 /*externalized string*/ => {
+                    let (string_index, next) = try!(read_compressed_int(bytes, pos));
+                    pos = next;
+                    let strval = vec::from_slice(try!(self.shared_string(string_index)));
+                    push_utf8(&mut out, strval);
+                    0
+                },
+                25 /*externalized descriptor*/ => {
+                    let (descriptor_index, next) = try!(read_compressed_int(bytes, pos));
+                    let (flow_length, next) = try!(read_compressed_int(bytes, next));
+                    if next + flow_length > bytes.len() {
+                        return Err(~"compact-cp descriptor indexes truncated");
+                    }
+                    let indexes = try!(read_compressed_flow(vec::view(bytes, next, next + flow_length)));
+                    pos = next + flow_length;
+                    let descriptor = try!(self.shared_string(descriptor_index));
+                    let mut strval: ~[u8] = ~[];
+                    let mut i = 0u;
+                    for descriptor.each |b| {
+                        strval.push(*b);
+                        if *b == 'L' as u8 {
+                            if i + 2 > indexes.len() {
+                                return Err(~"compact-cp descriptor index missing");
+                            }
+                            let package = try!(self.shared_string(indexes[i]));
+                            if !package.is_empty() {
+                                strval.push_all(package);
+                                strval.push('/' as u8);
+                            }
+                            strval.push_all(try!(self.shared_string(indexes[i + 1])));
+                            i += 2;
+                        }
+                    }
+                    push_utf8(&mut out, strval);
+                    0
+                },
+                1 => {
+                    if pos + 2 > bytes.len() {
+                        return Err(~"compact-cp constant pool truncated");
+                    }
+                    2 + ((bytes[pos] as uint << 8) | bytes[pos + 1] as uint)
+                },
+                5 | 6 => {
+                    //takes two slots
+                    index += 1;
+                    8
+                },
+                7 | 8 | 16 | 19 | 20 => 2,
+                15 => 3,
+                3 | 4 | 9 | 10 | 11 | 12 | 17 | 18 => 4,
+                _ => return Err(fmt!("unexpected constant pool tag %u", tag as uint))
+            };
+            if size != 0 {
+                if pos + size > bytes.len() {
+                    return Err(~"compact-cp constant pool truncated");
+                }
+                out.push(tag);
+                out.push_all(vec::view(bytes, pos, pos + size));
+                pos += size;
+            }
+            index += 1;
+        }
+        //the rest of the class is untouched
+        out.push_all(vec::view(bytes, pos, bytes.len()));
+        Ok(out)
+    }
+
+    fn shared_string(&self, offset: uint) -> Result<&self/[u8], ~str> {
+        match self.get_string_bytes(offset as u32) {
+            Ok(bytes) => Ok(bytes),
+            Err(err) => Err(err.to_str())
+        }
+    }
+
+    fn u32_in(&self, bytes: &[u8], pos: uint) -> u32 {
+        if self.big_endian {
+            (bytes[pos] as u32 << 24) | (bytes[pos + 1] as u32 << 16) | (bytes[pos + 2] as u32 << 8) |
+                bytes[pos + 3] as u32
+        } else {
+            bytes[pos] as u32 | (bytes[pos + 1] as u32 << 8) | (bytes[pos + 2] as u32 << 16) |
+                (bytes[pos + 3] as u32 << 24)
+        }
+    }
+
+    fn u64_in(&self, bytes: &[u8], pos: uint) -> u64 {
+        let first = self.u32_in(bytes, pos) as u64;
+        let second = self.u32_in(bytes, pos + 4) as u64;
+        if self.big_endian { (first << 32) | second } else { (second << 32) | first }
+    }
+}
+
+pub fn open_jimage(path: &Path) -> Result<JImage, JImageError> {
+    match io::read_whole_file(path) {
+        Ok(bytes) => JImage(bytes),
+        Err(reason) => Err(JImageError_Io(reason))
+    }
+}
+
+pub fn JImage(bytes: ~[u8]) -> Result<JImage, JImageError> {
+    if bytes.len() < HEADER_SIZE {
+        return Err(JImageError_Invalid(0, ~"too short for a jimage header"));
+    }
+    //the magic tells the byte order the image was written in
+    let little = bytes[0] as u32 | (bytes[1] as u32 << 8) | (bytes[2] as u32 << 16) | (bytes[3] as u32 << 24);
+    let big = (bytes[0] as u32 << 24) | (bytes[1] as u32 << 16) | (bytes[2] as u32 << 8) | bytes[3] as u32;
+    let big_endian = if little == IMAGE_MAGIC {
+        false
+    } else if big == IMAGE_MAGIC {
+        true
+    } else {
+        return Err(JImageError_Invalid(0, fmt!("bad magic 0x%x", little as uint)));
+    };
+    let mut image = JImage {
+        bytes: ~[],
+        big_endian: big_endian,
+        major_version: 0,
+        minor_version: 0,
+        flags: 0,
+        resource_count: 0,
+        table_length: 0,
+        locations_size: 0,
+        strings_size: 0
+    };
+    let version = image.u32_in(bytes, 4);
+    image.major_version = (version >> 16) as u16;
+    image.minor_version = (version & 0xFFFF) as u16;
+    image.flags = image.u32_in(bytes, 8);
+    image.resource_count = image.u32_in(bytes, 12);
+    image.table_length = image.u32_in(bytes, 16);
+    image.locations_size = image.u32_in(bytes, 20);
+    image.strings_size = image.u32_in(bytes, 24);
+    if image.major_version != 1 {
+        return Err(JImageError_Invalid(4, fmt!("unsupported jimage version %u.%u", image.major_version as uint,
+            image.minor_version as uint)));
+    }
+    if image.index_size() > bytes.len() {
+        return Err(JImageError_Invalid(HEADER_SIZE, ~"index past the end of the image"));
+    }
+    image.bytes = bytes;
+    Ok(image)
+}
+
+//ImageStringsReader.hashCode over the modified utf-8 of the name
+fn hash_code(name: &str, seed: u32) -> u32 {
+    let mut hash = seed;
+    for mutf8::encode(name).each |b| {
+        hash = (hash * HASH_MULTIPLIER) ^ *b as u32;
+    }
+    hash & 0x7FFFFFFF
+}
+
+//CompressIndexes: a set top bit means the next two bits hold the byte length and the low five bits the high bits
+//of the value, otherwise the value is a plain big endian u32
+fn read_compressed_int(bytes: &[u8], pos: uint) -> Result<(uint, uint), ~str> {
+    if pos >= bytes.len() {
+        return Err(~"compressed index truncated");
+    }
+    let header = bytes[pos];
+    let (size, mut value) = if header & 0x80 != 0 {
+        (((header >> 5) & 3) as uint, (header & 0x1F) as uint)
+    } else {
+        (4u, header as uint)
+    };
+    if size == 0 || pos + size > bytes.len() {
+        return Err(~"compressed index truncated");
+    }
+    for uint::range(1, size) |i| {
+        value = (value << 8) | bytes[pos + i] as uint;
+    }
+    Ok((value, pos + size))
+}
+
+fn read_compressed_flow(bytes: &[u8]) -> Result<~[uint], ~str> {
+    let mut values: ~[uint] = ~[];
+    let mut pos = 0u;
+    while pos < bytes.len() {
+        let (value, next) = try!(read_compressed_int(bytes, pos));
+        values.push(value);
+        pos = next;
+    }
+    Ok(values)
+}
+
+fn push_utf8(out: &mut ~[u8], strval: &[u8]) {
+    out.push(1);
+    out.push((strval.len() >> 8) as u8);
+    out.push(strval.len() as u8);
+    out.push_all(strval);
+}
@@ -0,0 +1,102 @@
+use class_file::ClassFile;
+use archive::{Archive, ArchiveEntry, ArchiveError, ArchiveError_Invalid};
+
+//.jmod files are a JM header (magic and a major and minor version byte) followed by a zip, whose offsets are
+//relative to the start of the zip, with entries grouped into sections by their top directory
+
+pub struct JMod {
+    major_version: u8,
+    minor_version: u8,
+    archive: Archive
+}
+
+pub enum JModSection {
+    JModSection_Classes,
+    JModSection_Config,
+    JModSection_HeaderFiles,
+    JModSection_LegalNotices,
+    JModSection_NativeCommands,
+    JModSection_NativeLibraries,
+    JModSection_ManPages
+}
+
+impl JModSection {
+    pub fn prefix(&self) -> &static/str {
+        match *self {
+            JModSection_Classes => "classes/",
+            JModSection_Config => "conf/",
+            JModSection_HeaderFiles => "include/",
+            JModSection_LegalNotices => "legal/",
+            JModSection_NativeCommands => "bin/",
+            JModSection_NativeLibraries => "lib/",
+            JModSection_ManPages => "man/"
+        }
+    }
+}
+
+impl JMod {
+    //names within the section, e.g. java/lang/Object.class for classes/java/lang/Object.class
+    pub fn each_entry(&self, section: JModSection, f: fn(&str, &ArchiveEntry) -> bool) {
+        let prefix = section.prefix();
+        for self.archive.entries.each |entry| {
+            if entry.name.starts_with(prefix) && entry.name.len() > prefix.len() {
+                if !f(str::slice(entry.name, prefix.len(), entry.name.len()), entry) {
+                    break;
+                }
+            }
+        }
+    }
+
+    pub fn find(&self, section: JModSection, name: &str) -> Option<&self/ArchiveEntry> {
+        self.archive.find(section.prefix() + name)
+    }
+
+    pub fn read(&self, section: JModSection, name: &str) -> Result<Option<~[u8]>, ArchiveError> {
+        match self.find(section, name) {
+            Some(entry) => match self.archive.read(entry) {
+                Ok(bytes) => Ok(Some(bytes)),
+                Err(err) => Err(err)
+            },
+            None => Ok(None)
+        }
+    }
+
+    pub fn read_class(&self, internal_name: &str) -> Result<Option<ClassFile>, ArchiveError> {
+        match self.find(JModSection_Classes, internal_name + ~".class") {
+            Some(entry) => match self.archive.read_class(entry) {
+                Ok(class_file) => Ok(Some(class_file)),
+                Err(err) => Err(err)
+            },
+            None => Ok(None)
+        }
+    }
+
+    //its Module attribute is reachable through ClassFile::module
+    pub fn module_info(&self) -> Result<Option<ClassFile>, ArchiveError> {
+        self.read_class("module-info")
+    }
+}
+
+pub fn open_jmod(path: &Path) -> Result<JMod, ArchiveError> {
+    match io::read_whole_file(path) {
+        Ok(bytes) => JMod(bytes),
+        Err(reason) => Err(archive::ArchiveError_Io(reason))
+    }
+}
+
+pub fn JMod(bytes: ~[u8]) -> Result<JMod, ArchiveError> {
+    if bytes.len() < 4 || bytes[0] != 'J' as u8 || bytes[1] != 'M' as u8 {
+        return Err(ArchiveError_Invalid(0, ~"bad jmod magic"));
+    }
+    if bytes[2] != 1 {
+        return Err(ArchiveError_Invalid(2, fmt!("unsupported jmod version %u.%u", bytes[2] as uint,
+            bytes[3] as uint)));
+    }
+    let archive = match archive::Archive(vec::from_slice(vec::view(bytes, 4, bytes.len()))) {
+        Ok(archive) => archive,
+        //offsets in archive errors are relative to the embedded zip
+        Err(ArchiveError_Invalid(offset, reason)) => return Err(ArchiveError_Invalid(offset + 4, reason)),
+        Err(err) => return Err(err)
+    };
+    Ok(JMod { major_version: bytes[2], minor_version: bytes[3], archive: archive })
+}
@@ -1,4 +1,3 @@
-
 fn main() {
     if os::args().len() == 1 {
         fail ~"Filename required";
@@ -8,5 +7,8 @@ fn main() {
         fail result::get_err(&res);
     }
     let fread = result::unwrap(res);
-    class_file::ClassFile(fread as io::ReaderUtil);
+    match class_file::ClassFile(fread) {
+        Ok(class_file) => disassembler::print_class_file(&class_file, io::stdout()),
+        Err(err) => fail fmt!("Invalid class file: %s", err.to_str())
+    }
 }
@@ -0,0 +1,92 @@
+//JVM "modified UTF-8" (JVMS 4.4.7): NUL is encoded as C0 80 and characters
+//outside the BMP are encoded as a surrogate pair of 3-byte sequences
+
+pub fn decode(bytes: &[u8]) -> Option<~str> {
+    let mut strval = ~"";
+    str::reserve(&mut strval, bytes.len());
+    let mut i = 0u;
+    while i < bytes.len() {
+        let b = bytes[i];
+        if b == 0 || b >= 0xF0 {
+            return None;
+        } else if b < 0x80 {
+            str::push_char(&mut strval, b as char);
+            i += 1;
+        } else if b & 0xE0 == 0xC0 {
+            if i + 1 >= bytes.len() || !is_continuation(bytes[i + 1]) {
+                return None;
+            }
+            let c = ((b as u32 & 0x1F) << 6) | (bytes[i + 1] as u32 & 0x3F);
+            str::push_char(&mut strval, c as char);
+            i += 2;
+        } else if b & 0xF0 == 0xE0 {
+            let unit = match decode_three(bytes, i) {
+                Some(unit) => unit,
+                None => return None
+            };
+            i += 3;
+            if is_high_surrogate(unit) {
+                match decode_three(bytes, i) {
+                    Some(low) if is_low_surrogate(low) => {
+                        let c = 0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00);
+                        str::push_char(&mut strval, c as char);
+                        i += 3;
+                    },
+                    _ => str::push_char(&mut strval, REPLACEMENT_CHAR)
+                }
+            } else if is_low_surrogate(unit) {
+                //unpaired, the raw bytes are still kept by the caller
+                str::push_char(&mut strval, REPLACEMENT_CHAR);
+            } else {
+                str::push_char(&mut strval, unit as char);
+            }
+        } else {
+            return None;
+        }
+    }
+    Some(strval)
+}
+
+pub fn encode(strval: &str) -> ~[u8] {
+    let mut bytes: ~[u8] = ~[];
+    vec::reserve(&mut bytes, strval.len());
+    for str::each_char(strval) |c| {
+        let code = c as u32;
+        if code == 0 {
+            bytes.push(0xC0);
+            bytes.push(0x80);
+        } else if code < 0x80 {
+            bytes.push(code as u8);
+        } else if code < 0x800 {
+            bytes.push((0xC0 | (code >> 6)) as u8);
+            bytes.push((0x80 | (code & 0x3F)) as u8);
+        } else if code < 0x10000 {
+            encode_three(&mut bytes, code);
+        } else {
+            let offset = code - 0x10000;
+            encode_three(&mut bytes, 0xD800 + (offset >> 10));
+            encode_three(&mut bytes, 0xDC00 + (offset & 0x3FF));
+        }
+    }
+    bytes
+}
+
+const REPLACEMENT_CHAR: char = '\uFFFD';
+
+fn is_continuation(b: u8) -> bool { b & 0xC0 == 0x80 }
+fn is_high_surrogate(unit: u32) -> bool { unit >= 0xD800 && unit <= 0xDBFF }
+fn is_low_surrogate(unit: u32) -> bool { unit >= 0xDC00 && unit <= 0xDFFF }
+
+fn decode_three(bytes: &[u8], i: uint) -> Option<u32> {
+    if i + 2 >= bytes.len() || bytes[i] & 0xF0 != 0xE0 ||
+            !is_continuation(bytes[i + 1]) || !is_continuation(bytes[i + 2]) {
+        return None;
+    }
+    Some(((bytes[i] as u32 & 0x0F) << 12) | ((bytes[i + 1] as u32 & 0x3F) << 6) | (bytes[i + 2] as u32 & 0x3F))
+}
+
+fn encode_three(bytes: &mut ~[u8], unit: u32) {
+    bytes.push((0xE0 | (unit >> 12)) as u8);
+    bytes.push((0x80 | ((unit >> 6) & 0x3F)) as u8);
+    bytes.push((0x80 | (unit & 0x3F)) as u8);
+}
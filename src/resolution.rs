@@ -0,0 +1,377 @@
+use class_file::{ClassFile, ConstantPool, FieldInfo, MethodInfo, MemberRef_Field, MemberRef_Method,
+    MemberRef_InterfaceMethod, ClassAccess_Interface, MethodAccess_Private, MethodAccess_Static, MethodAccess_Public,
+    MethodAccess_Abstract, MethodAccess_VarArgs, MethodAccess_Native};
+use class_path::{ClassPath, LoadError};
+use hierarchy::ClassHierarchy;
+use core::send_map::linear::LinearMap;
+
+//field and method resolution of JVMS 5.4.3.2 through 5.4.3.4 over the classes of a class path, access checks
+//(5.4.4) and loader constraints are not applied
+
+pub enum ResolutionError {
+    ResolutionError_Load(LoadError),
+    ResolutionError_NoClassDefFound(~str /*class*/),
+    ResolutionError_IncompatibleClassChange(~str /*reason*/),
+    ResolutionError_NoSuchField(~str /*field*/),
+    ResolutionError_NoSuchMethod(~str /*method*/),
+    //a class whose this_class, super_class or interfaces are not class constants
+    ResolutionError_ClassFormat(~str /*reason*/),
+    //a class that is its own superclass or superinterface
+    ResolutionError_ClassCircularity(~str /*class*/)
+}
+
+impl ResolutionError {
+    //in the style of the java.lang errors resolution would throw
+    pub fn to_str(&self) -> ~str {
+        match *self {
+            ResolutionError_Load(ref err) => err.to_str(),
+            ResolutionError_NoClassDefFound(ref class) => fmt!("NoClassDefFoundError: %s", *class),
+            ResolutionError_IncompatibleClassChange(ref reason) => fmt!("IncompatibleClassChangeError: %s", *reason),
+            ResolutionError_NoSuchField(ref field) => fmt!("NoSuchFieldError: %s", *field),
+            ResolutionError_NoSuchMethod(ref method) => fmt!("NoSuchMethodError: %s", *method),
+            ResolutionError_ClassFormat(ref reason) => fmt!("ClassFormatError: %s", *reason),
+            ResolutionError_ClassCircularity(ref class) => fmt!("ClassCircularityError: %s", *class)
+        }
+    }
+}
+
+pub struct ResolvedField {
+    //the class or interface declaring the field, not necessarily the one it was resolved in
+    class_file: @ClassFile,
+    index: uint
+}
+
+impl ResolvedField {
+    //always Some for results of resolution, which only loads classes with valid names
+    pub fn declaring_class(&self) -> Option<&self/str> {
+        self.class_file.class_name()
+    }
+
+    pub fn field(&self) -> &self/FieldInfo {
+        &self.class_file.fields[self.index]
+    }
+}
+
+pub struct ResolvedMethod {
+    class_file: @ClassFile,
+    index: uint
+}
+
+impl ResolvedMethod {
+    pub fn declaring_class(&self) -> Option<&self/str> {
+        self.class_file.class_name()
+    }
+
+    pub fn method(&self) -> &self/MethodInfo {
+        &self.class_file.methods[self.index]
+    }
+}
+
+//only classes whose this_class, super_class and interfaces are class constants are returned, so the name
+//accessors below do not fail on them
+fn load(class_path: &ClassPath, name: &str) -> Result<@ClassFile, ResolutionError> {
+    let class_file = match class_path.load(name) {
+        Ok(Some(class_file)) => class_file,
+        Ok(None) => return Err(ResolutionError_NoClassDefFound(str::from_slice(name))),
+        Err(err) => return Err(ResolutionError_Load(err))
+    };
+    if class_file.class_name().is_none() || class_file.interfaces().is_none() ||
+            (class_file.super_class != 0 && class_file.super_name().is_none()) {
+        return Err(ResolutionError_ClassFormat(fmt!("%s has an invalid this_class, super_class or interfaces",
+            name)));
+    }
+    Ok(class_file)
+}
+
+fn class_name(class_file: &r/ClassFile) -> &r/str {
+    class_file.class_name().get()
+}
+
+fn interfaces(class_file: &r/ClassFile) -> ~[&r/str] {
+    class_file.interfaces().get()
+}
+
+fn member_str(owner: &str, name: &str, descriptor: &str) -> ~str {
+    fmt!("%s.%s:%s", owner, name, descriptor)
+}
+
+fn find_field(class_file: &ClassFile, name: &str, descriptor: &str) -> Option<uint> {
+    let cp = &class_file.constant_pool;
+    for class_file.fields.eachi |index, field| {
+        if matches(field.name(cp), name) && matches(field.descriptor(cp), descriptor) {
+            return Some(index);
+        }
+    }
+    None
+}
+
+fn find_method(class_file: &ClassFile, name: &str, descriptor: &str) -> Option<uint> {
+    let cp = &class_file.constant_pool;
+    for class_file.methods.eachi |index, method| {
+        if matches(method.name(cp), name) && matches(method.descriptor(cp), descriptor) {
+            return Some(index);
+        }
+    }
+    None
+}
+
+fn matches(strval: Option<&str>, expected: &str) -> bool {
+    match strval {
+        Some(strval) => str::eq_slice(strval, expected),
+        None => false
+    }
+}
+
+//JVMS 2.9.3, a native varargs method taking a single Object[] in MethodHandle or VarHandle matches any descriptor,
+//whatever it returns
+fn find_signature_polymorphic(class_file: &ClassFile, name: &str) -> Option<uint> {
+    let class_name = class_name(class_file);
+    if !str::eq_slice(class_name, "java/lang/invoke/MethodHandle") &&
+            !str::eq_slice(class_name, "java/lang/invoke/VarHandle") {
+        return None;
+    }
+    let cp = &class_file.constant_pool;
+    let mut found = None;
+    for class_file.methods.eachi |index, method| {
+        if matches(method.name(cp), name) {
+            if found.is_some() {
+                //the name has to be unique to the class
+                return None;
+            }
+            let flags = method.flags();
+            let takes_object_array = match method.descriptor(cp) {
+                Some(descriptor) => descriptor.starts_with("([Ljava/lang/Object;)"),
+                None => false
+            };
+            if flags.contains(MethodAccess_VarArgs) && flags.contains(MethodAccess_Native) && takes_object_array {
+                found = Some(index);
+            } else {
+                return None;
+            }
+        }
+    }
+    found
+}
+
+//5.4.3.2, the class itself, then its superinterfaces, then its superclass
+pub fn resolve_field(class_path: &ClassPath, owner: &str, name: &str, descriptor: &str)
+        -> Result<ResolvedField, ResolutionError> {
+    let class_file = try!(load(class_path, owner));
+    match try!(lookup_field(class_path, class_file, name, descriptor, &mut ~[], &mut LinearMap())) {
+        Some(resolved) => Ok(resolved),
+        None => Err(ResolutionError_NoSuchField(member_str(owner, name, descriptor)))
+    }
+}
+
+//path holds the classes being searched further up the recursion, meeting one of them again is a cycle, while
+//searched holds those already searched without a match, which diamond shaped interface graphs reach twice
+fn lookup_field(class_path: &ClassPath, class_file: @ClassFile, name: &str, descriptor: &str, path: &mut ~[~str],
+        searched: &mut LinearMap<~str, ()>) -> Result<Option<ResolvedField>, ResolutionError> {
+    let class_name = str::from_slice(class_name(class_file));
+    if vec::contains(*path, &class_name) {
+        return Err(ResolutionError_ClassCircularity(class_name));
+    }
+    if searched.contains_key(&class_name) {
+        return Ok(None);
+    }
+    match find_field(class_file, name, descriptor) {
+        Some(index) => return Ok(Some(ResolvedField { class_file: class_file, index: index })),
+        None => ()
+    }
+    path.push(copy class_name);
+    for interfaces(class_file).each |interface| {
+        let interface_file = try!(load(class_path, *interface));
+        match try!(lookup_field(class_path, interface_file, name, descriptor, path, searched)) {
+            Some(resolved) => return Ok(Some(resolved)),
+            None => ()
+        }
+    }
+    let resolved = match class_file.super_name() {
+        Some(super_name) =>
+            try!(lookup_field(class_path, try!(load(class_path, super_name)), name, descriptor, path, searched)),
+        None => None
+    };
+    path.pop();
+    searched.insert(class_name, ());
+    Ok(resolved)
+}
+
+//5.4.3.3, the owner (java/lang/Object for arrays) and its superclasses, then its superinterfaces
+pub fn resolve_method(class_path: &ClassPath, owner: &str, name: &str, descriptor: &str)
+        -> Result<ResolvedMethod, ResolutionError> {
+    let class_file = try!(load(class_path, if owner.starts_with("[") { "java/lang/Object" } else { owner }));
+    if class_file.flags().contains(ClassAccess_Interface) {
+        return Err(ResolutionError_IncompatibleClassChange(fmt!("found interface %s, but class was expected",
+            class_name(class_file))));
+    }
+    let mut current = class_file;
+    let mut seen = ~[];
+    loop {
+        let current_name = str::from_slice(class_name(current));
+        if vec::contains(seen, &current_name) {
+            return Err(ResolutionError_ClassCircularity(current_name));
+        }
+        seen.push(current_name);
+        match find_signature_polymorphic(current, name) {
+            Some(index) => return Ok(ResolvedMethod { class_file: current, index: index }),
+            None => ()
+        }
+        match find_method(current, name, descriptor) {
+            Some(index) => return Ok(ResolvedMethod { class_file: current, index: index }),
+            None => ()
+        }
+        current = match current.super_name() {
+            Some(super_name) => try!(load(class_path, super_name)),
+            None => break
+        };
+    }
+    match try!(lookup_superinterface_method(class_path, class_file, name, descriptor)) {
+        Some(resolved) => Ok(resolved),
+        None => Err(ResolutionError_NoSuchMethod(member_str(owner, name, descriptor)))
+    }
+}
+
+//5.4.3.4, the owner, then the public instance methods of java/lang/Object, then its superinterfaces
+pub fn resolve_interface_method(class_path: &ClassPath, owner: &str, name: &str, descriptor: &str)
+        -> Result<ResolvedMethod, ResolutionError> {
+    let class_file = try!(load(class_path, owner));
+    if !class_file.flags().contains(ClassAccess_Interface) {
+        return Err(ResolutionError_IncompatibleClassChange(fmt!("found class %s, but interface was expected",
+            class_name(class_file))));
+    }
+    match find_method(class_file, name, descriptor) {
+        Some(index) => return Ok(ResolvedMethod { class_file: class_file, index: index }),
+        None => ()
+    }
+    let object = try!(load(class_path, "java/lang/Object"));
+    match find_method(object, name, descriptor) {
+        Some(index) => {
+            let flags = object.methods[index].flags();
+            if flags.contains(MethodAccess_Public) && !flags.contains(MethodAccess_Static) {
+                return Ok(ResolvedMethod { class_file: object, index: index });
+            }
+        },
+        None => ()
+    }
+    match try!(lookup_superinterface_method(class_path, class_file, name, descriptor)) {
+        Some(resolved) => Ok(resolved),
+        None => Err(ResolutionError_NoSuchMethod(member_str(owner, name, descriptor)))
+    }
+}
+
+//the single maximally-specific non-abstract superinterface method when there is one, otherwise any non-private
+//instance method of a superinterface
+fn lookup_superinterface_method(class_path: &ClassPath, class_file: @ClassFile, name: &str, descriptor: &str)
+        -> Result<Option<ResolvedMethod>, ResolutionError> {
+    let interfaces = try!(superinterfaces(class_path, class_file));
+    let mut hierarchy = ClassHierarchy();
+    for interfaces.each |interface| {
+        hierarchy.add_class(*interface);
+    }
+    //superinterfaces stops at interfaces it has seen, a cycle among them only shows in the hierarchy
+    let cycles = hierarchy.cycles();
+    if !cycles.is_empty() {
+        return Err(ResolutionError_ClassCircularity(str::from_slice(cycles[0][0])));
+    }
+    let mut candidates = ~[];
+    for interfaces.each |interface| {
+        match find_method(*interface, name, descriptor) {
+            Some(index) => {
+                let flags = interface.methods[index].flags();
+                if !flags.contains(MethodAccess_Private) && !flags.contains(MethodAccess_Static) {
+                    candidates.push(ResolvedMethod { class_file: *interface, index: index });
+                }
+            },
+            None => ()
+        }
+    }
+    if candidates.is_empty() {
+        return Ok(None);
+    }
+    //a candidate is maximally-specific when no other candidate's interface is a subinterface of its own
+    let mut maximally_specific = ~[];
+    for candidates.eachi |index, candidate| {
+        let mut overridden = false;
+        for candidates.eachi |other_index, other| {
+            if other_index != index &&
+                    hierarchy.is_subtype_of(class_name(other.class_file), class_name(candidate.class_file)) {
+                overridden = true;
+                break;
+            }
+        }
+        if !overridden {
+            maximally_specific.push(index);
+        }
+    }
+    let mut non_abstract = ~[];
+    for maximally_specific.each |index| {
+        if !candidates[*index].method().flags().contains(MethodAccess_Abstract) {
+            non_abstract.push(*index);
+        }
+    }
+    if non_abstract.len() == 1 {
+        return Ok(Some(copy candidates[non_abstract[0]]));
+    }
+    //the spec allows any of them, the first maximally-specific one keeps the choice stable
+    Ok(Some(copy candidates[maximally_specific[0]]))
+}
+
+//every interface the class implements or inherits, breadth first without duplicates. Only the class and its
+//superclasses are followed to their superclass, the superclass of an interface is always java/lang/Object
+fn superinterfaces(class_path: &ClassPath, class_file: @ClassFile) -> Result<~[@ClassFile], ResolutionError> {
+    let mut found: ~[@ClassFile] = ~[];
+    let mut superclasses = ~[str::from_slice(class_name(class_file))];
+    let mut queue = ~[class_file];
+    let mut next = 0u;
+    while next < queue.len() {
+        let current = queue[next];
+        next += 1;
+        for interfaces(current).each |interface| {
+            if !found.any(|existing| str::eq_slice(class_name(*existing), *interface)) {
+                let interface_file = try!(load(class_path, *interface));
+                found.push(interface_file);
+                queue.push(interface_file);
+            }
+        }
+        if current.flags().contains(ClassAccess_Interface) {
+            loop;
+        }
+        match current.super_name() {
+            Some(super_name) => {
+                let super_name = str::from_slice(super_name);
+                if vec::contains(superclasses, &super_name) {
+                    return Err(ResolutionError_ClassCircularity(super_name));
+                }
+                queue.push(try!(load(class_path, super_name)));
+                superclasses.push(super_name);
+            },
+            None => ()
+        }
+    }
+    Ok(found)
+}
+
+//resolves a FieldRef constant of the pool
+pub fn resolve_field_ref(class_path: &ClassPath, cp: &ConstantPool, index: u16)
+        -> Result<ResolvedField, ResolutionError> {
+    match cp.get_member_ref(index) {
+        Some(member_ref) if member_ref.is_field() =>
+            resolve_field(class_path, member_ref.owner, member_ref.name, member_ref.descriptor),
+        _ => Err(ResolutionError_IncompatibleClassChange(fmt!("#%u is not a field reference", index as uint)))
+    }
+}
+
+//resolves a MethodRef or InterfaceMethodRef constant of the pool
+pub fn resolve_method_ref(class_path: &ClassPath, cp: &ConstantPool, index: u16)
+        -> Result<ResolvedMethod, ResolutionError> {
+    match cp.get_member_ref(index) {
+        Some(member_ref) => match member_ref.kind {
+            MemberRef_Method => resolve_method(class_path, member_ref.owner, member_ref.name, member_ref.descriptor),
+            MemberRef_InterfaceMethod =>
+                resolve_interface_method(class_path, member_ref.owner, member_ref.name, member_ref.descriptor),
+            MemberRef_Field =>
+                Err(ResolutionError_IncompatibleClassChange(fmt!("#%u is not a method reference", index as uint)))
+        },
+        None => Err(ResolutionError_IncompatibleClassChange(fmt!("#%u is not a method reference", index as uint)))
+    }
+}
@@ -0,0 +1,397 @@
+use class_file::{ClassFormatError, FormatError_BadDescriptor};
+use descriptor::*;
+
+//generic signatures per JVMS 4.7.9.1, errors are FormatError_BadDescriptor with the char position in the signature
+
+pub enum TypeSignature {
+    //only the primitive FieldType variants appear here
+    Sig_Base(FieldType),
+    Sig_Class(ClassTypeSignature),
+    Sig_TypeVariable(~str /*name*/),
+    Sig_Array(~TypeSignature /*component_type*/)
+}
+
+pub struct ClassTypeSignature {
+    //slash separated with no trailing slash, empty for the default package
+    package: ~str,
+    //the outermost class first, then each inner class
+    classes: ~[SimpleClassTypeSignature]
+}
+
+pub struct SimpleClassTypeSignature {
+    name: ~str,
+    type_arguments: ~[TypeArgument]
+}
+
+pub enum TypeArgument {
+    TypeArg_Wildcard,
+    TypeArg_Exact(TypeSignature),
+    TypeArg_Extends(TypeSignature),
+    TypeArg_Super(TypeSignature)
+}
+
+pub struct TypeParameter {
+    name: ~str,
+    class_bound: Option<TypeSignature>,
+    interface_bounds: ~[TypeSignature]
+}
+
+pub struct ClassSignature {
+    type_parameters: ~[TypeParameter],
+    superclass: ClassTypeSignature,
+    superinterfaces: ~[ClassTypeSignature]
+}
+
+pub struct MethodSignature {
+    type_parameters: ~[TypeParameter],
+    parameters: ~[TypeSignature],
+    //None for void
+    return_type: Option<TypeSignature>,
+    throws: ~[TypeSignature]
+}
+
+impl TypeSignature {
+    pub fn to_str(&self) -> ~str {
+        match *self {
+            Sig_Base(ref base) => base.to_str(),
+            Sig_Class(ref class) => class.to_str(),
+            Sig_TypeVariable(ref name) => ~"T" + *name + ~";",
+            Sig_Array(ref component_type) => ~"[" + component_type.to_str()
+        }
+    }
+}
+
+impl ClassTypeSignature {
+    //the erased internal name, e.g. java/util/Map$Entry
+    pub fn internal_name(&self) -> ~str {
+        let mut names: ~[~str] = ~[];
+        for self.classes.each |class| {
+            names.push(copy class.name);
+        }
+        let name = str::connect(names, "$");
+        if self.package.is_empty() { name } else { self.package + ~"/" + name }
+    }
+
+    pub fn to_str(&self) -> ~str {
+        let mut strval = ~"L";
+        if !self.package.is_empty() {
+            str::push_str(&mut strval, self.package);
+            str::push_char(&mut strval, '/');
+        }
+        for self.classes.eachi |i, class| {
+            if i > 0 {
+                str::push_char(&mut strval, '.');
+            }
+            str::push_str(&mut strval, class.name);
+            if !class.type_arguments.is_empty() {
+                str::push_char(&mut strval, '<');
+                for class.type_arguments.each |argument| {
+                    str::push_str(&mut strval, argument.to_str());
+                }
+                str::push_char(&mut strval, '>');
+            }
+        }
+        str::push_char(&mut strval, ';');
+        strval
+    }
+}
+
+impl TypeArgument {
+    pub fn to_str(&self) -> ~str {
+        match *self {
+            TypeArg_Wildcard => ~"*",
+            TypeArg_Exact(ref signature) => signature.to_str(),
+            TypeArg_Extends(ref signature) => ~"+" + signature.to_str(),
+            TypeArg_Super(ref signature) => ~"-" + signature.to_str()
+        }
+    }
+}
+
+impl TypeParameter {
+    pub fn to_str(&self) -> ~str {
+        let mut strval = copy self.name;
+        str::push_char(&mut strval, ':');
+        match self.class_bound {
+            Some(ref bound) => str::push_str(&mut strval, bound.to_str()),
+            None => ()
+        }
+        for self.interface_bounds.each |bound| {
+            str::push_char(&mut strval, ':');
+            str::push_str(&mut strval, bound.to_str());
+        }
+        strval
+    }
+}
+
+fn type_parameters_to_str(type_parameters: &[TypeParameter]) -> ~str {
+    if type_parameters.is_empty() {
+        return ~"";
+    }
+    let mut strval = ~"<";
+    for type_parameters.each |type_parameter| {
+        str::push_str(&mut strval, type_parameter.to_str());
+    }
+    str::push_char(&mut strval, '>');
+    strval
+}
+
+impl ClassSignature {
+    pub fn to_str(&self) -> ~str {
+        let mut strval = type_parameters_to_str(self.type_parameters);
+        str::push_str(&mut strval, self.superclass.to_str());
+        for self.superinterfaces.each |superinterface| {
+            str::push_str(&mut strval, superinterface.to_str());
+        }
+        strval
+    }
+}
+
+impl MethodSignature {
+    pub fn to_str(&self) -> ~str {
+        let mut strval = type_parameters_to_str(self.type_parameters);
+        str::push_char(&mut strval, '(');
+        for self.parameters.each |parameter| {
+            str::push_str(&mut strval, parameter.to_str());
+        }
+        str::push_char(&mut strval, ')');
+        match self.return_type {
+            Some(ref return_type) => str::push_str(&mut strval, return_type.to_str()),
+            None => str::push_char(&mut strval, 'V')
+        }
+        for self.throws.each |throw| {
+            str::push_char(&mut strval, '^');
+            str::push_str(&mut strval, throw.to_str());
+        }
+        strval
+    }
+}
+
+pub fn parse_class_signature(signature: &str) -> Result<ClassSignature, ClassFormatError> {
+    let parser = Parser(signature, "class signature");
+    let type_parameters = try!(parser.type_parameters());
+    let superclass = try!(parser.class_type_signature());
+    let mut superinterfaces: ~[ClassTypeSignature] = ~[];
+    while !parser.at_end() {
+        superinterfaces.push(try!(parser.class_type_signature()));
+    }
+    Ok(ClassSignature {
+        type_parameters: type_parameters,
+        superclass: superclass,
+        superinterfaces: superinterfaces
+    })
+}
+
+pub fn parse_method_signature(signature: &str) -> Result<MethodSignature, ClassFormatError> {
+    let parser = Parser(signature, "method signature");
+    let type_parameters = try!(parser.type_parameters());
+    try!(parser.expect('('));
+    let mut parameters: ~[TypeSignature] = ~[];
+    while parser.peek() != Some(')') {
+        parameters.push(try!(parser.java_type_signature()));
+    }
+    try!(parser.expect(')'));
+    let return_type = if parser.peek() == Some('V') {
+        parser.pos += 1;
+        None
+    } else {
+        Some(try!(parser.java_type_signature()))
+    };
+    let mut throws: ~[TypeSignature] = ~[];
+    while parser.peek() == Some('^') {
+        parser.pos += 1;
+        match parser.peek() {
+            Some('L') => throws.push(Sig_Class(try!(parser.class_type_signature()))),
+            Some('T') => throws.push(try!(parser.type_variable_signature())),
+            _ => return Err(parser.error(~"expected a class or type variable after '^'"))
+        }
+    }
+    try!(parser.expect_end());
+    Ok(MethodSignature {
+        type_parameters: type_parameters,
+        parameters: parameters,
+        return_type: return_type,
+        throws: throws
+    })
+}
+
+pub fn parse_field_signature(signature: &str) -> Result<TypeSignature, ClassFormatError> {
+    let parser = Parser(signature, "field signature");
+    let field_signature = try!(parser.reference_type_signature());
+    try!(parser.expect_end());
+    Ok(field_signature)
+}
+
+struct Parser {
+    chars: ~[char],
+    mut pos: uint,
+    structure: ~str
+}
+
+fn Parser(signature: &str, structure: &str) -> Parser {
+    Parser { chars: str::chars(signature), pos: 0, structure: str::from_slice(structure) }
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        if self.pos < self.chars.len() { Some(self.chars[self.pos]) } else { None }
+    }
+
+    fn at_end(&self) -> bool { self.pos >= self.chars.len() }
+
+    fn error(&self, reason: ~str) -> ClassFormatError {
+        FormatError_BadDescriptor(self.pos, copy self.structure, reason)
+    }
+
+    fn expect(&self, c: char) -> Result<(), ClassFormatError> {
+        if self.peek() != Some(c) {
+            return Err(self.error(fmt!("expected '%c'", c)));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn expect_end(&self) -> Result<(), ClassFormatError> {
+        if !self.at_end() {
+            return Err(self.error(~"trailing characters"));
+        }
+        Ok(())
+    }
+
+    fn identifier(&self) -> Result<~str, ClassFormatError> {
+        let mut name = ~"";
+        loop {
+            match self.peek() {
+                Some('.') | Some(';') | Some('[') | Some('/') | Some('<') | Some('>') | Some(':') | None => break,
+                Some(c) => {
+                    str::push_char(&mut name, c);
+                    self.pos += 1;
+                }
+            }
+        }
+        if name.is_empty() {
+            return Err(self.error(~"expected an identifier"));
+        }
+        Ok(name)
+    }
+
+    fn type_parameters(&self) -> Result<~[TypeParameter], ClassFormatError> {
+        let mut type_parameters: ~[TypeParameter] = ~[];
+        if self.peek() != Some('<') {
+            return Ok(type_parameters);
+        }
+        self.pos += 1;
+        while self.peek() != Some('>') {
+            let name = try!(self.identifier());
+            try!(self.expect(':'));
+            //the class bound may be empty when only interface bounds are given
+            let class_bound = match self.peek() {
+                Some('L') | Some('T') | Some('[') => Some(try!(self.reference_type_signature())),
+                _ => None
+            };
+            let mut interface_bounds: ~[TypeSignature] = ~[];
+            while self.peek() == Some(':') {
+                self.pos += 1;
+                interface_bounds.push(try!(self.reference_type_signature()));
+            }
+            type_parameters.push(TypeParameter {
+                name: name,
+                class_bound: class_bound,
+                interface_bounds: interface_bounds
+            });
+        }
+        self.pos += 1;
+        if type_parameters.is_empty() {
+            return Err(self.error(~"empty type parameter list"));
+        }
+        Ok(type_parameters)
+    }
+
+    fn java_type_signature(&self) -> Result<TypeSignature, ClassFormatError> {
+        let base = match self.peek() {
+            Some('B') => Type_Byte,
+            Some('C') => Type_Char,
+            Some('D') => Type_Double,
+            Some('F') => Type_Float,
+            Some('I') => Type_Int,
+            Some('J') => Type_Long,
+            Some('S') => Type_Short,
+            Some('Z') => Type_Boolean,
+            _ => return self.reference_type_signature()
+        };
+        self.pos += 1;
+        Ok(Sig_Base(base))
+    }
+
+    fn reference_type_signature(&self) -> Result<TypeSignature, ClassFormatError> {
+        match self.peek() {
+            Some('L') => Ok(Sig_Class(try!(self.class_type_signature()))),
+            Some('T') => self.type_variable_signature(),
+            Some('[') => {
+                self.pos += 1;
+                Ok(Sig_Array(~try!(self.java_type_signature())))
+            },
+            _ => Err(self.error(~"expected a reference type"))
+        }
+    }
+
+    fn type_variable_signature(&self) -> Result<TypeSignature, ClassFormatError> {
+        try!(self.expect('T'));
+        let name = try!(self.identifier());
+        try!(self.expect(';'));
+        Ok(Sig_TypeVariable(name))
+    }
+
+    fn class_type_signature(&self) -> Result<ClassTypeSignature, ClassFormatError> {
+        try!(self.expect('L'));
+        //identifiers followed by '/' make up the package
+        let mut package = ~"";
+        let mut name = try!(self.identifier());
+        while self.peek() == Some('/') {
+            self.pos += 1;
+            if !package.is_empty() {
+                str::push_char(&mut package, '/');
+            }
+            str::push_str(&mut package, name);
+            name = try!(self.identifier());
+        }
+        let mut classes = ~[SimpleClassTypeSignature { name: name, type_arguments: try!(self.type_arguments()) }];
+        while self.peek() == Some('.') {
+            self.pos += 1;
+            let inner_name = try!(self.identifier());
+            classes.push(SimpleClassTypeSignature { name: inner_name, type_arguments: try!(self.type_arguments()) });
+        }
+        try!(self.expect(';'));
+        Ok(ClassTypeSignature { package: package, classes: classes })
+    }
+
+    fn type_arguments(&self) -> Result<~[TypeArgument], ClassFormatError> {
+        let mut type_arguments: ~[TypeArgument] = ~[];
+        if self.peek() != Some('<') {
+            return Ok(type_arguments);
+        }
+        self.pos += 1;
+        while self.peek() != Some('>') {
+            let argument = match self.peek() {
+                Some('*') => {
+                    self.pos += 1;
+                    TypeArg_Wildcard
+                },
+                Some('+') => {
+                    self.pos += 1;
+                    TypeArg_Extends(try!(self.reference_type_signature()))
+                },
+                Some('-') => {
+                    self.pos += 1;
+                    TypeArg_Super(try!(self.reference_type_signature()))
+                },
+                _ => TypeArg_Exact(try!(self.reference_type_signature()))
+            };
+            type_arguments.push(argument);
+        }
+        self.pos += 1;
+        if type_arguments.is_empty() {
+            return Err(self.error(~"empty type argument list"));
+        }
+        Ok(type_arguments)
+    }
+}